@@ -20,6 +20,14 @@ pub struct BingWallpaperArguments {
     #[clap(long = "loop", short = 'l', help = "Keep application running. Looking for new wallpaper every 900 seconds (15 minutes)\nYou can override value with `loop_interval_second` in the configuration file")]
     pub(crate) must_loop: bool,
 
+    /// If `true`, the wallpaper must be installed as display manager greeter (login screen) background.
+    #[clap(long = "apply-greeter", help = "Install wallpaper as login screen background (requires root privileges)")]
+    pub(crate) apply_greeter: bool,
+
+    /// Filesystem root used to install the greeter background.
+    #[clap(long = "greeter-root", help = "Use another filesystem root for --apply-greeter", requires = "apply_greeter")]
+    pub(crate) greeter_root: Option<String>,
+
     /// If `true`, the application will don't display console
     #[cfg(target_os = "windows")]
     #[clap(long = "nowindow", short = 'w', help = "Don't display console when not run from a CLI")]
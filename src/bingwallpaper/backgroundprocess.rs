@@ -0,0 +1,155 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// Time given to the process to fail (ie: unreadable file, no display) before it is considered started.
+const BACKGROUND_PROCESS_STARTUP_DELAY: Duration = Duration::from_millis(500);
+
+/// A process displaying something as long as it runs (ie: swaybg, mpvpaper).
+///
+/// A new process is started for each change, then the previous one is stopped, so that nothing flickers. Its PID is
+/// kept in a file, so that the process started by a previous run is stopped too.
+pub struct BackgroundProcess {
+    name: &'static str,
+    process_names: &'static [&'static str],
+    process: Mutex<Option<(Child, String)>>,
+}
+
+impl BackgroundProcess {
+    /// Creates a new instance, without process.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the process, used in the PID file name (ie: "swaybg")
+    /// * `process_names` - The names the process may have, to check the PID of a previous run
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use backgroundprocess::BackgroundProcess;
+    ///
+    /// static SWAYBG_PROCESS: BackgroundProcess = BackgroundProcess::new("swaybg", &["swaybg"]);
+    /// ```
+    pub const fn new(name: &'static str, process_names: &'static [&'static str]) -> BackgroundProcess {
+        BackgroundProcess { name, process_names, process: Mutex::new(None) }
+    }
+
+    /// Checks whether the process started for the given key is still running.
+    ///
+    /// # Arguments
+    /// * `key` - What the process displays (ie: the location and modification time of the video)
+    pub fn is_running(&self, key: &str) -> bool {
+        match self.process.lock() {
+            Err(_) => false,
+            Ok(mut process) => process
+                .as_mut()
+                .is_some_and(|(child, running_key)| running_key == key && matches!(child.try_wait(), Ok(None))),
+        }
+    }
+
+    /// Starts the process, then stops the previous one once the new one is running.
+    ///
+    /// # Arguments
+    /// * `command` - The command starting the process
+    /// * `key` - What the process displays (ie: the location and modification time of the video)
+    pub fn start(&self, command: &mut Command, key: String) -> Result<(), String> {
+        let program = command.get_program().to_string_lossy().to_string();
+        let mut child = match command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn() {
+            Err(error) => return Err(format!("Can't run {:?}: {:?}", program, error)),
+            Ok(child) => child,
+        };
+
+        thread::sleep(BACKGROUND_PROCESS_STARTUP_DELAY);
+        if let Ok(Some(status)) = child.try_wait() {
+            return Err(format!("{:?} failed ({})", program, status));
+        }
+
+        let mut process = match self.process.lock() {
+            Err(error) => return Err(format!("Can't access {} process: {:?}", self.name, error)),
+            Ok(process) => process,
+        };
+        self.stop_previous(process.take().map(|(child, _)| child));
+
+        if let Err(error) = fs::write(self.pid_filename(), child.id().to_string()) {
+            println!("Can't write {} PID file: {:?}", self.name, error);
+        }
+        *process = Some((child, key));
+
+        Ok(())
+    }
+
+    /// Stops the process, if running, including the one started by a previous run.
+    pub fn stop(&self) {
+        match self.process.lock() {
+            Err(error) => println!("Can't access {} process: {:?}", self.name, error),
+            Ok(mut process) => {
+                self.stop_previous(process.take().map(|(child, _)| child));
+                let _ = fs::remove_file(self.pid_filename());
+            }
+        }
+    }
+
+    /// Returns the location of the file holding the PID of the running process.
+    fn pid_filename(&self) -> PathBuf {
+        let runtime_directory = env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from).unwrap_or_else(env::temp_dir);
+        runtime_directory.join(format!("bingwallpaper-{}.pid", self.name))
+    }
+
+    /// Stops the previous process, started by this instance or, without one, by a previous run.
+    fn stop_previous(&self, previous_child: Option<Child>) {
+        if let Some(mut previous_child) = previous_child {
+            // Started by this instance: also reaped
+            let _ = previous_child.kill();
+            let _ = previous_child.wait();
+            return;
+        }
+
+        let pid = match fs::read_to_string(self.pid_filename()) {
+            Err(_) => return,
+            Ok(pid) => pid.trim().to_string(),
+        };
+
+        // The PID may have been reused by another process since
+        let process_name = fs::read_to_string(format!("/proc/{}/comm", pid)).unwrap_or_default();
+        if pid.bytes().all(|byte| byte.is_ascii_digit()) && self.process_names.contains(&process_name.trim()) {
+            if let Err(error) = Command::new("kill").arg(&pid).status() {
+                println!("Can't stop previous {} process: {:?}", self.name, error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::process::Command;
+
+    use super::BackgroundProcess;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn start_then_replace_and_stop() {
+        static SLEEP_PROCESS: BackgroundProcess = BackgroundProcess::new("test-sleep", &["sleep"]);
+
+        SLEEP_PROCESS.start(Command::new("sleep").arg("30"), "first".to_string()).unwrap();
+        let first_pid = fs::read_to_string(SLEEP_PROCESS.pid_filename()).unwrap();
+        assert!(SLEEP_PROCESS.is_running("first"));
+        assert!(!SLEEP_PROCESS.is_running("second"));
+
+        // The previous process is stopped once the new one runs
+        SLEEP_PROCESS.start(Command::new("sleep").arg("30"), "second".to_string()).unwrap();
+        assert!(fs::metadata(format!("/proc/{}", first_pid)).is_err());
+        assert!(SLEEP_PROCESS.is_running("second"));
+
+        // A failing process is reported, and the running one is kept
+        assert!(SLEEP_PROCESS.start(&mut Command::new("false"), "third".to_string()).is_err());
+        assert!(SLEEP_PROCESS.is_running("second"));
+
+        SLEEP_PROCESS.stop();
+        assert!(!SLEEP_PROCESS.is_running("second"));
+        assert!(!SLEEP_PROCESS.pid_filename().exists());
+    }
+}
@@ -1,14 +1,19 @@
-use std::fs::File;
-use std::io::Cursor;
+use std::fs::{self, File};
+use std::path::Path;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use reqwest::blocking::Client;
 use serde_derive::Deserialize;
+use serde_json::Value;
+
+/// Maximum time to download a video, much larger than an image.
+const VIDEO_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
 
 /// Bing API HTTP client.
 pub struct BingAPIClient {
     api_endpoint: String,
     http_client: Client,
+    market: Option<String>,
 }
 
 /// Bing API "Images Archives": root object.
@@ -35,6 +40,15 @@ pub struct BingAPIImagesArchiveImage {
 
     /// Date on which the image is proposed as wallpaper of the day.
     pub startdate: String,
+
+    /// Video published next to the image, only on some days. Its sources are read leniently, the format is not
+    /// documented.
+    #[serde(default)]
+    pub vid: Option<Value>,
+
+    /// URL of the video source best matching the requested dimension, if a video is published.
+    #[serde(skip)]
+    pub video_url: Option<String>,
 }
 
 impl BingAPIClient {
@@ -42,15 +56,16 @@ impl BingAPIClient {
     ///
     /// # Arguments
     /// * `proxy_url` - URL to the proxy to use (ie: http://proxy-ip:8080)
+    /// * `market` - Market to retrieve images for (ie: en-US)
     ///
     /// # Examples
     ///
     /// ```
     /// use bingwallpaper::BingAPIClient;
-    /// let instance = BingAPIClient::new(proxy_url);
+    /// let instance = BingAPIClient::new(proxy_url, market);
     /// ```
     #[must_use]
-    pub fn new(proxy_url: Option<String>) -> BingAPIClient {
+    pub fn new(proxy_url: Option<String>, market: Option<String>) -> BingAPIClient {
         // Configures HTTP client
         let mut client_builder = Client::builder()
             .timeout(Duration::from_secs(15))
@@ -67,6 +82,7 @@ impl BingAPIClient {
         BingAPIClient {
             api_endpoint: String::from("https://www.bing.com"),
             http_client: client_builder.build().unwrap(),
+            market,
         }
     }
 
@@ -94,8 +110,13 @@ impl BingAPIClient {
             &img_dimension_width,
             &img_dimension_height);
 
-        // Call Bing API
-        let http_response = match self.http_client.get(image_archive_api_uri).send() {
+        // Call Bing API (the market comes from the configuration, it is encoded)
+        let mut http_request = self.http_client.get(image_archive_api_uri);
+        if let Some(market) = &self.market {
+            http_request = http_request.query(&[("mkt", market)]);
+        }
+
+        let http_response = match http_request.send() {
             Err(error) => return Err(format!("Can't fetch Bing API: {:?}", error)),
             Ok(http_response) => http_response,
         };
@@ -115,6 +136,8 @@ impl BingAPIClient {
             copyright: image.copyright.clone(),
             copyrightlink: image.copyrightlink.clone(),
             startdate: image.startdate.clone(),
+            vid: None,
+            video_url: self.best_video_source(image.vid.as_ref(), img_dimension_height),
         })
     }
 
@@ -122,7 +145,6 @@ impl BingAPIClient {
     ///
     /// # Arguments
     /// * `image` - The image to download
-    /// * `target` - The location where to save image
     ///
     /// # Examples
     ///
@@ -130,18 +152,262 @@ impl BingAPIClient {
     /// use bingwallpaper::BingAPIClient;
     /// let instance = BingAPIClient::new();
     ///
-    /// instance.download_image(image, "/tmp/out.png");
+    /// let image_content = instance.download_image(image)?;
     /// ```
-    pub fn download_image(&self, image: &BingAPIImagesArchiveImage, target: &String) -> Result<(), String> {
+    pub fn download_image(&self, image: &BingAPIImagesArchiveImage) -> Result<Vec<u8>, String> {
         let image_content_uri: String = format!("{0}{1}", &self.api_endpoint, &image.url);
-        let image_response = self.http_client.get(image_content_uri).send().unwrap();
-        let mut output_file = File::create(target).unwrap();
-        let mut image_content = Cursor::new(image_response.bytes().unwrap());
+        let image_response = match self.http_client
+            .get(image_content_uri)
+            .send()
+            .and_then(|response| response.error_for_status()) {
+            Err(error) => return Err(format!("Can't download image: {:?}", error)),
+            Ok(image_response) => image_response,
+        };
+
+        match image_response.bytes() {
+            Err(error) => Err(format!("Can't download image: {:?}", error)),
+            Ok(image_content) => Ok(image_content.to_vec()),
+        }
+    }
+
+    /// Downloads video into a file. The file is only replaced once the video is fully downloaded.
+    ///
+    /// # Arguments
+    /// * `video_url` - The URL of the video, as returned by `retrieve_latest_image`
+    /// * `filename` - The location where to store the video
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bingwallpaper::BingAPIClient;
+    /// let instance = BingAPIClient::new();
+    ///
+    /// instance.download_video(&video_url, "/home/user/.bingwallpaper-video.mp4")?;
+    /// ```
+    pub fn download_video(&self, video_url: &str, filename: &str) -> Result<(), String> {
+        let mut video_response = match self.http_client
+            .get(video_url)
+            .timeout(VIDEO_DOWNLOAD_TIMEOUT)
+            .send()
+            .and_then(|response| response.error_for_status()) {
+            Err(error) => return Err(format!("Can't download video: {:?}", error)),
+            Ok(video_response) => video_response,
+        };
+
+        let partial_filename = format!("{}.part", filename);
+        let copy_result = File::create(&partial_filename)
+            .map_err(|error| format!("{:?}", error))
+            .and_then(|mut file| video_response.copy_to(&mut file).map_err(|error| format!("{:?}", error)));
+        if let Err(error) = copy_result {
+            let _ = fs::remove_file(&partial_filename);
+            return Err(format!("Can't download video: {}", error));
+        }
+
+        match fs::rename(&partial_filename, filename) {
+            Err(error) => Err(format!("Can't store video: {:?}", error)),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Returns the URL of the video source to download: the smallest one at least as high as requested, else the
+    /// highest one. Sources are lists of strings (ie: `["eot", "video/mp4", "//az29176.vo.msecnd.net/..._1080_HD.mp4"]`),
+    /// the resolution is read from the file name.
+    ///
+    /// # Arguments
+    /// * `vid` - The video object of the image, if any
+    /// * `img_dimension_height` - Requested image dimension "height"
+    fn best_video_source(&self, vid: Option<&Value>, img_dimension_height: u32) -> Option<String> {
+        let sources = vid?.get("sources")?.as_array()?;
+
+        let mut video_sources: Vec<(String, Option<u32>)> = sources
+            .iter()
+            .filter_map(|source| source.as_array())
+            .filter_map(|source| {
+                let fields: Vec<&str> = source.iter().filter_map(|field| field.as_str()).collect();
+                let is_video = fields.iter().any(|field| {
+                    let path = field.split('?').next().unwrap_or(field);
+                    field.starts_with("video/") || path.ends_with(".mp4") || path.ends_with(".webm")
+                });
+
+                fields
+                    .into_iter()
+                    .find(|field| field.starts_with("http") || field.starts_with('/'))
+                    .filter(|_| is_video)
+            })
+            .map(|url| (self.absolute_url(url), BingAPIClient::video_height(url)))
+            .collect();
+
+        // Smallest sufficient source first, then the higher ones, unknown resolutions last
+        video_sources.sort_by_key(|(_, height)| match height {
+            Some(height) if *height >= img_dimension_height => (0, *height as i64),
+            Some(height) => (1, -(*height as i64)),
+            None => (2, 0),
+        });
 
-        if let Err(error) = std::io::copy(&mut image_content, &mut output_file) {
-            Err(format!("Can't store file: {:?}", error))
+        video_sources.into_iter().next().map(|(url, _)| url)
+    }
+
+    /// Returns the absolute URL of a video source, which may be relative to the protocol or to the API endpoint.
+    fn absolute_url(&self, url: &str) -> String {
+        if url.starts_with("//") {
+            format!("https:{}", url)
+        } else if url.starts_with('/') {
+            format!("{}{}", self.api_endpoint, url)
         } else {
-            Ok(())
+            url.to_string()
+        }
+    }
+
+    /// Returns the height of a video, from its file name (ie: "Greenland_1080_HD.mp4", "Alps_1920x1080.mp4").
+    fn video_height(url: &str) -> Option<u32> {
+        let file_stem = Path::new(url.split('?').next().unwrap_or(url)).file_stem()?.to_string_lossy().to_string();
+
+        file_stem
+            .split(['_', '-', '.'])
+            .filter_map(|part| {
+                let part = part.to_lowercase();
+                let height = match part.split_once('x') {
+                    Some((_, height)) => height,
+                    None => part.strip_suffix('p').unwrap_or(&part),
+                };
+
+                height.parse::<u32>().ok().filter(|height| (144..=4320).contains(height))
+            })
+            .next_back()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::process;
+    use std::thread;
+
+    use serde_json::{json, Value};
+
+    use super::{BingAPIClient, BingAPIImagesArchiveImage};
+
+    /// Serves a single HTTP request with the given status line, and returns the URL of the server.
+    fn serve_once(status_line: &'static str, body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request);
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status_line,
+                body.len(),
+                body);
+            let _ = stream.write_all(response.as_bytes());
+        });
+
+        url
+    }
+
+    fn image(url: &str) -> BingAPIImagesArchiveImage {
+        BingAPIImagesArchiveImage {
+            url: url.to_string(),
+            title: String::new(),
+            copyright: String::new(),
+            copyrightlink: String::new(),
+            startdate: String::new(),
+            vid: None,
+            video_url: None,
         }
     }
+
+    #[test]
+    fn video_height() {
+        assert_eq!(BingAPIClient::video_height("//az29176.vo.msecnd.net/videocontent/Greenland_1080_HD.mp4"), Some(1080));
+        assert_eq!(BingAPIClient::video_height("https://www.bing.com/Alps_1920x1080.mp4?w=1"), Some(1080));
+        assert_eq!(BingAPIClient::video_height("/videos/Tokyo-720p.webm"), Some(720));
+        assert_eq!(BingAPIClient::video_height("/videos/Tokyo_2024_720.mp4"), Some(720));
+
+        // Without resolution in the name (numbers out of the video heights are ignored)
+        assert_eq!(BingAPIClient::video_height("/videos/Greenland_HD.mp4"), None);
+        assert_eq!(BingAPIClient::video_height("/videos/Greenland_42_8K.mp4"), None);
+        assert_eq!(BingAPIClient::video_height(""), None);
+    }
+
+    #[test]
+    fn best_video_source() {
+        let bing_api_client = BingAPIClient::new(None, None);
+        let vid = json!({
+            "sources": [
+                ["eot", "video/mp4", "//az29176.vo.msecnd.net/videocontent/Greenland_720_HD.mp4"],
+                ["eot", "video/mp4", "//az29176.vo.msecnd.net/videocontent/Greenland_1080_HD.mp4"],
+                ["eot", "video/mp4", "//az29176.vo.msecnd.net/videocontent/Greenland_2160_UHD.mp4"],
+                ["eot", "image/jpeg", "/th?id=OHR.Greenland_1920x1080.jpg"],
+                "not a source",
+            ]
+        });
+        let best_video_source = |vid: Option<&Value>, height| bing_api_client.best_video_source(vid, height);
+
+        // Smallest source at least as high as requested, else the highest one
+        assert_eq!(
+            best_video_source(Some(&vid), 1080).as_deref(),
+            Some("https://az29176.vo.msecnd.net/videocontent/Greenland_1080_HD.mp4"));
+        assert_eq!(
+            best_video_source(Some(&vid), 1200).as_deref(),
+            Some("https://az29176.vo.msecnd.net/videocontent/Greenland_2160_UHD.mp4"));
+        assert_eq!(
+            best_video_source(Some(&vid), 480).as_deref(),
+            Some("https://az29176.vo.msecnd.net/videocontent/Greenland_720_HD.mp4"));
+        assert_eq!(
+            best_video_source(Some(&vid), 4320).as_deref(),
+            Some("https://az29176.vo.msecnd.net/videocontent/Greenland_2160_UHD.mp4"));
+
+        // Sources without resolution in the name come last, relative URLs use the API endpoint
+        let vid = json!({
+            "sources": [
+                ["video/webm", "/videocontent/Greenland.webm"],
+                ["eot", "https://www.bing.com/videocontent/Greenland_480.mp4?mkt=en-US"],
+            ]
+        });
+        assert_eq!(
+            best_video_source(Some(&vid), 1080).as_deref(),
+            Some("https://www.bing.com/videocontent/Greenland_480.mp4?mkt=en-US"));
+        let vid = json!({ "sources": [["video/webm", "/videocontent/Greenland.webm"]] });
+        assert_eq!(
+            best_video_source(Some(&vid), 1080).as_deref(),
+            Some("https://www.bing.com/videocontent/Greenland.webm"));
+
+        // Without video
+        assert_eq!(best_video_source(None, 1080), None);
+        assert_eq!(best_video_source(Some(&json!({})), 1080), None);
+        assert_eq!(best_video_source(Some(&json!({ "sources": [["image/jpeg", "/th?id=OHR.jpg"]] })), 1080), None);
+    }
+
+    #[test]
+    fn error_status_fails_downloads() {
+        let mut bing_api_client = BingAPIClient::new(None, None);
+
+        bing_api_client.api_endpoint = serve_once("200 OK", "image");
+        assert_eq!(bing_api_client.download_image(&image("/th?id=OHR.Greenland.jpg")).unwrap(), b"image");
+
+        bing_api_client.api_endpoint = serve_once("404 Not Found", "Not Found");
+        let error = bing_api_client.download_image(&image("/th?id=OHR.Greenland.jpg")).unwrap_err();
+        assert!(error.starts_with("Can't download image:") && error.contains("404"), "{}", error);
+
+        let directory = env::temp_dir().join(format!("bingwallpaper-video-{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let filename = directory.join("video.mp4").to_string_lossy().to_string();
+        fs::write(&filename, "previous video").unwrap();
+
+        let video_url = serve_once("500 Internal Server Error", "Error");
+        let error = bing_api_client.download_video(&video_url, &filename).unwrap_err();
+        assert!(error.starts_with("Can't download video:") && error.contains("500"), "{}", error);
+        assert_eq!(fs::read_to_string(&filename).unwrap(), "previous video");
+
+        bing_api_client.download_video(&serve_once("200 OK", "video"), &filename).unwrap();
+        assert_eq!(fs::read_to_string(&filename).unwrap(), "video");
+        assert!(!directory.join("video.mp4.part").exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
 }
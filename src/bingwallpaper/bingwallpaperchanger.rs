@@ -1,17 +1,18 @@
 use chrono::{DateTime, Utc};
-#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
-use std::env;
+use serde_json::{json, Map, Value};
 #[cfg(target_os = "windows")]
 use std::ffi::CString;
-#[cfg(target_os = "macos")]
 use std::fs;
 #[cfg(target_os = "macos")]
 use std::fs::File;
 #[cfg(target_os = "macos")]
 use std::io::Write;
 use std::path::Path;
+#[cfg(target_os = "macos")]
 use std::process::Command;
-use std::time::SystemTime;
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
 #[cfg(target_os = "windows")]
 use winapi::ctypes::c_void;
 #[cfg(target_os = "windows")]
@@ -21,7 +22,20 @@ use winvd::{get_desktop_count, get_desktops};
 #[cfg(target_os = "windows")]
 use winver::WindowsVersion;
 
-use crate::bingwallpaper::{BingAPIClient, BingWallpaperConfiguration, TextOverlay};
+use crate::bingwallpaper::collage::DEFAULT_COLLAGE_IMAGES;
+use crate::bingwallpaper::configuration::{HookEvent, HookFailurePolicy, PaletteFormat};
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
+use crate::bingwallpaper::wallpapersetter::WallpaperSetterDetector;
+use crate::bingwallpaper::{
+    BingAPIClient, BingWallpaperConfiguration, ColorPalette, CommandRunner, GreeterBackground, RenderPipeline,
+    SunCalculator, VideoWallpaper, WallpaperArchive, WallpaperMetadata,
+};
+
+/// Default maximum number of images kept in the archive.
+const DEFAULT_ARCHIVE_MAX_IMAGES: u32 = 60;
+
+/// Default maximum time to wait for a command (ie: `exec_apply_wallpaper`).
+const DEFAULT_EXEC_TIMEOUT_SECOND: u64 = 60;
 
 /// Retrieves from Bing API and applies the wallpaper of the day.
 ///
@@ -35,6 +49,8 @@ use crate::bingwallpaper::{BingAPIClient, BingWallpaperConfiguration, TextOverla
 pub struct BingWallpaperChanger {
     configuration: BingWallpaperConfiguration,
     bing_api_client: BingAPIClient,
+    #[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
+    last_applied_wallpaper: Mutex<Option<String>>,
 }
 
 impl BingWallpaperChanger {
@@ -52,10 +68,13 @@ impl BingWallpaperChanger {
     #[must_use]
     pub fn new(configuration: BingWallpaperConfiguration) -> BingWallpaperChanger {
         let proxy_url = configuration.proxy_url.clone();
+        let market = configuration.market.clone();
 
         BingWallpaperChanger {
             configuration,
-            bing_api_client: BingAPIClient::new(proxy_url),
+            bing_api_client: BingAPIClient::new(proxy_url, market),
+            #[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
+            last_applied_wallpaper: Mutex::new(None),
         }
     }
 
@@ -69,40 +88,218 @@ impl BingWallpaperChanger {
         self.process(false)
     }
 
-    /// Do job.
+    /// Installs the downloaded image as greeter (login screen) background.
+    ///
+    /// It runs with root privileges: the image already downloaded by the user is only read, nothing is written
+    /// in the user directories (files created there would belong to root).
+    ///
+    /// # Arguments
+    /// * `root` - The filesystem root to install the background into, "/" if not specified
+    pub fn try_apply_greeter(&self, root: Option<&str>) -> Result<(), String> {
+        let original_filename = self.configuration.original_filename();
+        let metadata = match WallpaperMetadata::load(&original_filename) {
+            Some(metadata) if Path::new(&original_filename).exists() => metadata,
+            _ => {
+                return Err(format!(
+                    "Can't find the downloaded image {:?}: run bingwallpaper as the desktop user first",
+                    original_filename))
+            }
+        };
+        let greeter_configuration = self.configuration.greeter.clone().unwrap_or_default();
+        let root = root.unwrap_or("/");
+
+        let background_filename = GreeterBackground::target_filename(&greeter_configuration);
+        let state_filename = GreeterBackground::state_filename(root, &background_filename);
+        let background_filename = GreeterBackground::rooted(root, &background_filename);
+        GreeterBackground::create_parent_directory(&background_filename)?;
+
+        let background_filename = background_filename.to_string_lossy();
+        if RenderPipeline::render_greeter(&self.configuration, &background_filename, &state_filename, &metadata)? {
+            println!("Greeter background rendered: {}", background_filename);
+        }
+
+        GreeterBackground::install(&greeter_configuration, root)
+    }
+
+    /// Returns the time remaining until the next sunrise or sunset, if the night variant is enabled.
+    pub fn time_until_next_sun_event(&self) -> Option<Duration> {
+        self.configuration.night_variant.as_ref()?;
+        let (latitude, longitude) = self.configuration.location()?;
+        let now = Utc::now();
+        let next_sun_event = SunCalculator::next_event(now, latitude, longitude)?;
+
+        // Wakes up slightly after the event, to be sure the new wallpaper is applied
+        (next_sun_event - now).to_std().ok().map(|duration| duration + Duration::from_secs(1))
+    }
+
+    /// Do job, then runs the `ON_ERROR` hooks if something goes wrong.
     ///
     /// # Arguments
     /// * `must_change_wallpaper` - `true` to change wallpaper after download
     fn process(&self, must_change_wallpaper: bool) -> Result<(), String> {
-        let system_date_as_str = self.get_date_system();
-        let wallpaper_date_as_str = self.get_date_current_wallpaper();
+        let result = self.process_steps(must_change_wallpaper);
+
+        if let Err(error) = &result {
+            let metadata = WallpaperMetadata::load(&self.configuration.original_filename());
+            if let Err(hook_error) = self.run_hooks(HookEvent::OnError, metadata.as_ref(), &[("error", error.clone())]) {
+                println!("{}", hook_error);
+            }
+        }
+
+        result
+    }
+
+    /// Do job, running the hooks of each step.
+    ///
+    /// # Arguments
+    /// * `must_change_wallpaper` - `true` to change wallpaper after download
+    fn process_steps(&self, must_change_wallpaper: bool) -> Result<(), String> {
+        let current_metadata = WallpaperMetadata::load(&self.configuration.original_filename());
+        self.run_hooks(HookEvent::PreFetch, current_metadata.as_ref(), &[])?;
 
-        // Checks if current downloaded wallpaper is up to date
-        if system_date_as_str == wallpaper_date_as_str {
-            return self.change_wallpaper();
+        let (metadata, original_content) = self.retrieve_original()?;
+        if original_content.is_some() {
+            self.run_hooks(HookEvent::PostDownload, Some(&metadata), &[])?;
         }
 
-        // Retrieves information from Bing API
-        let bing_image = self.bing_api_client.retrieve_latest_image(
-            self.configuration.image_dimension_width,
-            self.configuration.image_dimension_height)?;
+        // Downloads the video published next to the image (if enabled), the still image is used without it
+        if let Err(error) = self.retrieve_video(&metadata, original_content.is_some()) {
+            println!("Can't retrieve video wallpaper: {}", error);
+        }
 
-        println!("Wallpaper information");
-        println!("  - Title    : {}", &bing_image.title);
-        println!("  - Copyright: {}", &bing_image.copyright);
-        println!("               {}", &bing_image.copyrightlink);
+        // Renders wallpaper from the downloaded image (only if something changed)
+        let rendered_filenames = RenderPipeline::render(&self.configuration, &metadata, original_content.as_deref())?;
+        let mut is_rendered = !rendered_filenames.is_empty();
+        for rendered_filename in rendered_filenames {
+            println!("Wallpaper rendered: {}", rendered_filename);
+        }
+
+        // Keeps the downloaded image and renders the collage of the latest ones (if enabled)
+        if let Some(archive_directory) = self.configuration.archive_directory() {
+            let collage_images = self.configuration.collage
+                .as_ref()
+                .map(|collage_configuration| collage_configuration.images.unwrap_or(DEFAULT_COLLAGE_IMAGES))
+                .unwrap_or_default();
+            let max_images = self.configuration.archive_max_images.unwrap_or(DEFAULT_ARCHIVE_MAX_IMAGES).max(collage_images);
+
+            WallpaperArchive::store(
+                &archive_directory,
+                &self.configuration.original_filename(),
+                &metadata,
+                max_images as usize)?;
+
+            if let (Some(collage_configuration), Some(collage_filename)) =
+                (&self.configuration.collage, self.configuration.collage_filename()) {
+                let archived_images = WallpaperArchive::latest(&archive_directory, collage_images.max(1) as usize);
+                if RenderPipeline::render_collage(
+                    &self.configuration,
+                    collage_configuration,
+                    &collage_filename,
+                    &archived_images)? {
+                    println!("Collage rendered: {} ({} images)", collage_filename, archived_images.len());
+                    is_rendered = true;
+                }
+            }
+        }
 
-        if wallpaper_date_as_str.contains("00000000") || bing_image.startdate != system_date_as_str {
-            // Downloads image
-            self.bing_api_client.download_image(&bing_image, &self.configuration.target_filename)?;
+        if is_rendered {
+            self.run_hooks(HookEvent::PostRender, Some(&metadata), &[])?;
         }
 
-        // Overlay
-        TextOverlay::apply_overlay(&self.configuration, bing_image.title, bing_image.copyright);
+        // Extracts the color palette of the downloaded image (if enabled)
+        let mut is_palette_changed = false;
+        if let Some(palette_configuration) = &self.configuration.palette {
+            for exported_filename in ColorPalette::export(&self.configuration, palette_configuration, &metadata)? {
+                println!("Palette exported: {}", exported_filename);
+                is_palette_changed = true;
+            }
+        }
 
         // Change current wallpaper (if requested)
         if must_change_wallpaper {
-            return self.change_wallpaper();
+            self.change_wallpaper(&metadata)?;
+        }
+
+        // Applies the palette (only if it changed)
+        if is_palette_changed {
+            if let Err(error) = self.exec_apply_palette(&metadata) {
+                println!("Can't apply palette: {:?}", error);
+            }
+        }
+
+        if must_change_wallpaper {
+            self.run_hooks(HookEvent::PostApply, Some(&metadata), &[])?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the information of the downloaded image, downloading the image of the day first if needed.
+    /// The image content is also returned when it has just been downloaded.
+    fn retrieve_original(&self) -> Result<(WallpaperMetadata, Option<Vec<u8>>), String> {
+        let system_date_as_str = self.get_date_system();
+        let original_filename = self.configuration.original_filename();
+        let original_date_as_str = self.get_date_file_modified(&original_filename);
+        let checked_date_as_str = self.get_date_file_modified(&WallpaperMetadata::filename(&original_filename));
+        let mut metadata = WallpaperMetadata::load(&original_filename);
+        let mut original_content: Option<Vec<u8>> = None;
+
+        // Checks if downloaded wallpaper has been checked today, otherwise looks for a new one
+        if metadata.is_none() || system_date_as_str != checked_date_as_str {
+            // Retrieves information from Bing API
+            let bing_image = self.bing_api_client.retrieve_latest_image(
+                self.configuration.image_dimension_width,
+                self.configuration.image_dimension_height)?;
+
+            println!("Wallpaper information");
+            println!("  - Title    : {}", &bing_image.title);
+            println!("  - Copyright: {}", &bing_image.copyright);
+            println!("               {}", &bing_image.copyrightlink);
+
+            let latest_metadata = WallpaperMetadata::from_bing_image(&bing_image, self.configuration.market.as_deref());
+            let is_new_image = original_date_as_str.contains("00000000")
+                || metadata.as_ref().is_none_or(|current_metadata| current_metadata.url != latest_metadata.url);
+
+            if is_new_image {
+                // Downloads image
+                let content = self.bing_api_client.download_image(&bing_image)?;
+                if let Err(error) = fs::write(&original_filename, &content) {
+                    return Err(format!("Can't store original image: {:?}", error));
+                }
+
+                latest_metadata.save(&original_filename)?;
+                metadata = Some(latest_metadata);
+                original_content = Some(content);
+            } else if let Some(current_metadata) = &metadata {
+                // Bing still proposes the same image: stores it again, so that the check is not repeated until tomorrow
+                current_metadata.save(&original_filename)?;
+            }
+        }
+
+        Ok((metadata.unwrap(), original_content))
+    }
+
+    /// Downloads the video published next to the image, if enabled. The video of a previous image is removed.
+    ///
+    /// # Arguments
+    /// * `metadata` - The information about the downloaded image
+    /// * `is_new_image` - `true` if the image has just been downloaded
+    fn retrieve_video(&self, metadata: &WallpaperMetadata, is_new_image: bool) -> Result<(), String> {
+        let video_filename = match self.configuration.video_filename() {
+            None => return Ok(()),
+            Some(video_filename) => video_filename,
+        };
+
+        // The video of the previous image must not be played with the new one, even if the download fails
+        if (is_new_image || metadata.video_url.is_empty()) && Path::new(&video_filename).exists() {
+            if let Err(error) = fs::remove_file(&video_filename) {
+                return Err(format!("Can't remove previous video: {:?}", error));
+            }
+        }
+
+        if !metadata.video_url.is_empty() && !Path::new(&video_filename).exists() {
+            self.bing_api_client.download_video(&metadata.video_url, &video_filename)?;
+            println!("Video downloaded: {}", video_filename);
         }
 
         Ok(())
@@ -114,11 +311,11 @@ impl BingWallpaperChanger {
         format!("{}", date_time.format("%Y%m%d"))
     }
 
-    /// Returns the date (UTC) as a String following the format "%Y%m%d" of the current wallpaper.
+    /// Returns the last modification date (UTC) of a file as a String following the format "%Y%m%d".
     ///
-    /// In case of current wallpaper does not exist, "00000000" will be return.
-    fn get_date_current_wallpaper(&self) -> String {
-        match Path::new(self.configuration.target_filename.as_str()).metadata() {
+    /// In case of file does not exist, "00000000" will be return.
+    fn get_date_file_modified(&self, filename: &str) -> String {
+        match Path::new(filename).metadata() {
             Err(_) => String::from("00000000"),
             Ok(metadata) => match metadata.modified() {
                 Err(_) => String::from("00000000"),
@@ -130,10 +327,38 @@ impl BingWallpaperChanger {
         }
     }
 
+    /// Returns the location of the wallpaper to apply: the collage (if enabled), the night
+    /// variant between sunset and sunrise (if enabled), the wallpaper otherwise.
+    fn wallpaper_filename(&self) -> String {
+        if let Some(collage_filename) = self.configuration.collage_filename() {
+            if Path::new(&collage_filename).exists() {
+                return collage_filename;
+            }
+        }
+
+        if let Some(night_variant_filename) = self.configuration.night_variant_filename() {
+            match self.configuration.location() {
+                None => println!("Night variant is ignored: latitude and longitude must be configured"),
+                Some((latitude, longitude)) => {
+                    if SunCalculator::is_night(Utc::now(), latitude, longitude) {
+                        return night_variant_filename;
+                    }
+                }
+            }
+        }
+
+        self.configuration.target_filename.clone()
+    }
+
     /// Change wallpaper.
-    fn change_wallpaper(&self) -> Result<(), String> {
+    ///
+    /// # Arguments
+    /// * `metadata` - The information about the downloaded image
+    fn change_wallpaper(&self, metadata: &WallpaperMetadata) -> Result<(), String> {
+        let wallpaper_filename = self.wallpaper_filename();
+
         if self.configuration.exec_apply_wallpaper.is_some() {
-            self.exec_apply_wallpaper();
+            self.exec_apply_wallpaper(&wallpaper_filename, metadata)?;
         } else {
             #[cfg(any(
                 target_os = "linux",
@@ -141,98 +366,237 @@ impl BingWallpaperChanger {
                 target_os = "netbsd",
                 target_os = "openbsd"
             ))] {
-                self.change_wallpaper_linux();
+                self.change_wallpaper_linux(&wallpaper_filename)?;
             }
 
             #[cfg(target_os = "macos")] {
-                self.change_wallpaper_macos();
+                self.change_wallpaper_macos(&wallpaper_filename);
             }
 
             #[cfg(target_os = "windows")] {
-                self.change_wallpaper_windows();
+                self.change_wallpaper_windows(&wallpaper_filename);
             }
         }
 
+        self.apply_video(&wallpaper_filename, metadata);
+
         Ok(())
     }
 
+    /// Returns what identifies the applied images: the location, modification time and size of the wallpaper, and of
+    /// the other images applied with it (ie: dark and lock screen variants, per-screen images).
+    ///
+    /// # Arguments
+    /// * `wallpaper_filename` - The location of the wallpaper
+    #[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
+    fn applied_wallpaper(&self, wallpaper_filename: &str) -> String {
+        let mut filenames = vec![wallpaper_filename.to_string()];
+        filenames.extend(self.configuration.dark_variant_filename());
+        filenames.extend(self.configuration.lock_screen_variant_filename());
+        if let Some(plasma_configuration) = &self.configuration.plasma {
+            filenames.extend(plasma_configuration.screen_filenames.iter().flatten().cloned());
+        }
+        if let Some(wayland_configuration) = &self.configuration.wayland {
+            let output_filenames = wayland_configuration.output_filenames.iter().flat_map(|filenames| filenames.values());
+            filenames.extend(output_filenames.cloned());
+        }
+
+        filenames
+            .iter()
+            .map(|filename| {
+                let metadata = fs::metadata(filename).ok();
+                let modified = metadata.as_ref().and_then(|metadata| metadata.modified().ok());
+                format!("{} {:?} {:?}", filename, modified, metadata.map(|metadata| metadata.len()))
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Plays the video over the wallpaper, if enabled and published next to the image. Otherwise, or when no animated
+    /// wallpaper backend is available, the video played previously is stopped and the still wallpaper remains.
+    ///
+    /// # Arguments
+    /// * `wallpaper_filename` - The location of the wallpaper
+    /// * `metadata` - The information about the downloaded image
+    fn apply_video(&self, wallpaper_filename: &str, metadata: &WallpaperMetadata) {
+        let (video_configuration, video_filename) = match (&self.configuration.video, self.configuration.video_filename()) {
+            (Some(video_configuration), Some(video_filename)) => (video_configuration, video_filename),
+            _ => return,
+        };
+
+        if !metadata.video_url.is_empty() && Path::new(&video_filename).exists() {
+            let mut variables = self.exec_variables(wallpaper_filename, Some(metadata));
+            variables.push(("video_filename", video_filename.clone()));
+
+            let video_wallpaper = VideoWallpaper::new(video_configuration.clone(), self.configuration.fit_mode);
+            match video_wallpaper.play(&video_filename, &variables, self.exec_timeout()) {
+                Ok(true) => return,
+                Ok(false) => println!("Video wallpaper is ignored: no animated wallpaper backend (ie: mpvpaper, xwinwrap)"),
+                Err(error) => println!("Can't play video wallpaper: {}", error),
+            }
+        }
+
+        VideoWallpaper::stop();
+    }
+
     /// Changes the wallpaper by executing custom command
-    fn exec_apply_wallpaper(&self) {
-        // Replaces all variables
-        let mut cmd_as_str = self.configuration.exec_apply_wallpaper.clone().unwrap();
-        cmd_as_str = str::replace(&cmd_as_str, "{target_filename}", &self.configuration.target_filename);
-        cmd_as_str = str::replace(&cmd_as_str, "{image_dimension_width}", &self.configuration.image_dimension_width.to_string());
-        cmd_as_str = str::replace(&cmd_as_str, "{image_dimension_height}", &self.configuration.image_dimension_height.to_string());
-
-        // Prepares command to run
-        if !cmd_as_str.is_empty() {
-            let mut cmd_to_run;
-            let cmd_tokens = cmd_as_str.split_whitespace();
-
-            if cmd_as_str.len() == 1 {
-                cmd_to_run = Command::new(cmd_as_str);
-            } else {
-                let mut it = cmd_tokens.into_iter().peekable();
-
-                cmd_to_run = Command::new(it.next().unwrap());
-                while it.peek().is_some() {
-                    cmd_to_run.arg(it.next().unwrap());
+    fn exec_apply_wallpaper(&self, wallpaper_filename: &str, metadata: &WallpaperMetadata) -> Result<(), String> {
+        let command = match &self.configuration.exec_apply_wallpaper {
+            None => return Ok(()),
+            Some(command) => command,
+        };
+
+        CommandRunner::run(command, &self.exec_variables(wallpaper_filename, Some(metadata)), self.exec_timeout())
+    }
+
+    /// Applies the color palette by executing custom command, if configured
+    fn exec_apply_palette(&self, metadata: &WallpaperMetadata) -> Result<(), String> {
+        let command = match self.configuration.palette.as_ref().and_then(|palette| palette.exec_apply_palette.as_ref()) {
+            None => return Ok(()),
+            Some(command) => command,
+        };
+
+        let mut variables = self.exec_variables(&self.configuration.target_filename, Some(metadata));
+        for (variable, format) in [
+            ("json_filename", PaletteFormat::Json),
+            ("pywal_filename", PaletteFormat::Pywal),
+            ("xresources_filename", PaletteFormat::Xresources),
+            ("css_filename", PaletteFormat::Css),
+        ] {
+            variables.push((variable, self.configuration.palette_filename(format)));
+        }
+
+        CommandRunner::run(command, &variables, self.exec_timeout())
+    }
+
+    /// Runs the hooks of an event. The information about the image is passed as variables, and as JSON on the standard
+    /// input of the commands.
+    ///
+    /// # Arguments
+    /// * `event` - The step of the wallpaper update
+    /// * `metadata` - The information about the image, if known
+    /// * `event_variables` - The variables specific to the event (ie: "error")
+    fn run_hooks(&self,
+                 event: HookEvent,
+                 metadata: Option<&WallpaperMetadata>,
+                 event_variables: &[(&'static str, String)]) -> Result<(), String> {
+        let hooks: Vec<_> = self.configuration.hooks
+            .iter()
+            .flatten()
+            .filter(|hook| hook.event == event)
+            .collect();
+        if hooks.is_empty() {
+            return Ok(());
+        }
+
+        let mut variables = vec![("event", event.to_string())];
+        variables.extend(self.exec_variables(&self.configuration.target_filename, metadata));
+        variables.extend_from_slice(event_variables);
+
+        let mut input = Map::new();
+        for (name, value) in &variables {
+            input.insert(name.to_string(), Value::from(value.as_str()));
+        }
+        input.insert("metadata".to_string(), json!(metadata));
+        let input = Value::Object(input).to_string();
+
+        for hook in hooks {
+            if let Err(error) = CommandRunner::run_with_input(&hook.command, &variables, Some(&input), self.exec_timeout()) {
+                match hook.on_failure.unwrap_or(HookFailurePolicy::Warn) {
+                    HookFailurePolicy::Ignore => {}
+                    HookFailurePolicy::Warn => println!("Hook {} failed: {}", event, error),
+                    HookFailurePolicy::Abort => return Err(format!("Hook {} failed: {}", event, error)),
                 }
             }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the variables available to the commands, with their value.
+    ///
+    /// # Arguments
+    /// * `wallpaper_filename` - The location of the wallpaper
+    /// * `metadata` - The information about the downloaded image, if known
+    fn exec_variables(&self,
+                      wallpaper_filename: &str,
+                      metadata: Option<&WallpaperMetadata>) -> Vec<(&'static str, String)> {
+        let (title, copyright, date) = match metadata {
+            None => Default::default(),
+            Some(metadata) => (
+                metadata.title.clone(),
+                metadata.copyright.clone(),
+                metadata.date().map(|date| date.format("%Y-%m-%d").to_string()).unwrap_or_default(),
+            ),
+        };
+
+        vec![
+            ("target_filename", wallpaper_filename.to_string()),
+            ("original_filename", self.configuration.original_filename()),
+            ("dark_filename", self.configuration.dark_variant_filename().unwrap_or_default()),
+            ("image_dimension_width", self.configuration.image_dimension_width.to_string()),
+            ("image_dimension_height", self.configuration.image_dimension_height.to_string()),
+            ("title", title),
+            ("copyright", copyright),
+            ("date", date),
+        ]
+    }
 
-            // Run command
-            let mut child = cmd_to_run
-                .spawn()
-                .expect("Can't change wallpaper");
-            child.wait().expect("Can't wait for child process");
+    /// Returns the maximum time to wait for a command, none if disabled (`exec_timeout_second` set to `0`).
+    fn exec_timeout(&self) -> Option<Duration> {
+        match self.configuration.exec_timeout_second.unwrap_or(DEFAULT_EXEC_TIMEOUT_SECOND) {
+            0 => None,
+            exec_timeout_second => Some(Duration::from_secs(exec_timeout_second)),
         }
     }
 
-    /// Changes the wallpaper with the given picture on Linux.
+    /// Changes the wallpaper with the given picture on Linux, with the wallpaper setter of the
+    /// configured or detected desktop environment. The lock screen variant is also applied, if enabled.
+    ///
+    /// Other lock screens (ie: swaylock, i3lock, hyprlock) have to reference the
+    /// lock screen variant location in their own configuration.
     #[cfg(any(
         target_os = "linux",
         target_os = "freebsd",
         target_os = "netbsd",
         target_os = "openbsd"
     ))]
-    fn change_wallpaper_linux(&self) {
-        let session = env::var("DESKTOP_SESSION").unwrap();
-
-        if session.eq("cinnamon") {
-            // Cinnamon
-            let mut child = Command::new("gsettings")
-                .arg("set")
-                .arg("org.cinnamon.desktop.background")
-                .arg("picture-uri")
-                .arg(format!("file://{}", &self.configuration.target_filename))
-                .spawn()
-                .expect("Can't change wallpaper");
-            child.wait().expect("Can't wait for child process");
-        } else {
-            // Gnome
-            let mut child = Command::new("gsettings")
-                .arg("set")
-                .arg("org.gnome.desktop.background")
-                .arg("picture-uri")
-                .arg(&self.configuration.target_filename)
-                .spawn()
-                .expect("Can't change wallpaper");
-            child.wait().expect("Can't wait for child process");
-
-            let mut child = Command::new("gsettings")
-                .arg("set")
-                .arg("org.gnome.desktop.background")
-                .arg("picture-uri-dark")
-                .arg(&self.configuration.target_filename)
-                .spawn()
-                .expect("Can't change wallpaper");
-            child.wait().expect("Can't wait for child process");
+    fn change_wallpaper_linux(&self, wallpaper_filename: &str) -> Result<(), String> {
+        // The same images are not applied again on each loop: some desktops ask the user to confirm (ie: desktop
+        // portal preview), play a transition (ie: swww) or restart a process (ie: swaybg)
+        let applied_wallpaper = self.applied_wallpaper(wallpaper_filename);
+        let mut last_applied_wallpaper = match self.last_applied_wallpaper.lock() {
+            Err(error) => return Err(format!("Can't access last applied wallpaper: {:?}", error)),
+            Ok(last_applied_wallpaper) => last_applied_wallpaper,
         };
+        if last_applied_wallpaper.as_ref() == Some(&applied_wallpaper) {
+            return Ok(());
+        }
+
+        let wallpaper_setter = WallpaperSetterDetector::detect(&self.configuration)?;
+
+        // At night, the night variant is also applied to the dark style
+        let dark_variant_filename = self.configuration
+            .dark_variant_filename()
+            .filter(|_| wallpaper_filename == self.configuration.target_filename);
+        if let Err(error) = wallpaper_setter.set_wallpaper(wallpaper_filename, dark_variant_filename.as_deref()) {
+            return Err(format!("Can't change {} wallpaper: {}", wallpaper_setter.name(), error));
+        }
+
+        if let Some(lock_screen_filename) = self.configuration.lock_screen_variant_filename() {
+            if let Err(error) = wallpaper_setter.set_lock_screen(&lock_screen_filename) {
+                println!("Can't change {} lock screen wallpaper: {}", wallpaper_setter.name(), error);
+            }
+        }
+
+        *last_applied_wallpaper = Some(applied_wallpaper);
+
+        Ok(())
     }
 
     /// Changes the wallpaper with the given picture on MacOS.
     #[cfg(target_os = "macos")]
-    fn change_wallpaper_macos(&self) {
+    fn change_wallpaper_macos(&self, wallpaper_filename: &str) {
         // TODO: copy file with unique filename (hidden file) and apply it!
         // Writes script SWIFT used to change wallpaper into temporary location
         let swift_script_path = Path::new("/tmp/bingwallpaper.swift");
@@ -250,7 +614,7 @@ impl BingWallpaperChanger {
 
         // MacOS does not refresh the screen if the file name of
         // the new wallpaper is the same as the old one.
-        let target_filename_as_path = Path::new(&self.configuration.target_filename);
+        let target_filename_as_path = Path::new(wallpaper_filename);
         let tmp_filename_prefix = format!(
             "{0}/._{1}_",
             target_filename_as_path.parent().unwrap().to_str().unwrap(),
@@ -265,9 +629,11 @@ impl BingWallpaperChanger {
             }
         }
 
-        // Apply new temporary wallpaper
+        // Apply new temporary wallpaper (hard link avoids copying the file when possible)
         let tmp_filename = format!("{0}{1}", tmp_filename_prefix, self.get_date_system());
-        fs::copy(self.configuration.target_filename.as_str(), &tmp_filename).unwrap();
+        if fs::hard_link(wallpaper_filename, &tmp_filename).is_err() {
+            fs::copy(wallpaper_filename, &tmp_filename).unwrap();
+        }
         let mut child = Command::new("swift")
             .arg("/tmp/bingwallpaper.swift")
             .arg(&tmp_filename)
@@ -281,25 +647,25 @@ impl BingWallpaperChanger {
 
     /// Changes the wallpaper with the given picture on Windows.
     #[cfg(target_os = "windows")]
-    fn change_wallpaper_windows(&self) {
+    fn change_wallpaper_windows(&self, wallpaper_filename: &str) {
         let win_version = WindowsVersion::detect().unwrap();
 
         if win_version >= WindowsVersion::new(10, 0, 22621) && get_desktop_count().unwrap() > 1 {
-            if let Err(error) = self.change_wallpaper_windows_virtualdesktop() {
+            if let Err(error) = self.change_wallpaper_windows_virtualdesktop(wallpaper_filename) {
                 println!("Something goes wrong with Virtual Desktop API. Fallback to legacy Windows API\n{:?}", error);
-                self.change_wallpaper_windows_winuser();
+                self.change_wallpaper_windows_winuser(wallpaper_filename);
             }
         } else {
-            self.change_wallpaper_windows_winuser();
+            self.change_wallpaper_windows_winuser(wallpaper_filename);
         }
     }
 
     /// Changes the wallpaper with the given picture on Windows using the Virtual Desktop API.
     #[cfg(target_os = "windows")]
-    fn change_wallpaper_windows_virtualdesktop(&self) -> Result<(), String> {
+    fn change_wallpaper_windows_virtualdesktop(&self, wallpaper_filename: &str) -> Result<(), String> {
         if let Ok(detected_desktops) = get_desktops() {
             for desktop in detected_desktops {
-                if let Err(error) = desktop.set_wallpaper(&self.configuration.target_filename) {
+                if let Err(error) = desktop.set_wallpaper(wallpaper_filename) {
                     return Err(format!(
                         "Can't change Virtual Desktop wallpaper for #{:?}\n{:?}",
                         desktop.get_id().unwrap(),
@@ -315,8 +681,8 @@ impl BingWallpaperChanger {
 
     /// Changes the wallpaper with the given picture on Windows using the legacy Windows API.
     #[cfg(target_os = "windows")]
-    fn change_wallpaper_windows_winuser(&self) {
-        let image_path = CString::new(String::from(&self.configuration.target_filename)).unwrap();
+    fn change_wallpaper_windows_winuser(&self, wallpaper_filename: &str) {
+        let image_path = CString::new(wallpaper_filename).unwrap();
         unsafe {
             winuser::SystemParametersInfoA(
                 winuser::SPI_SETDESKWALLPAPER,
@@ -326,3 +692,171 @@ impl BingWallpaperChanger {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::Path;
+    use std::process;
+
+    use image::{Rgb, RgbImage};
+    use serde_json::{json, Value};
+
+    use super::BingWallpaperChanger;
+    use crate::bingwallpaper::configuration::{ExecCommand, HookConfiguration, HookEvent, HookFailurePolicy};
+    use crate::bingwallpaper::{BingWallpaperConfiguration, WallpaperMetadata};
+
+    /// Returns a configuration storing the wallpaper in a new temporary directory.
+    fn configuration(name: &str) -> (BingWallpaperConfiguration, std::path::PathBuf) {
+        let directory = std::env::temp_dir().join(format!("bingwallpaper-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+
+        let mut configuration = BingWallpaperConfiguration::default();
+        configuration.image_dimension_width = 16;
+        configuration.image_dimension_height = 8;
+        configuration.target_filename = directory.join("user/bingwallpaper.jpg").to_string_lossy().to_string();
+        fs::create_dir_all(directory.join("user")).unwrap();
+
+        (configuration, directory)
+    }
+
+    /// Returns the information of a downloaded image.
+    fn metadata() -> WallpaperMetadata {
+        WallpaperMetadata {
+            url: "/th?id=OHR.LakeLouise".to_string(),
+            title: "Lake Louise".to_string(),
+            copyright: "Lake Louise, Canada (© John Doe)".to_string(),
+            copyright_link: String::new(),
+            photographer: "John Doe".to_string(),
+            location: "Lake Louise, Canada".to_string(),
+            start_date: "20250621".to_string(),
+            market: "en-US".to_string(),
+            source: "Bing".to_string(),
+            video_url: String::new(),
+        }
+    }
+
+    #[test]
+    fn apply_greeter_from_the_stored_image_only() {
+        let (configuration, directory) = configuration("apply-greeter");
+        let root = directory.join("root").to_string_lossy().to_string();
+        let original_filename = configuration.original_filename();
+
+        // Nothing downloaded yet
+        let bing_wallpaper_changer = BingWallpaperChanger::new(configuration);
+        let error = bing_wallpaper_changer.try_apply_greeter(Some(&root)).unwrap_err();
+        assert!(error.contains("run bingwallpaper as the desktop user first"), "{}", error);
+
+        RgbImage::from_pixel(32, 16, Rgb([40, 80, 120])).save(&original_filename).unwrap();
+        metadata().save(&original_filename).unwrap();
+        let user_files = |directory: &Path| {
+            let mut filenames: Vec<String> = fs::read_dir(directory)
+                .unwrap()
+                .map(|dir_entry| dir_entry.unwrap().file_name().to_string_lossy().to_string())
+                .collect();
+            filenames.sort();
+            filenames
+        };
+        let user_files_before = user_files(&directory.join("user"));
+
+        bing_wallpaper_changer.try_apply_greeter(Some(&root)).unwrap();
+
+        // Only the system locations, inside the root, are written
+        let background_filename = directory.join("root/usr/share/backgrounds/bingwallpaper-greeter.jpg");
+        assert_eq!(image::open(&background_filename).unwrap().width(), 16);
+        assert_eq!(fs::read_dir(directory.join("root/var/cache/bingwallpaper")).unwrap().count(), 1);
+        assert_eq!(user_files(&directory.join("user")), user_files_before);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn run_hooks_with_each_failure_policy() {
+        let (mut configuration, directory) = configuration("run-hooks");
+        let target_filename = configuration.target_filename.clone();
+
+        // Records its standard input and its environment, then exits with the given status
+        let script_filename = directory.join("hook.sh");
+        fs::write(
+            &script_filename,
+            "cat > \"$1.json\"\nenv | grep '^BINGWALLPAPER_' > \"$1.env\"\nexit \"$2\"\n").unwrap();
+        let hook = |event, name: &str, status: &str, on_failure| HookConfiguration {
+            event,
+            command: ExecCommand::Arguments(vec![
+                "sh".to_string(),
+                script_filename.to_string_lossy().to_string(),
+                directory.join(name).to_string_lossy().to_string(),
+                status.to_string(),
+            ]),
+            on_failure,
+        };
+        configuration.hooks = Some(vec![
+            hook(HookEvent::PreFetch, "ignore", "1", Some(HookFailurePolicy::Ignore)),
+            hook(HookEvent::PreFetch, "after-ignore", "0", None),
+            hook(HookEvent::PostRender, "warn", "1", Some(HookFailurePolicy::Warn)),
+            hook(HookEvent::PostRender, "after-warn", "0", None),
+            hook(HookEvent::OnError, "abort", "1", Some(HookFailurePolicy::Abort)),
+            hook(HookEvent::OnError, "after-abort", "0", None),
+            hook(HookEvent::PostApply, "post-apply", "0", None),
+        ]);
+        let input = |name: &str| -> Value {
+            serde_json::from_str(&fs::read_to_string(directory.join(format!("{}.json", name))).unwrap()).unwrap()
+        };
+        let environment = |name: &str| -> HashMap<String, String> {
+            fs::read_to_string(directory.join(format!("{}.env", name)))
+                .unwrap()
+                .lines()
+                .filter_map(|line| line.split_once('='))
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        };
+        let bing_wallpaper_changer = BingWallpaperChanger::new(configuration);
+
+        // Ignored failure: the next hooks run, with the variables and the metadata
+        bing_wallpaper_changer.run_hooks(HookEvent::PreFetch, Some(&metadata()), &[]).unwrap();
+        for name in ["ignore", "after-ignore"] {
+            let input = input(name);
+            assert_eq!(input["event"], "PRE_FETCH");
+            assert_eq!(input["target_filename"], target_filename.as_str());
+            assert_eq!(input["title"], "Lake Louise");
+            assert_eq!(input["date"], "2025-06-21");
+            assert_eq!(input["image_dimension_width"], "16");
+            assert_eq!(input["metadata"]["photographer"], "John Doe");
+            assert_eq!(input["metadata"]["market"], "en-US");
+
+            let environment = environment(name);
+            assert_eq!(environment["BINGWALLPAPER_EVENT"], "PRE_FETCH");
+            assert_eq!(environment["BINGWALLPAPER_TARGET_FILENAME"], target_filename);
+            assert_eq!(environment["BINGWALLPAPER_TITLE"], "Lake Louise");
+            assert_eq!(environment["BINGWALLPAPER_COPYRIGHT"], "Lake Louise, Canada (© John Doe)");
+            assert_eq!(environment["BINGWALLPAPER_DATE"], "2025-06-21");
+            assert_eq!(environment["BINGWALLPAPER_IMAGE_DIMENSION_HEIGHT"], "8");
+        }
+
+        // Warned failure: the next hooks run, without metadata when the image is unknown
+        bing_wallpaper_changer.run_hooks(HookEvent::PostRender, None, &[]).unwrap();
+        for name in ["warn", "after-warn"] {
+            assert_eq!(input(name)["event"], "POST_RENDER");
+            assert_eq!(input(name)["title"], "");
+            assert_eq!(input(name)["metadata"], json!(null));
+            assert_eq!(environment(name)["BINGWALLPAPER_EVENT"], "POST_RENDER");
+            assert_eq!(environment(name)["BINGWALLPAPER_DATE"], "");
+        }
+
+        // Aborting failure: returned, the next hooks don't run
+        let error = bing_wallpaper_changer
+            .run_hooks(HookEvent::OnError, Some(&metadata()), &[("error", "Can't download image".to_string())])
+            .unwrap_err();
+        assert_eq!(error, "Hook ON_ERROR failed: Command \"sh\" failed: exit status: 1");
+        assert_eq!(input("abort")["error"], "Can't download image");
+        assert_eq!(environment("abort")["BINGWALLPAPER_ERROR"], "Can't download image");
+        assert!(!directory.join("after-abort.json").exists());
+
+        // Hooks of other events don't run
+        assert!(!directory.join("post-apply.json").exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,285 @@
+use image::imageops::FilterType;
+use image::{Rgb, RgbImage};
+
+use crate::bingwallpaper::configuration::{CollageConfiguration, CollageLayout};
+use crate::bingwallpaper::wallpaperarchive::ArchivedImage;
+use crate::bingwallpaper::{TextOverlay, TextOverlayFonts};
+
+/// Default number of images in the collage.
+pub const DEFAULT_COLLAGE_IMAGES: u32 = 7;
+
+/// Share of the width taken by the most recent image in the mosaic layout.
+const MOSAIC_MAIN_IMAGE_RATIO: f32 = 2.0 / 3.0;
+
+/// Location and size of a tile: x, y, width, height.
+type TileBounds = (u32, u32, u32, u32);
+
+/// Lays out archived images into a grid or a mosaic.
+pub struct Collage {}
+
+impl Collage {
+    /// Composes the collage, most recent image first. An image that can't be decoded leaves its tile empty.
+    ///
+    /// # Arguments
+    /// * `collage_configuration` - The collage configuration to use
+    /// * `archived_images` - The images to lay out, most recent first
+    /// * `dimensions` - Width and height of the collage
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use collage::Collage;
+    ///
+    /// let image = Collage::compose(&collage_configuration, &archived_images, (1920, 1080))?;
+    /// ```
+    pub fn compose(collage_configuration: &CollageConfiguration,
+                   archived_images: &[ArchivedImage],
+                   dimensions: (u32, u32)) -> Result<RgbImage, String> {
+        let (width, height) = dimensions;
+        let gutter = collage_configuration.gutter.unwrap_or(0);
+        let corner_radius = collage_configuration.corner_radius.unwrap_or(0);
+        let area = (gutter, gutter, width.saturating_sub(2 * gutter), height.saturating_sub(2 * gutter));
+
+        let tile_count = archived_images.len() as u32;
+        let tiles = match collage_configuration.layout.unwrap_or(CollageLayout::Grid) {
+            CollageLayout::Grid => {
+                let columns = collage_configuration.columns.unwrap_or((tile_count as f32).sqrt().ceil() as u32);
+                Collage::grid_layout(tile_count, columns, area, gutter)
+            }
+            CollageLayout::Mosaic => Collage::mosaic_layout(tile_count, collage_configuration.columns, area, gutter),
+        };
+
+        let mut collage = RgbImage::new(width, height);
+        let mut fonts = TextOverlayFonts::new();
+        for (archived_image, bounds) in archived_images.iter().zip(tiles) {
+            let (_, _, tile_width, tile_height) = bounds;
+            if tile_width == 0 || tile_height == 0 {
+                continue;
+            }
+
+            let image = match image::open(&archived_image.filename) {
+                Err(error) => {
+                    println!("Can't decode archived image {:?}, skipped: {:?}", archived_image.filename, error);
+                    continue;
+                }
+                Ok(image) => image,
+            };
+
+            let mut tile = image.resize_to_fill(tile_width, tile_height, FilterType::Lanczos3).into_rgb8();
+            if let Some(caption_configuration) = &collage_configuration.caption {
+                TextOverlay::apply_overlay(&mut tile, caption_configuration, &archived_image.metadata, &mut fonts);
+            }
+
+            Collage::draw_tile(&mut collage, &tile, bounds, corner_radius);
+        }
+
+        Ok(collage)
+    }
+
+    /// Lays out tiles in rows, the last row being stretched when it is not full.
+    fn grid_layout(tile_count: u32, columns: u32, area: TileBounds, gutter: u32) -> Vec<TileBounds> {
+        if tile_count == 0 {
+            return Vec::new();
+        }
+
+        let columns = columns.clamp(1, tile_count);
+        let rows = tile_count.div_ceil(columns);
+        let (area_x, area_y, area_width, area_height) = area;
+        let mut tiles = Vec::new();
+
+        for row in 0..rows {
+            let row_columns = columns.min(tile_count - row * columns);
+            let (y, tile_height) = Collage::split(area_y, area_height, rows, row, gutter);
+
+            for column in 0..row_columns {
+                let (x, tile_width) = Collage::split(area_x, area_width, row_columns, column, gutter);
+                tiles.push((x, y, tile_width, tile_height));
+            }
+        }
+
+        tiles
+    }
+
+    /// Lays out the most recent image on the left, the others as a grid on the right.
+    fn mosaic_layout(tile_count: u32, columns: Option<u32>, area: TileBounds, gutter: u32) -> Vec<TileBounds> {
+        if tile_count <= 1 {
+            return Collage::grid_layout(tile_count, 1, area, gutter);
+        }
+
+        let (area_x, area_y, area_width, area_height) = area;
+        let main_width = (area_width.saturating_sub(gutter) as f32 * MOSAIC_MAIN_IMAGE_RATIO).round() as u32;
+        let others_area = (
+            area_x + main_width + gutter,
+            area_y,
+            area_width.saturating_sub(main_width + gutter),
+            area_height);
+        let others_columns = columns.unwrap_or(if tile_count - 1 > 4 { 2 } else { 1 });
+
+        let mut tiles = vec![(area_x, area_y, main_width, area_height)];
+        tiles.extend(Collage::grid_layout(tile_count - 1, others_columns, others_area, gutter));
+
+        tiles
+    }
+
+    /// Returns the start and the length of the nth of `count` segments of a length, separated by the gutter.
+    fn split(start: u32, length: u32, count: u32, index: u32, gutter: u32) -> (u32, u32) {
+        let step = (length + gutter) as f32 / count as f32;
+        let segment_start = (index as f32 * step).round() as u32;
+        let segment_end = (((index + 1) as f32 * step).round() as u32).saturating_sub(gutter);
+
+        (start + segment_start, segment_end.saturating_sub(segment_start))
+    }
+
+    /// Draws a tile on the collage, with anti-aliased rounded corners.
+    fn draw_tile(collage: &mut RgbImage, tile: &RgbImage, bounds: TileBounds, corner_radius: u32) {
+        let (tile_x, tile_y, tile_width, tile_height) = bounds;
+        let radius = corner_radius.min(tile_width / 2).min(tile_height / 2) as f32;
+
+        for (x, y, pixel) in tile.enumerate_pixels() {
+            let (collage_x, collage_y) = (tile_x + x, tile_y + y);
+            if collage_x >= collage.width() || collage_y >= collage.height() {
+                continue;
+            }
+
+            // Distance from the center of the corner arc, when inside a corner
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            let dx = (radius - px).max(px - (tile_width as f32 - radius)).max(0.0);
+            let dy = (radius - py).max(py - (tile_height as f32 - radius)).max(0.0);
+            let coverage = if dx > 0.0 && dy > 0.0 {
+                (radius - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0.0, 1.0)
+            } else {
+                1.0
+            };
+
+            if coverage <= 0.0 {
+                continue;
+            }
+
+            let background = *collage.get_pixel(collage_x, collage_y);
+            let blended = Rgb([0, 1, 2].map(|channel| {
+                (pixel[channel] as f32 * coverage + background[channel] as f32 * (1.0 - coverage)).round() as u8
+            }));
+            collage.put_pixel(collage_x, collage_y, blended);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use image::{Rgb, RgbImage};
+
+    use super::{Collage, TileBounds};
+    use crate::bingwallpaper::configuration::{CollageConfiguration, CollageLayout};
+    use crate::bingwallpaper::wallpaperarchive::ArchivedImage;
+    use crate::bingwallpaper::WallpaperMetadata;
+
+    /// Checks that tiles stay in the area and are separated by at least the gutter.
+    fn assert_separated(tiles: &[TileBounds], area: TileBounds, gutter: u32) {
+        let (area_x, area_y, area_width, area_height) = area;
+        for (index, &(x, y, width, height)) in tiles.iter().enumerate() {
+            assert!(width > 0 && height > 0, "{:?}", tiles);
+            assert!(x >= area_x && y >= area_y, "{:?}", tiles);
+            assert!(x + width <= area_x + area_width && y + height <= area_y + area_height, "{:?}", tiles);
+
+            for &(other_x, other_y, other_width, other_height) in &tiles[index + 1..] {
+                let is_apart = x + width + gutter <= other_x
+                    || other_x + other_width + gutter <= x
+                    || y + height + gutter <= other_y
+                    || other_y + other_height + gutter <= y;
+                assert!(is_apart, "{:?}", tiles);
+            }
+        }
+    }
+
+    #[test]
+    fn grid_layout() {
+        assert_eq!(Collage::grid_layout(0, 2, (0, 0, 100, 50), 0), []);
+        assert_eq!(
+            Collage::grid_layout(4, 2, (0, 0, 100, 50), 0),
+            [(0, 0, 50, 25), (50, 0, 50, 25), (0, 25, 50, 25), (50, 25, 50, 25)]);
+
+        // The last row is stretched, the gutter is kept between tiles
+        assert_eq!(
+            Collage::grid_layout(3, 2, (10, 10, 180, 80), 10),
+            [(10, 10, 85, 35), (105, 10, 85, 35), (10, 55, 180, 35)]);
+
+        // More columns than tiles: a single row
+        assert_eq!(Collage::grid_layout(2, 5, (0, 0, 100, 50), 0), [(0, 0, 50, 50), (50, 0, 50, 50)]);
+
+        for tile_count in 1..=12 {
+            for columns in 1..=4 {
+                let tiles = Collage::grid_layout(tile_count, columns, (8, 8, 1904, 1064), 8);
+                assert_eq!(tiles.len(), tile_count as usize);
+                assert_separated(&tiles, (8, 8, 1904, 1064), 8);
+            }
+        }
+    }
+
+    #[test]
+    fn mosaic_layout() {
+        assert_eq!(Collage::mosaic_layout(1, None, (0, 0, 300, 100), 0), [(0, 0, 300, 100)]);
+
+        // Most recent image on two thirds of the width, the others in a column
+        assert_eq!(
+            Collage::mosaic_layout(4, None, (0, 0, 300, 100), 0),
+            [(0, 0, 200, 100), (200, 0, 100, 33), (200, 33, 100, 34), (200, 67, 100, 33)]);
+
+        // Two columns beyond four other images
+        let tiles = Collage::mosaic_layout(6, None, (0, 0, 300, 100), 0);
+        assert_eq!(
+            tiles[1..],
+            [(200, 0, 50, 33), (250, 0, 50, 33), (200, 33, 50, 34), (250, 33, 50, 34), (200, 67, 100, 33)]);
+
+        for tile_count in 1..=12 {
+            let tiles = Collage::mosaic_layout(tile_count, None, (8, 8, 1904, 1064), 8);
+            assert_eq!(tiles.len(), tile_count as usize);
+            assert_separated(&tiles, (8, 8, 1904, 1064), 8);
+        }
+    }
+
+    #[test]
+    fn undecodable_images_are_skipped() {
+        let directory = env::temp_dir().join(format!("bingwallpaper-collage-{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let broken_filename = directory.join("20250622.jpg").to_string_lossy().to_string();
+        let image_filename = directory.join("20250621.png").to_string_lossy().to_string();
+        fs::write(&broken_filename, b"not a JPEG").unwrap();
+        RgbImage::from_pixel(8, 8, Rgb([255, 0, 0])).save(&image_filename).unwrap();
+
+        let metadata = WallpaperMetadata {
+            url: String::new(),
+            title: String::new(),
+            copyright: String::new(),
+            copyright_link: String::new(),
+            photographer: String::new(),
+            location: String::new(),
+            start_date: String::new(),
+            market: String::new(),
+            source: String::new(),
+            video_url: String::new(),
+        };
+        let archived_images = [
+            ArchivedImage { filename: broken_filename, metadata: metadata.clone() },
+            ArchivedImage { filename: image_filename, metadata },
+        ];
+        let collage_configuration = CollageConfiguration {
+            target_filename: None,
+            images: None,
+            layout: Some(CollageLayout::Grid),
+            columns: Some(2),
+            gutter: None,
+            corner_radius: None,
+            caption: None,
+        };
+
+        let collage = Collage::compose(&collage_configuration, &archived_images, (20, 10)).unwrap();
+        assert_eq!(*collage.get_pixel(5, 5), Rgb([0, 0, 0]));
+        assert_eq!(*collage.get_pixel(15, 5), Rgb([255, 0, 0]));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
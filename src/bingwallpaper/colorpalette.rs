@@ -0,0 +1,631 @@
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use image::imageops::FilterType;
+use image::{Rgb, RgbImage};
+use serde_json::{json, Map, Value};
+
+use crate::bingwallpaper::configuration::{PaletteConfiguration, PaletteFormat};
+use crate::bingwallpaper::{BingWallpaperConfiguration, Fingerprint, TextOverlay, WallpaperMetadata};
+
+/// Default number of colors in the palette.
+const DEFAULT_PALETTE_COLORS: u32 = 8;
+
+/// Maximum number of colors in the palette.
+const MAX_PALETTE_COLORS: u32 = 32;
+
+/// Size of the thumbnail the colors are extracted from.
+const SAMPLE_SIZE: u32 = 256;
+
+/// Minimum contrast ratio between the terminal colors and the background.
+const MIN_CONTRAST_RATIO: f32 = 4.5;
+
+/// Every format, exported when none is configured.
+const ALL_FORMATS: [PaletteFormat; 4] = [
+    PaletteFormat::Json,
+    PaletteFormat::Pywal,
+    PaletteFormat::Xresources,
+    PaletteFormat::Css,
+];
+
+/// Dominant color of the image.
+struct PaletteColor {
+    /// The average color of the pixels it represents.
+    color: Rgb<u8>,
+
+    /// Share of the image pixels it represents, between 0.0 and 1.0.
+    population: f32,
+}
+
+/// 16 colors terminal scheme derived from the palette.
+struct TerminalColors {
+    background: Rgb<u8>,
+    foreground: Rgb<u8>,
+    colors: [Rgb<u8>; 16],
+}
+
+/// Extracts the dominant colors of the downloaded image (median cut) and exports them
+/// as JSON, pywal `colors.json`, Xresources and CSS variables.
+pub struct ColorPalette {}
+
+impl ColorPalette {
+    /// Extracts and exports the palette of the downloaded image, if needed.
+    ///
+    /// Only the files whose content changed are written: returns their location, empty if the palette is unchanged.
+    ///
+    /// # Arguments
+    /// * `configuration` - The Bing Wallpaper configuration to use
+    /// * `palette_configuration` - The palette configuration to use
+    /// * `metadata` - The information about the downloaded image
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use colorpalette::ColorPalette;
+    ///
+    /// let exported_filenames = ColorPalette::export(&configuration, &palette_configuration, &metadata)?;
+    /// ```
+    pub fn export(configuration: &BingWallpaperConfiguration,
+                  palette_configuration: &PaletteConfiguration,
+                  metadata: &WallpaperMetadata) -> Result<Vec<String>, String> {
+        let original_filename = configuration.original_filename();
+        let formats = ColorPalette::formats(palette_configuration);
+
+        // Checks if the exported files are up to date
+        let fingerprint = ColorPalette::fingerprint(configuration, palette_configuration, metadata, &original_filename)?;
+        let state_filename = ColorPalette::state_filename(&configuration.palette_filename(PaletteFormat::Json));
+        let is_up_to_date = formats.iter().all(|format| Path::new(&configuration.palette_filename(*format)).exists())
+            && fs::read_to_string(&state_filename).is_ok_and(|previous_fingerprint| previous_fingerprint == fingerprint);
+        if is_up_to_date {
+            return Ok(Vec::new());
+        }
+
+        let image = match image::open(&original_filename) {
+            Err(error) => return Err(format!("Can't decode original image: {:?}", error)),
+            Ok(image) => image,
+        };
+
+        let sample = image.resize(SAMPLE_SIZE, SAMPLE_SIZE, FilterType::Triangle).into_rgb8();
+        let color_count = palette_configuration.colors.unwrap_or(DEFAULT_PALETTE_COLORS).clamp(1, MAX_PALETTE_COLORS);
+        let palette = ColorPalette::extract(&sample, color_count as usize);
+        let terminal_colors = ColorPalette::terminal_colors(&palette);
+
+        let mut exported_filenames = Vec::new();
+        for format in formats {
+            let filename = configuration.palette_filename(format);
+            let content = match format {
+                PaletteFormat::Json => ColorPalette::to_json(configuration, metadata, &palette, &terminal_colors),
+                PaletteFormat::Pywal => ColorPalette::to_pywal(configuration, &terminal_colors),
+                PaletteFormat::Xresources => ColorPalette::to_xresources(&terminal_colors),
+                PaletteFormat::Css => ColorPalette::to_css(configuration, &palette, &terminal_colors),
+            };
+
+            if fs::read_to_string(&filename).is_ok_and(|previous_content| previous_content == content) {
+                continue;
+            }
+
+            if let Err(error) = fs::write(&filename, content) {
+                return Err(format!("Can't store palette {:?}: {:?}", filename, error));
+            }
+
+            exported_filenames.push(filename);
+        }
+
+        if let Err(error) = fs::write(&state_filename, fingerprint) {
+            println!("Can't store palette state: {:?}", error);
+        }
+
+        Ok(exported_filenames)
+    }
+
+    /// Returns the dominant colors of an image file as "#rrggbb", most present first.
+    ///
+    /// The colors are extracted once per version of the file: they are stored next to it, with the size
+    /// and modification time of the file, and reused until the file is rendered again.
+    ///
+    /// # Arguments
+    /// * `image_filename` - The location of the image to analyze
+    /// * `count` - The maximum number of colors
+    #[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
+    pub fn dominant_colors(image_filename: &str, count: u32) -> Result<Vec<String>, String> {
+        let image_metadata = match fs::metadata(image_filename) {
+            Err(error) => return Err(format!("Can't read image {:?}: {:?}", image_filename, error)),
+            Ok(image_metadata) => image_metadata,
+        };
+        let image_modified = image_metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| modified.as_nanos())
+            .unwrap_or_default();
+        let fingerprint = format!("{} {} {}", image_metadata.len(), image_modified, count);
+
+        // Reuses the colors extracted from the same version of the image
+        let colors_filename = ColorPalette::colors_filename(image_filename);
+        if let Ok(content) = fs::read_to_string(&colors_filename) {
+            let mut lines = content.lines();
+            if lines.next() == Some(fingerprint.as_str()) {
+                return Ok(lines.map(str::to_string).collect());
+            }
+        }
+
+        let image = match image::open(image_filename) {
+            Err(error) => return Err(format!("Can't decode image {:?}: {:?}", image_filename, error)),
+            Ok(image) => image,
+        };
+        let sample = image.resize(SAMPLE_SIZE, SAMPLE_SIZE, FilterType::Triangle).into_rgb8();
+        let colors: Vec<String> = ColorPalette::extract(&sample, count.clamp(1, MAX_PALETTE_COLORS) as usize)
+            .iter()
+            .map(|palette_color| ColorPalette::hex(palette_color.color))
+            .collect();
+
+        let mut content = fingerprint;
+        for color in &colors {
+            content.push('\n');
+            content.push_str(color);
+        }
+        if let Err(error) = fs::write(&colors_filename, content) {
+            println!("Can't store dominant colors: {:?}", error);
+        }
+
+        Ok(colors)
+    }
+
+    /// Returns the formats to export, in the configured order.
+    fn formats(palette_configuration: &PaletteConfiguration) -> Vec<PaletteFormat> {
+        palette_configuration.formats.clone().unwrap_or(ALL_FORMATS.to_vec())
+    }
+
+    /// Extracts the dominant colors with the median cut algorithm, most present first.
+    ///
+    /// The box to split is the one with the widest channel range, weighted by its number of pixels,
+    /// so that large uniform areas (ie: sky) get several shades while small details are merged.
+    fn extract(image: &RgbImage, count: usize) -> Vec<PaletteColor> {
+        let mut pixels: Vec<[u8; 3]> = image.pixels().map(|pixel| pixel.0).collect();
+        if pixels.is_empty() {
+            return Vec::new();
+        }
+
+        // Boxes are ranges of the pixels vector
+        let mut boxes = vec![(0, pixels.len())];
+        while boxes.len() < count {
+            let candidate = boxes
+                .iter()
+                .enumerate()
+                .filter(|(_, (start, end))| end - start >= 2)
+                .map(|(idx, (start, end))| {
+                    let (channel, range) = ColorPalette::widest_channel(&pixels[*start..*end]);
+                    (idx, channel, range as f32 * ((end - start) as f32).sqrt())
+                })
+                .filter(|(_, _, score)| *score > 0.0)
+                .max_by(|(_, _, a), (_, _, b)| a.total_cmp(b));
+
+            let (idx, channel, _) = match candidate {
+                None => break,
+                Some(candidate) => candidate,
+            };
+
+            let (start, end) = boxes[idx];
+            pixels[start..end].sort_unstable_by_key(|pixel| pixel[channel]);
+            let middle = start + (end - start) / 2;
+            boxes[idx] = (start, middle);
+            boxes.push((middle, end));
+        }
+
+        let total = pixels.len() as f32;
+        let mut palette: Vec<PaletteColor> = boxes
+            .iter()
+            .map(|(start, end)| {
+                let mut sums = [0u64; 3];
+                for pixel in &pixels[*start..*end] {
+                    for channel in 0..3 {
+                        sums[channel] += pixel[channel] as u64;
+                    }
+                }
+
+                let length = (end - start) as u64;
+                PaletteColor {
+                    color: Rgb(sums.map(|sum| ((sum + length / 2) / length) as u8)),
+                    population: length as f32 / total,
+                }
+            })
+            .collect();
+
+        palette.sort_by(|a, b| b.population.total_cmp(&a.population));
+        palette
+    }
+
+    /// Returns the channel with the widest range of values, and its range.
+    fn widest_channel(pixels: &[[u8; 3]]) -> (usize, u8) {
+        let mut minimums = [u8::MAX; 3];
+        let mut maximums = [u8::MIN; 3];
+        for pixel in pixels {
+            for channel in 0..3 {
+                minimums[channel] = minimums[channel].min(pixel[channel]);
+                maximums[channel] = maximums[channel].max(pixel[channel]);
+            }
+        }
+
+        (0..3)
+            .map(|channel| (channel, maximums[channel] - minimums[channel]))
+            .max_by_key(|(_, range)| *range)
+            .unwrap()
+    }
+
+    /// Derives a dark 16 colors terminal scheme (pywal layout) from the palette:
+    /// the darkest color as background, the lightest as foreground and the most present as accents,
+    /// lightened when needed to stay readable on the background.
+    fn terminal_colors(palette: &[PaletteColor]) -> TerminalColors {
+        let by_luminance = |a: &&PaletteColor, b: &&PaletteColor| {
+            TextOverlay::relative_luminance(a.color).total_cmp(&TextOverlay::relative_luminance(b.color))
+        };
+        let darkest = palette.iter().min_by(by_luminance).map(|color| color.color).unwrap_or(Rgb([0, 0, 0]));
+        let lightest = palette.iter().max_by(by_luminance).map(|color| color.color).unwrap_or(Rgb([255, 255, 255]));
+
+        let background = ColorPalette::mix(darkest, Rgb([0, 0, 0]), 0.6);
+        let foreground = ColorPalette::readable(ColorPalette::mix(lightest, Rgb([255, 255, 255]), 0.75), background);
+
+        let mut colors = [background; 16];
+        for idx in 0..6 {
+            let accent = palette.get(idx % palette.len().max(1)).map(|color| color.color).unwrap_or(foreground);
+            colors[idx + 1] = ColorPalette::readable(accent, background);
+            colors[idx + 9] = ColorPalette::mix(colors[idx + 1], Rgb([255, 255, 255]), 0.2);
+        }
+        colors[7] = foreground;
+        colors[8] = ColorPalette::mix(background, foreground, 0.3);
+        colors[15] = foreground;
+
+        TerminalColors { background, foreground, colors }
+    }
+
+    /// Lightens the color until its contrast ratio with the background is high enough.
+    fn readable(color: Rgb<u8>, background: Rgb<u8>) -> Rgb<u8> {
+        let background_luminance = TextOverlay::relative_luminance(background);
+
+        (0..=20)
+            .map(|step| ColorPalette::mix(color, Rgb([255, 255, 255]), step as f32 / 20.0))
+            .find(|candidate| {
+                let luminance = TextOverlay::relative_luminance(*candidate);
+                (luminance + 0.05) / (background_luminance + 0.05) >= MIN_CONTRAST_RATIO
+            })
+            .unwrap_or(Rgb([255, 255, 255]))
+    }
+
+    /// Mixes two colors, `amount` being the share of the second one.
+    fn mix(color: Rgb<u8>, other: Rgb<u8>, amount: f32) -> Rgb<u8> {
+        Rgb([0, 1, 2].map(|channel| {
+            (color[channel] as f32 * (1.0 - amount) + other[channel] as f32 * amount).round() as u8
+        }))
+    }
+
+    /// Formats a color as "#rrggbb".
+    fn hex(color: Rgb<u8>) -> String {
+        format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+    }
+
+    /// Formats the palette and the terminal scheme as JSON.
+    fn to_json(configuration: &BingWallpaperConfiguration,
+               metadata: &WallpaperMetadata,
+               palette: &[PaletteColor],
+               terminal_colors: &TerminalColors) -> String {
+        let colors: Vec<Value> = palette
+            .iter()
+            .map(|color| json!({
+                "hex": ColorPalette::hex(color.color),
+                "rgb": color.color.0,
+                "population": (color.population * 1000.0).round() / 1000.0,
+            }))
+            .collect();
+
+        let content = json!({
+            "wallpaper": configuration.target_filename,
+            "title": metadata.title,
+            "date": metadata.start_date,
+            "colors": colors,
+            "background": ColorPalette::hex(terminal_colors.background),
+            "foreground": ColorPalette::hex(terminal_colors.foreground),
+            "terminal": terminal_colors.colors.map(ColorPalette::hex),
+        });
+
+        serde_json::to_string_pretty(&content).unwrap_or_default()
+    }
+
+    /// Formats the terminal scheme as pywal `colors.json`.
+    fn to_pywal(configuration: &BingWallpaperConfiguration, terminal_colors: &TerminalColors) -> String {
+        let mut colors = Map::new();
+        for (idx, color) in terminal_colors.colors.iter().enumerate() {
+            colors.insert(format!("color{}", idx), Value::String(ColorPalette::hex(*color)));
+        }
+
+        let content = json!({
+            "wallpaper": configuration.target_filename,
+            "alpha": "100",
+            "special": {
+                "background": ColorPalette::hex(terminal_colors.background),
+                "foreground": ColorPalette::hex(terminal_colors.foreground),
+                "cursor": ColorPalette::hex(terminal_colors.foreground),
+            },
+            "colors": colors,
+        });
+
+        serde_json::to_string_pretty(&content).unwrap_or_default()
+    }
+
+    /// Formats the terminal scheme as X resources, to load with `xrdb -merge`.
+    fn to_xresources(terminal_colors: &TerminalColors) -> String {
+        let mut content = String::from("! Generated by bingwallpaper\n");
+        content.push_str(&format!("*background: {}\n", ColorPalette::hex(terminal_colors.background)));
+        content.push_str(&format!("*foreground: {}\n", ColorPalette::hex(terminal_colors.foreground)));
+        content.push_str(&format!("*cursorColor: {}\n", ColorPalette::hex(terminal_colors.foreground)));
+        for (idx, color) in terminal_colors.colors.iter().enumerate() {
+            content.push_str(&format!("*color{}: {}\n", idx, ColorPalette::hex(*color)));
+        }
+
+        content
+    }
+
+    /// Formats the palette and the terminal scheme as CSS variables.
+    fn to_css(configuration: &BingWallpaperConfiguration,
+              palette: &[PaletteColor],
+              terminal_colors: &TerminalColors) -> String {
+        let mut content = String::from("/* Generated by bingwallpaper */\n:root {\n");
+        content.push_str(&format!("    --wallpaper: url(\"file://{}\");\n", configuration.target_filename));
+        content.push_str(&format!("    --background: {};\n", ColorPalette::hex(terminal_colors.background)));
+        content.push_str(&format!("    --foreground: {};\n", ColorPalette::hex(terminal_colors.foreground)));
+        content.push_str(&format!("    --cursor: {};\n", ColorPalette::hex(terminal_colors.foreground)));
+        for (idx, color) in terminal_colors.colors.iter().enumerate() {
+            content.push_str(&format!("    --color{}: {};\n", idx, ColorPalette::hex(*color)));
+        }
+        for (idx, color) in palette.iter().enumerate() {
+            content.push_str(&format!("    --palette{}: {};\n", idx, ColorPalette::hex(color.color)));
+        }
+        content.push_str("}\n");
+
+        content
+    }
+
+    /// Returns the location of the file holding the fingerprint of the last export.
+    fn state_filename(palette_filename: &str) -> String {
+        let palette_filename_as_path = Path::new(palette_filename);
+        let file_name = palette_filename_as_path.file_name().unwrap_or_default().to_string_lossy();
+
+        palette_filename_as_path
+            .with_file_name(format!(".{}.render", file_name.trim_start_matches('.')))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Returns the location of the file holding the dominant colors of an image.
+    #[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
+    fn colors_filename(image_filename: &str) -> String {
+        let image_filename_as_path = Path::new(image_filename);
+        let file_name = image_filename_as_path.file_name().unwrap_or_default().to_string_lossy();
+
+        image_filename_as_path
+            .with_file_name(format!(".{}.colors", file_name.trim_start_matches('.')))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Computes a fingerprint of everything the exported files depend on:
+    /// the original image, the wallpaper information and the palette settings.
+    fn fingerprint(configuration: &BingWallpaperConfiguration,
+                   palette_configuration: &PaletteConfiguration,
+                   metadata: &WallpaperMetadata,
+                   original_filename: &str) -> Result<String, String> {
+        let original_metadata = match fs::metadata(original_filename) {
+            Err(error) => return Err(format!("Can't read original image: {:?}", error)),
+            Ok(original_metadata) => original_metadata,
+        };
+
+        let original_modified = original_metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| modified.as_nanos())
+            .unwrap_or_default();
+
+        let fingerprint = Fingerprint::new()
+            .add(original_metadata.len().to_le_bytes())
+            .add(original_modified.to_le_bytes())
+            .add(&configuration.target_filename)
+            .add(serde_json::to_string(metadata).unwrap_or_default())
+            .add(serde_json::to_string(&palette_configuration.colors).unwrap_or_default())
+            .add(serde_json::to_string(&palette_configuration.formats).unwrap_or_default())
+            .to_hex();
+
+        Ok(fingerprint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::process;
+
+    use image::{Rgb, RgbImage};
+    use serde_json::Value;
+
+    use super::{ColorPalette, PaletteColor, TerminalColors, MIN_CONTRAST_RATIO};
+    use crate::bingwallpaper::configuration::{PaletteConfiguration, PaletteFormat};
+    use crate::bingwallpaper::{BingWallpaperConfiguration, TextOverlay, WallpaperMetadata};
+
+    fn metadata() -> WallpaperMetadata {
+        WallpaperMetadata {
+            url: "/th?id=OHR.LakeLouise".to_string(),
+            title: "Lake Louise".to_string(),
+            copyright: String::new(),
+            copyright_link: String::new(),
+            photographer: String::new(),
+            location: String::new(),
+            start_date: "20250621".to_string(),
+            market: String::new(),
+            source: "Bing".to_string(),
+            video_url: String::new(),
+        }
+    }
+
+    fn contrast_ratio(color: Rgb<u8>, background: Rgb<u8>) -> f32 {
+        (TextOverlay::relative_luminance(color) + 0.05) / (TextOverlay::relative_luminance(background) + 0.05)
+    }
+
+    #[test]
+    fn median_cut() {
+        // Four quadrants, with some noise: one color each
+        let image = RgbImage::from_fn(64, 64, |x, y| {
+            let noise = ((x + y) % 3) as u8;
+            match (x < 32, y < 32) {
+                (true, true) => Rgb([250 + noise, noise, noise]),
+                (false, true) => Rgb([noise, 250 + noise, noise]),
+                (true, false) => Rgb([noise, noise, 250 + noise]),
+                (false, false) => Rgb([250 + noise, 250 + noise, 250 + noise]),
+            }
+        });
+
+        let palette = ColorPalette::extract(&image, 4);
+        let mut colors: Vec<String> = palette.iter().map(|color| ColorPalette::hex(color.color)).collect();
+        colors.sort();
+        assert_eq!(colors, ["#0101fb", "#01fb01", "#fb0101", "#fbfbfb"]);
+        assert!(palette.iter().all(|color| color.population == 0.25));
+
+        // Never more colors than distinct pixels
+        let palette = ColorPalette::extract(&RgbImage::from_pixel(4, 4, Rgb([10, 20, 30])), 8);
+        assert_eq!(palette.len(), 1);
+        assert_eq!(palette[0].color, Rgb([10, 20, 30]));
+        assert!(ColorPalette::extract(&RgbImage::new(0, 0), 8).is_empty());
+    }
+
+    #[test]
+    fn terminal_colors_are_readable() {
+        let palette = [
+            PaletteColor { color: Rgb([20, 40, 90]), population: 0.5 },
+            PaletteColor { color: Rgb([200, 180, 150]), population: 0.3 },
+            PaletteColor { color: Rgb([60, 20, 20]), population: 0.2 },
+        ];
+
+        let terminal_colors = ColorPalette::terminal_colors(&palette);
+        assert_eq!(terminal_colors.background, Rgb([24, 8, 8]));
+        assert_eq!(terminal_colors.colors[0], terminal_colors.background);
+        assert_eq!(terminal_colors.colors[7], terminal_colors.foreground);
+        assert_eq!(terminal_colors.colors[15], terminal_colors.foreground);
+        for idx in (1..=6).chain(9..=15) {
+            let color = terminal_colors.colors[idx];
+            assert!(contrast_ratio(color, terminal_colors.background) >= MIN_CONTRAST_RATIO, "color{}", idx);
+        }
+
+        // Accents follow the palette order, bright accents are lighter
+        assert!(contrast_ratio(terminal_colors.colors[9], terminal_colors.colors[1]) > 1.0);
+        assert_eq!(terminal_colors.colors[2], Rgb([200, 180, 150]));
+        assert_eq!(terminal_colors.colors[4], terminal_colors.colors[1]);
+
+        // Without palette: black and white
+        let terminal_colors = ColorPalette::terminal_colors(&[]);
+        assert_eq!(terminal_colors.background, Rgb([0, 0, 0]));
+        assert_eq!(terminal_colors.foreground, Rgb([255, 255, 255]));
+    }
+
+    #[test]
+    fn formats() {
+        let mut configuration = BingWallpaperConfiguration::default();
+        configuration.target_filename = "/home/user/bingwallpaper.jpg".to_string();
+        let palette = [PaletteColor { color: Rgb([255, 0, 0]), population: 0.6666 }];
+        let mut colors = [Rgb([0, 0, 0]); 16];
+        colors[15] = Rgb([255, 255, 255]);
+        let terminal_colors = TerminalColors { background: Rgb([0, 0, 0]), foreground: Rgb([255, 255, 255]), colors };
+
+        let xresources = ColorPalette::to_xresources(&terminal_colors);
+        let xresources: Vec<&str> = xresources.lines().collect();
+        assert_eq!(
+            xresources[..5],
+            ["! Generated by bingwallpaper", "*background: #000000", "*foreground: #ffffff", "*cursorColor: #ffffff",
+                "*color0: #000000"]);
+        assert_eq!(xresources.len(), 20);
+        assert_eq!(xresources[19], "*color15: #ffffff");
+
+        let css = ColorPalette::to_css(&configuration, &palette, &terminal_colors);
+        assert!(css.starts_with("/* Generated by bingwallpaper */\n:root {\n"), "{}", css);
+        assert!(css.contains("    --wallpaper: url(\"file:///home/user/bingwallpaper.jpg\");\n"), "{}", css);
+        assert!(css.contains("    --color15: #ffffff;\n    --palette0: #ff0000;\n}\n"), "{}", css);
+
+        let pywal: Value = serde_json::from_str(&ColorPalette::to_pywal(&configuration, &terminal_colors)).unwrap();
+        assert_eq!(pywal["wallpaper"], "/home/user/bingwallpaper.jpg");
+        assert_eq!(pywal["special"]["background"], "#000000");
+        assert_eq!(pywal["special"]["cursor"], "#ffffff");
+        assert_eq!(pywal["colors"].as_object().unwrap().len(), 16);
+        assert_eq!(pywal["colors"]["color15"], "#ffffff");
+
+        let json = ColorPalette::to_json(&configuration, &metadata(), &palette, &terminal_colors);
+        let json: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["title"], "Lake Louise");
+        assert_eq!(json["date"], "20250621");
+        assert_eq!(json["colors"][0]["hex"], "#ff0000");
+        assert_eq!(json["colors"][0]["rgb"], serde_json::json!([255, 0, 0]));
+        assert!((json["colors"][0]["population"].as_f64().unwrap() - 0.667).abs() < 1e-6);
+        assert_eq!(json["terminal"].as_array().unwrap().len(), 16);
+    }
+
+    #[test]
+    fn export_only_changed_files() {
+        let directory = std::env::temp_dir().join(format!("bingwallpaper-palette-{}", process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let mut configuration = BingWallpaperConfiguration::default();
+        configuration.target_filename = directory.join("bingwallpaper.jpg").to_string_lossy().to_string();
+        let palette_configuration = PaletteConfiguration {
+            colors: Some(2),
+            formats: Some(vec![PaletteFormat::Xresources, PaletteFormat::Css]),
+            exec_apply_palette: None,
+        };
+        let save_original = || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            RgbImage::from_pixel(16, 16, Rgb([200, 40, 40])).save(configuration.original_filename()).unwrap();
+        };
+
+        save_original();
+        let exported_filenames = ColorPalette::export(&configuration, &palette_configuration, &metadata()).unwrap();
+        assert_eq!(
+            exported_filenames,
+            [configuration.palette_filename(PaletteFormat::Xresources), configuration.palette_filename(PaletteFormat::Css)]);
+        assert!(ColorPalette::export(&configuration, &palette_configuration, &metadata()).unwrap().is_empty());
+
+        // The same image downloaded again: the palette is the same
+        save_original();
+        assert!(ColorPalette::export(&configuration, &palette_configuration, &metadata()).unwrap().is_empty());
+
+        // Only the file that differs is written again
+        fs::write(configuration.palette_filename(PaletteFormat::Css), "").unwrap();
+        save_original();
+        let exported_filenames = ColorPalette::export(&configuration, &palette_configuration, &metadata()).unwrap();
+        assert_eq!(exported_filenames, [configuration.palette_filename(PaletteFormat::Css)]);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
+    fn dominant_colors_are_extracted_once_per_image() {
+        let directory = std::env::temp_dir().join(format!("bingwallpaper-colors-{}", process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let image_filename = directory.join("wallpaper.png").to_string_lossy().to_string();
+
+        let image = RgbImage::from_fn(256, 4, |x, _| if x < 128 { Rgb([255, 0, 0]) } else { Rgb([0, 0, 255]) });
+        image.save(&image_filename).unwrap();
+        let colors = ColorPalette::dominant_colors(&image_filename, 2).unwrap();
+        assert_eq!(colors, vec!["#ff0000", "#0000ff"]);
+
+        // The stored colors are reused while the image is unchanged
+        let colors_filename = directory.join(".wallpaper.png.colors");
+        let content = fs::read_to_string(&colors_filename).unwrap();
+        fs::write(&colors_filename, content.replace("#0000ff", "#00ff00")).unwrap();
+        assert_eq!(ColorPalette::dominant_colors(&image_filename, 2).unwrap(), vec!["#ff0000", "#00ff00"]);
+
+        // A new image is analyzed again
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        RgbImage::from_pixel(8, 4, Rgb([0, 255, 0])).save(&image_filename).unwrap();
+        assert_eq!(ColorPalette::dominant_colors(&image_filename, 2).unwrap(), vec!["#00ff00"]);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
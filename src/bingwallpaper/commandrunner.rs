@@ -0,0 +1,316 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::bingwallpaper::configuration::ExecCommand;
+
+/// Interval between two checks of the command state.
+const COMMAND_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Time to wait for the remaining output, once the command has exited. Processes started in the background by the
+/// command may keep its output open.
+const COMMAND_OUTPUT_GRACE_PERIOD: Duration = Duration::from_millis(200);
+
+/// Runs the commands configured by the user (ie: `exec_apply_wallpaper`).
+///
+/// Variables (ie: `{target_filename}`) are replaced in each argument, after the command line has been split, so values
+/// containing spaces remain a single argument. They are also exported to the command as environment variables
+/// (ie: `BINGWALLPAPER_TARGET_FILENAME`). The output of the command is logged.
+pub struct CommandRunner {}
+
+impl CommandRunner {
+    /// Executes a command and waits for it. A command exiting with a non-zero status is an error.
+    ///
+    /// # Arguments
+    /// * `command` - The command to execute
+    /// * `variables` - The variables, by name (ie: "target_filename"), and their value
+    /// * `timeout` - The maximum time to wait for the command, which is killed once elapsed
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use commandrunner::CommandRunner;
+    ///
+    /// let command = ExecCommand::CommandLine("feh --bg-fill {target_filename}".to_string());
+    /// CommandRunner::run(&command, &[("target_filename", "/home/user/My Pictures/bing.jpg".to_string())], None)?;
+    /// ```
+    pub fn run(command: &ExecCommand, variables: &[(&str, String)], timeout: Option<Duration>) -> Result<(), String> {
+        CommandRunner::run_with_input(command, variables, None, timeout)
+    }
+
+    /// Executes a command, writing the given input on its standard input, and waits for it.
+    ///
+    /// # Arguments
+    /// * `command` - The command to execute
+    /// * `variables` - The variables, by name (ie: "target_filename"), and their value
+    /// * `input` - The content written on the standard input of the command, none to leave it empty
+    /// * `timeout` - The maximum time to wait for the command, which is killed once elapsed
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use commandrunner::CommandRunner;
+    ///
+    /// let command = ExecCommand::Arguments(vec!["jq".to_string(), ".title".to_string()]);
+    /// CommandRunner::run_with_input(&command, &[], Some("{\"title\": \"Lake Louise\"}"), None)?;
+    /// ```
+    pub fn run_with_input(command: &ExecCommand,
+                          variables: &[(&str, String)],
+                          input: Option<&str>,
+                          timeout: Option<Duration>) -> Result<(), String> {
+        let arguments = match command {
+            ExecCommand::CommandLine(command_line) => CommandRunner::split(command_line)?,
+            ExecCommand::Arguments(arguments) => arguments.clone(),
+        };
+        let arguments: Vec<String> = arguments
+            .iter()
+            .map(|argument| CommandRunner::replace_variables(argument, variables))
+            .collect();
+        let program = match arguments.first() {
+            None => return Ok(()),
+            Some(program) => program.clone(),
+        };
+
+        let mut command_to_run = Command::new(&program);
+        let stdin = if input.is_some() { Stdio::piped() } else { Stdio::null() };
+        command_to_run.args(&arguments[1..]).stdin(stdin).stdout(Stdio::piped()).stderr(Stdio::piped());
+        for (name, value) in variables {
+            command_to_run.env(format!("BINGWALLPAPER_{}", name.to_uppercase()), value);
+        }
+
+        let mut child = match command_to_run.spawn() {
+            Err(error) => return Err(format!("Can't run command {:?}: {:?}", program, error)),
+            Ok(child) => child,
+        };
+
+        // Input is written by another thread, the command may not read it (or only once started)
+        if let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) {
+            let input = input.to_string();
+            thread::spawn(move || {
+                let _ = stdin.write_all(input.as_bytes());
+            });
+        }
+
+        // Output is read by a thread per stream, a full pipe would block the command
+        let (output_sender, output_receiver) = mpsc::channel();
+        if let Some(stdout) = child.stdout.take() {
+            CommandRunner::forward_output(stdout, output_sender.clone());
+        }
+        if let Some(stderr) = child.stderr.take() {
+            CommandRunner::forward_output(stderr, output_sender);
+        }
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let status = loop {
+            match child.try_wait() {
+                Err(error) => return Err(format!("Can't wait for command {:?}: {:?}", program, error)),
+                Ok(Some(status)) => break status,
+                Ok(None) => {}
+            }
+
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                let _ = child.kill();
+                let _ = child.wait();
+                CommandRunner::log_remaining_output(&program, &output_receiver);
+
+                return Err(format!(
+                    "Command {:?} timed out after {} seconds",
+                    program,
+                    timeout.unwrap_or_default().as_secs()));
+            }
+
+            match output_receiver.recv_timeout(COMMAND_POLL_INTERVAL) {
+                Ok(line) => println!("  {} > {}", program, line),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => thread::sleep(COMMAND_POLL_INTERVAL),
+            }
+        };
+        CommandRunner::log_remaining_output(&program, &output_receiver);
+
+        if !status.success() {
+            return Err(format!("Command {:?} failed: {}", program, status));
+        }
+
+        Ok(())
+    }
+
+    /// Splits a command line into arguments, like a POSIX shell does: arguments are separated by whitespaces, single
+    /// quotes keep their content as is, double quotes keep it except for escaped characters (`\"`, `\\`, `\$`, `` \` ``).
+    ///
+    /// Outside quotes, a backslash only escapes a whitespace, a quote or a backslash, so Windows paths
+    /// (ie: `C:\Tools\setwallpaper.exe`) can be written as is.
+    ///
+    /// # Arguments
+    /// * `command_line` - The command line to split
+    fn split(command_line: &str) -> Result<Vec<String>, String> {
+        let mut arguments = Vec::new();
+        let mut argument = String::new();
+        let mut is_in_argument = false;
+        let mut chars = command_line.chars().peekable();
+
+        while let Some(char) = chars.next() {
+            match char {
+                '\'' => {
+                    is_in_argument = true;
+                    loop {
+                        match chars.next() {
+                            None => return Err(format!("Can't parse command {:?}: unterminated quote", command_line)),
+                            Some('\'') => break,
+                            Some(quoted_char) => argument.push(quoted_char),
+                        }
+                    }
+                }
+                '"' => {
+                    is_in_argument = true;
+                    loop {
+                        match chars.next() {
+                            None => return Err(format!("Can't parse command {:?}: unterminated quote", command_line)),
+                            Some('"') => break,
+                            Some('\\') if chars.peek().is_some_and(|next| ['"', '\\', '$', '`'].contains(next)) => {
+                                argument.push(chars.next().unwrap());
+                            }
+                            Some(quoted_char) => argument.push(quoted_char),
+                        }
+                    }
+                }
+                '\\' if chars.peek().is_some_and(|next| next.is_whitespace() || ['"', '\'', '\\'].contains(next)) => {
+                    is_in_argument = true;
+                    argument.push(chars.next().unwrap());
+                }
+                _ if char.is_whitespace() => {
+                    if is_in_argument {
+                        arguments.push(std::mem::take(&mut argument));
+                        is_in_argument = false;
+                    }
+                }
+                _ => {
+                    is_in_argument = true;
+                    argument.push(char);
+                }
+            }
+        }
+
+        if is_in_argument {
+            arguments.push(argument);
+        }
+
+        Ok(arguments)
+    }
+
+    /// Replaces the variables (ie: `{target_filename}`) of an argument, in a single pass: values are inserted as is,
+    /// even if they contain a variable name (ie: a title containing `{date}`). Unknown variables are kept.
+    fn replace_variables(argument: &str, variables: &[(&str, String)]) -> String {
+        let mut replaced_argument = String::with_capacity(argument.len());
+        let mut remaining = argument;
+
+        while let Some(start) = remaining.find('{') {
+            replaced_argument.push_str(&remaining[..start]);
+            remaining = &remaining[start..];
+
+            let value = remaining[1..].find('}').and_then(|end| {
+                let name = &remaining[1..end + 1];
+                variables.iter().find(|(variable_name, _)| *variable_name == name).map(|(_, value)| (end + 2, value))
+            });
+            match value {
+                Some((variable_end, value)) => {
+                    replaced_argument.push_str(value);
+                    remaining = &remaining[variable_end..];
+                }
+                None => {
+                    replaced_argument.push('{');
+                    remaining = &remaining[1..];
+                }
+            }
+        }
+
+        replaced_argument.push_str(remaining);
+        replaced_argument
+    }
+
+    /// Sends each line of a command output stream to the receiver, from a dedicated thread.
+    fn forward_output(stream: impl Read + Send + 'static, output_sender: Sender<String>) {
+        thread::spawn(move || {
+            for line in BufReader::new(stream).split(b'\n').map_while(Result::ok) {
+                let line = String::from_utf8_lossy(&line).trim_end().to_string();
+                if output_sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Logs the output not logged yet, without waiting for processes started in the background by the command.
+    fn log_remaining_output(program: &str, output_receiver: &Receiver<String>) {
+        while let Ok(line) = output_receiver.recv_timeout(COMMAND_OUTPUT_GRACE_PERIOD) {
+            println!("  {} > {}", program, line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CommandRunner;
+
+    #[test]
+    fn split_on_whitespaces() {
+        assert_eq!(CommandRunner::split("  feh   --bg-fill\t{target_filename} ").unwrap(), vec![
+            "feh", "--bg-fill", "{target_filename}",
+        ]);
+        assert!(CommandRunner::split("   ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn split_quotes() {
+        assert_eq!(CommandRunner::split("notify-send 'Bing Wallpaper' \"{title}\"").unwrap(), vec![
+            "notify-send", "Bing Wallpaper", "{title}",
+        ]);
+        assert_eq!(CommandRunner::split("echo '' \"\" a'b c'\"d\"").unwrap(), vec!["echo", "", "", "ab cd"]);
+        assert_eq!(CommandRunner::split("echo 'It\\s' \"$HOME\"").unwrap(), vec!["echo", "It\\s", "$HOME"]);
+    }
+
+    #[test]
+    fn split_escapes() {
+        assert_eq!(CommandRunner::split("echo a\\ b \\\"c\\' d\\\\").unwrap(), vec!["echo", "a b", "\"c'", "d\\"]);
+        assert_eq!(
+            CommandRunner::split("echo \"a \\\"b\\\" \\$c \\` \\\\ \\d\"").unwrap(),
+            vec!["echo", "a \"b\" $c ` \\ \\d"]);
+    }
+
+    #[test]
+    fn split_unterminated_quotes() {
+        assert!(CommandRunner::split("echo 'a").is_err());
+        assert!(CommandRunner::split("echo \"a\\\"").is_err());
+        assert!(CommandRunner::split("echo a\"").is_err());
+    }
+
+    #[test]
+    fn split_windows_paths() {
+        assert_eq!(CommandRunner::split("C:\\Tools\\setwallpaper.exe {target_filename}").unwrap(), vec![
+            "C:\\Tools\\setwallpaper.exe", "{target_filename}",
+        ]);
+        assert_eq!(CommandRunner::split("\"C:\\Program Files\\Tools\\set.exe\" /s").unwrap(), vec![
+            "C:\\Program Files\\Tools\\set.exe", "/s",
+        ]);
+        assert_eq!(CommandRunner::split("'\\\\server\\share\\set.exe'").unwrap(), vec!["\\\\server\\share\\set.exe"]);
+    }
+
+    #[test]
+    fn replace_variables_once() {
+        let variables = [
+            ("title", "Lake {date} at {target_filename}".to_string()),
+            ("date", "20250621".to_string()),
+            ("target_filename", "/tmp/bing.jpg".to_string()),
+        ];
+
+        assert_eq!(CommandRunner::replace_variables("{title}", &variables), "Lake {date} at {target_filename}");
+        assert_eq!(
+            CommandRunner::replace_variables("--file={target_filename}:{date}", &variables),
+            "--file=/tmp/bing.jpg:20250621");
+        assert_eq!(
+            CommandRunner::replace_variables("{{date}} {unknown} {date", &variables),
+            "{20250621} {unknown} {date");
+        assert_eq!(CommandRunner::replace_variables("été {date}}", &variables), "été 20250621}");
+    }
+}
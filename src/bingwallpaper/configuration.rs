@@ -1,6 +1,11 @@
+use std::collections::BTreeMap;
 use std::ffi::OsString;
+use std::fmt;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
+use serde::de::{self, Visitor};
+use serde::{Deserializer, Serializer};
 use serde_derive::{Deserialize, Serialize};
 use winit::dpi::PhysicalSize;
 use winit::event_loop::EventLoop;
@@ -12,11 +17,418 @@ pub struct BingWallpaperConfiguration {
     pub(crate) image_dimension_width: u32,
     pub(crate) image_dimension_height: u32,
     pub(crate) target_filename: String,
-    pub(crate) text_overlay_position: Option<String>,
-    pub(crate) text_overlay_position_offset_x: Option<u32>,
-    pub(crate) text_overlay_position_offset_y: Option<u32>,
-    pub(crate) exec_apply_wallpaper: Option<String>,
+    pub(crate) original_filename: Option<String>,
+    pub(crate) output_format: Option<OutputFormatConfiguration>,
+    pub(crate) exec_apply_wallpaper: Option<ExecCommand>,
+    pub(crate) exec_timeout_second: Option<u64>,
+    pub(crate) hooks: Option<Vec<HookConfiguration>>,
+    pub(crate) wallpaper_setter: Option<WallpaperSetterBackend>,
+    pub(crate) fit_mode: Option<WallpaperFitMode>,
+    pub(crate) gnome: Option<GnomeConfiguration>,
+    pub(crate) plasma: Option<PlasmaConfiguration>,
+    pub(crate) wayland: Option<WaylandConfiguration>,
+    pub(crate) portal: Option<PortalConfiguration>,
     pub(crate) proxy_url: Option<String>,
+    pub(crate) market: Option<String>,
+    pub(crate) filters: Option<Vec<ImageFilterConfiguration>>,
+    pub(crate) text_overlay: Option<TextOverlayConfiguration>,
+    pub(crate) dark_variant: Option<WallpaperVariantConfiguration>,
+    pub(crate) night_variant: Option<WallpaperVariantConfiguration>,
+    pub(crate) lock_screen_variant: Option<WallpaperVariantConfiguration>,
+    pub(crate) greeter: Option<GreeterConfiguration>,
+    pub(crate) archive_directory: Option<String>,
+    pub(crate) archive_max_images: Option<u32>,
+    pub(crate) collage: Option<CollageConfiguration>,
+    pub(crate) palette: Option<PaletteConfiguration>,
+    pub(crate) eink: Option<EinkConfiguration>,
+    pub(crate) video: Option<VideoConfiguration>,
+    pub(crate) latitude: Option<f64>,
+    pub(crate) longitude: Option<f64>,
+
+    // Legacy text overlay settings, only read to migrate old configuration files
+    #[serde(default, skip_serializing)]
+    text_overlay_position: Option<String>,
+    #[serde(default, skip_serializing)]
+    text_overlay_position_offset_x: Option<u32>,
+    #[serde(default, skip_serializing)]
+    text_overlay_position_offset_y: Option<u32>,
+}
+
+/// Output format of the rendered wallpaper
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OutputFormatConfiguration {
+    Jpeg {
+        quality: Option<u8>,
+        progressive: Option<bool>,
+    },
+    Png {
+        compression_level: Option<u8>,
+    },
+    Webp {
+        lossless: Option<bool>,
+        quality: Option<f32>,
+    },
+    Avif {
+        quality: Option<u8>,
+        speed: Option<u8>,
+    },
+}
+
+/// Command to execute: a command line (ie: `"feh --bg-fill '{target_filename}'"`), split into arguments like a POSIX
+/// shell does, or the list of its arguments (ie: `["feh", "--bg-fill", "{target_filename}"]`).
+#[derive(Clone, PartialEq)]
+pub enum ExecCommand {
+    CommandLine(String),
+    Arguments(Vec<String>),
+}
+
+/// Command executed at a step of the wallpaper update
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HookConfiguration {
+    pub(crate) event: HookEvent,
+    pub(crate) command: ExecCommand,
+    pub(crate) on_failure: Option<HookFailurePolicy>,
+}
+
+/// Hook: step of the wallpaper update after which (or before which) the command is executed.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum HookEvent {
+    PreFetch,
+    PostDownload,
+    PostRender,
+    PostApply,
+    OnError,
+}
+
+/// Hook: what happens when the command fails.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum HookFailurePolicy {
+    Ignore,
+    Warn,
+    Abort,
+}
+
+/// Backend used to apply the wallpaper on Linux, detected from the desktop environment when not configured
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum WallpaperSetterBackend {
+    Gnome,
+    Cinnamon,
+    Kde,
+    Xfce,
+    Mate,
+    Lxqt,
+    Lxde,
+    Budgie,
+    Sway,
+    Hyprland,
+    Swww,
+    Swaybg,
+    X11,
+    Portal,
+}
+
+/// How the wallpaper fills the screen, converted into the setting of each desktop environment
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum WallpaperFitMode {
+    Zoom,
+    Fit,
+    Stretch,
+    Center,
+    Tile,
+    Span,
+}
+
+/// GNOME (and Cinnamon, Budgie) wallpaper settings
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GnomeConfiguration {
+    pub(crate) color_shading_type: Option<GnomeColorShadingType>,
+}
+
+/// GNOME: how the background colors, visible around the image, are drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum GnomeColorShadingType {
+    Solid,
+    Horizontal,
+    Vertical,
+}
+
+/// KDE Plasma wallpaper settings
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlasmaConfiguration {
+    pub(crate) fill_mode: Option<PlasmaFillMode>,
+    pub(crate) screen_filenames: Option<Vec<String>>,
+}
+
+/// KDE Plasma: how the image fills the screen.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PlasmaFillMode {
+    Stretch,
+    PreserveAspectFit,
+    PreserveAspectCrop,
+    Tile,
+    TileVertically,
+    TileHorizontally,
+    Pad,
+}
+
+/// Wayland compositors (sway, Hyprland, river) wallpaper settings
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WaylandConfiguration {
+    pub(crate) output_filenames: Option<BTreeMap<String, String>>,
+    pub(crate) transition_type: Option<SwwwTransitionType>,
+    pub(crate) transition_duration: Option<f32>,
+}
+
+/// swww: animation played when the wallpaper changes.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SwwwTransitionType {
+    None,
+    Simple,
+    Fade,
+    Left,
+    Right,
+    Top,
+    Bottom,
+    Wipe,
+    Wave,
+    Grow,
+    Center,
+    Outer,
+    Any,
+    Random,
+}
+
+/// XDG Desktop Portal wallpaper settings, used inside sandboxes (ie: Flatpak, Snap)
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PortalConfiguration {
+    pub(crate) set_on: Option<PortalSetOn>,
+    pub(crate) show_preview: Option<bool>,
+}
+
+/// XDG Desktop Portal: where the wallpaper is applied.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PortalSetOn {
+    Background,
+    Lockscreen,
+    Both,
+}
+
+/// Image adjustment filter, applied to the image before the text overlay
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ImageFilterConfiguration {
+    Dim {
+        amount: Option<f32>,
+    },
+    Blur {
+        sigma: Option<f32>,
+    },
+    Grayscale,
+    Saturation {
+        factor: Option<f32>,
+    },
+    Contrast {
+        factor: Option<f32>,
+    },
+    Vignette {
+        strength: Option<f32>,
+        radius: Option<f32>,
+    },
+    Tint {
+        temperature: f32,
+    },
+}
+
+/// Variant of the wallpaper rendered alongside the main one, from the same image
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WallpaperVariantConfiguration {
+    pub(crate) target_filename: Option<String>,
+    pub(crate) filters: Option<Vec<ImageFilterConfiguration>>,
+    pub(crate) text_overlay: Option<bool>,
+}
+
+/// Collage of the latest archived images, applied instead of the wallpaper
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CollageConfiguration {
+    pub(crate) target_filename: Option<String>,
+    pub(crate) images: Option<u32>,
+    pub(crate) layout: Option<CollageLayout>,
+    pub(crate) columns: Option<u32>,
+    pub(crate) gutter: Option<u32>,
+    pub(crate) corner_radius: Option<u32>,
+    pub(crate) caption: Option<TextOverlayConfiguration>,
+}
+
+/// Collage: arrangement of the images.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CollageLayout {
+    Grid,
+    Mosaic,
+}
+
+/// Color palette extracted from the downloaded image, exported for other applications
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PaletteConfiguration {
+    pub(crate) colors: Option<u32>,
+    pub(crate) formats: Option<Vec<PaletteFormat>>,
+    pub(crate) exec_apply_palette: Option<ExecCommand>,
+}
+
+/// Palette: file format of an exported palette.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PaletteFormat {
+    Json,
+    Pywal,
+    Xresources,
+    Css,
+}
+
+/// Video wallpaper, downloaded when Bing publishes a video next to the image, and played over the wallpaper
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VideoConfiguration {
+    pub(crate) target_filename: Option<String>,
+    pub(crate) backend: Option<VideoBackend>,
+    pub(crate) exec_apply_video: Option<ExecCommand>,
+}
+
+/// Video wallpaper: animated wallpaper backend, detected from the session when not configured.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum VideoBackend {
+    Mpvpaper,
+    Xwinwrap,
+    Command,
+}
+
+/// E-ink (e-paper) panel output, rendered from the same image as the wallpaper
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EinkConfiguration {
+    pub(crate) target_filename: Option<String>,
+    pub(crate) image_dimension_width: u32,
+    pub(crate) image_dimension_height: u32,
+    pub(crate) palette: Option<EinkPalette>,
+    pub(crate) dithering: Option<EinkDithering>,
+    pub(crate) format: Option<EinkFormat>,
+    pub(crate) filters: Option<Vec<ImageFilterConfiguration>>,
+    pub(crate) text_overlay: Option<TextOverlayConfiguration>,
+}
+
+/// E-ink: colors the panel can display.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum EinkPalette {
+    Mono,
+    Gray4,
+    Acep7,
+}
+
+/// E-ink: dithering applied when reducing the image to the panel colors.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum EinkDithering {
+    None,
+    FloydSteinberg,
+    Ordered,
+}
+
+/// E-ink: file format of the output.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum EinkFormat {
+    Png,
+    Bmp,
+    Raw,
+}
+
+/// Greeter (login screen) background configuration
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GreeterConfiguration {
+    pub(crate) target_filename: Option<String>,
+    pub(crate) image_dimension_width: Option<u32>,
+    pub(crate) image_dimension_height: Option<u32>,
+    pub(crate) filters: Option<Vec<ImageFilterConfiguration>>,
+    pub(crate) text_overlay: Option<bool>,
+    pub(crate) display_managers: Option<Vec<DisplayManager>>,
+    pub(crate) sddm_theme: Option<String>,
+}
+
+/// Greeter: display manager showing the login screen.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DisplayManager {
+    Lightdm,
+    Sddm,
+}
+
+/// Text overlay configuration
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TextOverlayConfiguration {
+    pub(crate) template: Option<String>,
+    pub(crate) anchor: TextOverlayAnchor,
+    pub(crate) alignment: Option<TextOverlayAlignment>,
+    pub(crate) margin_x: Option<TextOverlayLength>,
+    pub(crate) margin_y: Option<TextOverlayLength>,
+    pub(crate) offset_x: Option<i32>,
+    pub(crate) offset_y: Option<i32>,
+    pub(crate) max_line_width: Option<TextOverlayLength>,
+    pub(crate) font_size: Option<f32>,
+    pub(crate) font_files: Option<Vec<String>>,
+    pub(crate) font_families: Option<Vec<String>>,
+    pub(crate) color: Option<TextOverlayColor>,
+    pub(crate) min_contrast_ratio: Option<f32>,
+}
+
+/// Text overlay: location of the text block on the image. `Auto` picks the least busy location,
+/// `AutoBottom` picks the least busy location at the bottom of the image.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TextOverlayAnchor {
+    Auto,
+    AutoBottom,
+    TopLeft,
+    TopCenter,
+    TopRight,
+    MiddleLeft,
+    Center,
+    MiddleRight,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+}
+
+/// Text overlay: alignment of the lines inside the text block.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TextOverlayAlignment {
+    Left,
+    Center,
+    Right,
+}
+
+/// Text overlay: text color, either `"AUTO"` (light or dark depending on the image) or `"#RRGGBB"`.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum TextOverlayColor {
+    Auto,
+    Rgb(u8, u8, u8),
+}
+
+/// Text overlay: length expressed in pixels (ie: `60` or `"60px"`) or in percent of the image dimension (ie: `"5%"`).
+#[derive(Clone, Copy, PartialEq)]
+pub enum TextOverlayLength {
+    Pixels(u32),
+    Percent(f32),
 }
 
 
@@ -28,15 +440,351 @@ impl Default for BingWallpaperConfiguration {
             image_dimension_height: 1080,
             image_dimension_width: 1920,
             target_filename: "/tmp/bingwallpaper.jpg".into(),
+            original_filename: None,
+            output_format: None,
+            exec_apply_wallpaper: None,
+            exec_timeout_second: None,
+            hooks: None,
+            wallpaper_setter: None,
+            fit_mode: None,
+            gnome: None,
+            plasma: None,
+            wayland: None,
+            portal: None,
+            proxy_url: None,
+            market: None,
+            filters: None,
+            text_overlay: None,
+            dark_variant: None,
+            night_variant: None,
+            lock_screen_variant: None,
+            greeter: None,
+            archive_directory: None,
+            archive_max_images: None,
+            collage: None,
+            palette: None,
+            eink: None,
+            video: None,
+            latitude: None,
+            longitude: None,
             text_overlay_position: None,
             text_overlay_position_offset_x: None,
             text_overlay_position_offset_y: None,
-            exec_apply_wallpaper: None,
-            proxy_url: None,
         }
     }
 }
 
+/// `GreeterConfiguration` implements `Default`
+impl Default for GreeterConfiguration {
+    fn default() -> Self {
+        Self {
+            target_filename: None,
+            image_dimension_width: None,
+            image_dimension_height: None,
+            filters: None,
+            text_overlay: None,
+            display_managers: None,
+            sddm_theme: None,
+        }
+    }
+}
+
+/// `GnomeConfiguration` implements `Default`
+impl Default for GnomeConfiguration {
+    fn default() -> Self {
+        Self {
+            color_shading_type: None,
+        }
+    }
+}
+
+/// `PlasmaConfiguration` implements `Default`
+impl Default for PlasmaConfiguration {
+    fn default() -> Self {
+        Self {
+            fill_mode: None,
+            screen_filenames: None,
+        }
+    }
+}
+
+/// `WaylandConfiguration` implements `Default`
+impl Default for WaylandConfiguration {
+    fn default() -> Self {
+        Self {
+            output_filenames: None,
+            transition_type: None,
+            transition_duration: None,
+        }
+    }
+}
+
+/// `PortalConfiguration` implements `Default`
+impl Default for PortalConfiguration {
+    fn default() -> Self {
+        Self {
+            set_on: None,
+            show_preview: None,
+        }
+    }
+}
+
+/// `TextOverlayConfiguration` implements `Default`
+impl Default for TextOverlayConfiguration {
+    fn default() -> Self {
+        Self {
+            template: None,
+            anchor: TextOverlayAnchor::TopLeft,
+            alignment: None,
+            margin_x: None,
+            margin_y: None,
+            offset_x: None,
+            offset_y: None,
+            max_line_width: None,
+            font_size: None,
+            font_files: None,
+            font_families: None,
+            color: None,
+            min_contrast_ratio: None,
+        }
+    }
+}
+
+impl OutputFormatConfiguration {
+    /// Returns the default output format matching the file extension.
+    ///
+    /// # Arguments
+    /// * `filename` - The file name to use (ie: "/tmp/wallpaper.jpg")
+    pub(crate) fn from_filename(filename: &str) -> Result<OutputFormatConfiguration, String> {
+        let extension = Path::new(filename)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match extension.as_str() {
+            "jpg" | "jpeg" => Ok(OutputFormatConfiguration::Jpeg { quality: None, progressive: None }),
+            "png" => Ok(OutputFormatConfiguration::Png { compression_level: None }),
+            "webp" => Ok(OutputFormatConfiguration::Webp { lossless: None, quality: None }),
+            "avif" => Ok(OutputFormatConfiguration::Avif { quality: None, speed: None }),
+            _ => Err(format!("Unsupported wallpaper file extension: {:?}", filename)),
+        }
+    }
+
+    /// Returns the file extensions accepted for this format.
+    pub(crate) fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            OutputFormatConfiguration::Jpeg { .. } => &["jpg", "jpeg"],
+            OutputFormatConfiguration::Png { .. } => &["png"],
+            OutputFormatConfiguration::Webp { .. } => &["webp"],
+            OutputFormatConfiguration::Avif { .. } => &["avif"],
+        }
+    }
+}
+
+impl TextOverlayAnchor {
+    /// Returns the alignment matching the anchor column (left, center or right).
+    pub(crate) fn default_alignment(&self) -> TextOverlayAlignment {
+        match self {
+            TextOverlayAnchor::Auto
+            | TextOverlayAnchor::AutoBottom
+            | TextOverlayAnchor::TopLeft
+            | TextOverlayAnchor::MiddleLeft
+            | TextOverlayAnchor::BottomLeft => TextOverlayAlignment::Left,
+            TextOverlayAnchor::TopCenter | TextOverlayAnchor::Center | TextOverlayAnchor::BottomCenter =>
+                TextOverlayAlignment::Center,
+            TextOverlayAnchor::TopRight | TextOverlayAnchor::MiddleRight | TextOverlayAnchor::BottomRight =>
+                TextOverlayAlignment::Right,
+        }
+    }
+
+    /// Returns the anchor moved to the bottom of the image, leaving the top and the
+    /// center of the image (where lock screens display the clock) clear.
+    pub(crate) fn away_from_clock(&self) -> TextOverlayAnchor {
+        match self {
+            TextOverlayAnchor::Auto | TextOverlayAnchor::AutoBottom => TextOverlayAnchor::AutoBottom,
+            TextOverlayAnchor::TopLeft | TextOverlayAnchor::MiddleLeft | TextOverlayAnchor::BottomLeft =>
+                TextOverlayAnchor::BottomLeft,
+            TextOverlayAnchor::TopCenter | TextOverlayAnchor::Center | TextOverlayAnchor::BottomCenter =>
+                TextOverlayAnchor::BottomCenter,
+            TextOverlayAnchor::TopRight | TextOverlayAnchor::MiddleRight | TextOverlayAnchor::BottomRight =>
+                TextOverlayAnchor::BottomRight,
+        }
+    }
+}
+
+/// `TextOverlayColor` implements `TryFrom<String>`
+impl TryFrom<String> for TextOverlayColor {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("AUTO") {
+            return Ok(TextOverlayColor::Auto);
+        }
+
+        let hex = value.strip_prefix('#').unwrap_or(value);
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Invalid color value: {:?}", value));
+        }
+
+        let channel = |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16).unwrap();
+        Ok(TextOverlayColor::Rgb(channel(0), channel(2), channel(4)))
+    }
+}
+
+/// `TextOverlayColor` implements `Into<String>`
+impl From<TextOverlayColor> for String {
+    fn from(value: TextOverlayColor) -> Self {
+        match value {
+            TextOverlayColor::Auto => String::from("AUTO"),
+            TextOverlayColor::Rgb(red, green, blue) => format!("#{:02X}{:02X}{:02X}", red, green, blue),
+        }
+    }
+}
+
+impl TextOverlayLength {
+    /// Resolves the length in pixels.
+    ///
+    /// # Arguments
+    /// * `reference` - The dimension (in pixels) used when length is expressed in percent
+    pub(crate) fn resolve(&self, reference: u32) -> i32 {
+        match self {
+            TextOverlayLength::Pixels(value) => *value as i32,
+            TextOverlayLength::Percent(value) => (reference as f32 * value / 100.0).round() as i32,
+        }
+    }
+}
+
+/// `TextOverlayLength` implements `FromStr`
+impl FromStr for TextOverlayLength {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+
+        if let Some(percent) = value.strip_suffix('%') {
+            match percent.trim().parse::<f32>() {
+                Ok(percent) if percent >= 0.0 => Ok(TextOverlayLength::Percent(percent)),
+                _ => Err(format!("Invalid percent value: {:?}", value)),
+            }
+        } else {
+            match value.strip_suffix("px").unwrap_or(value).trim().parse::<u32>() {
+                Ok(pixels) => Ok(TextOverlayLength::Pixels(pixels)),
+                Err(_) => Err(format!("Invalid pixel value: {:?}", value)),
+            }
+        }
+    }
+}
+
+/// `TextOverlayLength` implements `Display`
+impl fmt::Display for TextOverlayLength {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextOverlayLength::Pixels(value) => write!(formatter, "{}px", value),
+            TextOverlayLength::Percent(value) => write!(formatter, "{}%", value),
+        }
+    }
+}
+
+/// `TextOverlayLength` implements `Serialize`
+impl serde::Serialize for TextOverlayLength {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// `TextOverlayLength` implements `Deserialize`
+impl<'de> serde::Deserialize<'de> for TextOverlayLength {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TextOverlayLengthVisitor;
+
+        impl Visitor<'_> for TextOverlayLengthVisitor {
+            type Value = TextOverlayLength;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a number of pixels (ie: 60 or \"60px\") or a percentage (ie: \"5%\")")
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+                u32::try_from(value)
+                    .map(TextOverlayLength::Pixels)
+                    .map_err(|_| E::custom(format!("Invalid pixel value: {}", value)))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+                u32::try_from(value)
+                    .map(TextOverlayLength::Pixels)
+                    .map_err(|_| E::custom(format!("Invalid pixel value: {}", value)))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                TextOverlayLength::from_str(value).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(TextOverlayLengthVisitor)
+    }
+}
+
+/// `HookEvent` implements `Display`
+impl fmt::Display for HookEvent {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            HookEvent::PreFetch => "PRE_FETCH",
+            HookEvent::PostDownload => "POST_DOWNLOAD",
+            HookEvent::PostRender => "POST_RENDER",
+            HookEvent::PostApply => "POST_APPLY",
+            HookEvent::OnError => "ON_ERROR",
+        };
+
+        formatter.write_str(name)
+    }
+}
+
+/// `ExecCommand` implements `Serialize`
+impl serde::Serialize for ExecCommand {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ExecCommand::CommandLine(command_line) => serializer.serialize_str(command_line),
+            ExecCommand::Arguments(arguments) => serde::Serialize::serialize(arguments, serializer),
+        }
+    }
+}
+
+/// `ExecCommand` implements `Deserialize`
+impl<'de> serde::Deserialize<'de> for ExecCommand {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ExecCommandVisitor;
+
+        impl<'de> Visitor<'de> for ExecCommandVisitor {
+            type Value = ExecCommand;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a command line (ie: \"feh --bg-fill {target_filename}\") or a list of arguments")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                Ok(ExecCommand::CommandLine(value.to_string()))
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut arguments = Vec::new();
+                while let Some(argument) = seq.next_element::<String>()? {
+                    arguments.push(argument);
+                }
+
+                if arguments.is_empty() {
+                    return Err(de::Error::custom("Invalid command: the list of arguments is empty"));
+                }
+
+                Ok(ExecCommand::Arguments(arguments))
+            }
+        }
+
+        deserializer.deserialize_any(ExecCommandVisitor)
+    }
+}
+
 impl BingWallpaperConfiguration {
     /// Initializes a new configuration file.
     ///
@@ -67,21 +815,26 @@ impl BingWallpaperConfiguration {
 
         println!("  > Wallpaper dimension: {}x{}", config.image_dimension_width, config.image_dimension_height);
 
-        // Target filename ($HOME/.bingwallpaper.png)
-        config.target_filename = std::env::home_dir()
-            .map(PathBuf::into_os_string)
-            .map(OsString::into_string)
-            .map(Result::unwrap)
-            .map(|mut location| {
-                #[cfg(target_os = "macos")]
-                location.push_str("/Pictures/bing-wallpaper.png");
+        // Target filename ($HOME/.bingwallpaper.jpg, or $XDG_DATA_HOME/bingwallpaper.jpg inside a Flatpak sandbox)
+        config.target_filename = match BingWallpaperConfiguration::flatpak_directories() {
+            Some((_, data_directory)) => data_directory.join("bingwallpaper.jpg").to_string_lossy().to_string(),
+            None => std::env::home_dir()
+                .map(PathBuf::into_os_string)
+                .map(OsString::into_string)
+                .map(Result::unwrap)
+                .map(|mut location| {
+                    #[cfg(target_os = "macos")]
+                    location.push_str("/Pictures/bing-wallpaper.jpg");
+
+                    #[cfg(not(target_os = "macos"))]
+                    location.push_str("/.bingwallpaper.jpg");
 
-                #[cfg(not(target_os = "macos"))]
-                location.push_str("/.bingwallpaper.png");
+                    location
+                })
+                .unwrap(),
+        };
 
-                location
-            })
-            .unwrap();
+        config.output_format = Some(OutputFormatConfiguration::Jpeg { quality: None, progressive: None });
 
         println!("  > Target location: {}", config.target_filename);
 
@@ -112,15 +865,169 @@ impl BingWallpaperConfiguration {
             panic!("Configuration file does not exist: {:?}", file_name)
         }
 
-        match confy::load_path(file_name) {
+        match confy::load_path::<BingWallpaperConfiguration>(file_name) {
             Err(error) => panic!("Can't load or create configuration file: {:?}", error),
-            Ok(configuration) => configuration,
+            Ok(mut configuration) => {
+                configuration.migrate_legacy_text_overlay();
+                configuration
+            }
+        }
+    }
+
+    /// Returns the location where the image is stored as downloaded, before any rendering.
+    /// Default location is a hidden file next to `target_filename`.
+    pub(crate) fn original_filename(&self) -> String {
+        if let Some(original_filename) = &self.original_filename {
+            return original_filename.clone();
+        }
+
+        let target_filename_as_path = Path::new(&self.target_filename);
+        let file_stem = target_filename_as_path.file_stem().unwrap_or_default().to_string_lossy();
+
+        target_filename_as_path
+            .with_file_name(format!(".{}.original.jpg", file_stem.trim_start_matches('.')))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Returns the location of the dark variant of the wallpaper, if enabled.
+    /// Default location is next to `target_filename`, with a "-dark" suffix.
+    pub(crate) fn dark_variant_filename(&self) -> Option<String> {
+        self.dark_variant.as_ref().map(|dark_variant| self.variant_filename(dark_variant.target_filename.as_ref(), "dark"))
+    }
+
+    /// Returns the location of the night variant of the wallpaper, if enabled.
+    /// Default location is next to `target_filename`, with a "-night" suffix.
+    pub(crate) fn night_variant_filename(&self) -> Option<String> {
+        self.night_variant
+            .as_ref()
+            .map(|night_variant| self.variant_filename(night_variant.target_filename.as_ref(), "night"))
+    }
+
+    /// Returns the location of the lock screen variant of the wallpaper, if enabled.
+    /// Default location is next to `target_filename`, with a "-lock" suffix.
+    pub(crate) fn lock_screen_variant_filename(&self) -> Option<String> {
+        self.lock_screen_variant
+            .as_ref()
+            .map(|lock_screen_variant| self.variant_filename(lock_screen_variant.target_filename.as_ref(), "lock"))
+    }
+
+    /// Returns the location (latitude, longitude) used to compute sunrise and sunset, if configured.
+    pub(crate) fn location(&self) -> Option<(f64, f64)> {
+        match (self.latitude, self.longitude) {
+            (Some(latitude), Some(longitude)) => Some((latitude.clamp(-90.0, 90.0), longitude.clamp(-180.0, 180.0))),
+            _ => None,
+        }
+    }
+
+    /// Returns the location of the collage, if enabled.
+    /// Default location is next to `target_filename`, with a "-collage" suffix.
+    pub(crate) fn collage_filename(&self) -> Option<String> {
+        self.collage.as_ref().map(|collage| self.variant_filename(collage.target_filename.as_ref(), "collage"))
+    }
+
+    /// Returns the location of the e-ink panel output, if enabled.
+    /// Default location is next to `target_filename`, with a "-eink" suffix and a ".png" extension.
+    pub(crate) fn eink_filename(&self) -> Option<String> {
+        let eink = self.eink.as_ref()?;
+        if let Some(target_filename) = &eink.target_filename {
+            return Some(target_filename.clone());
+        }
+
+        let eink_filename = self.variant_filename(None, "eink");
+        Some(Path::new(&eink_filename).with_extension("png").to_string_lossy().to_string())
+    }
+
+    /// Returns the location of the video wallpaper, if enabled.
+    /// Default location is next to `target_filename`, with a "-video" suffix and the ".mp4" extension.
+    pub(crate) fn video_filename(&self) -> Option<String> {
+        let video = self.video.as_ref()?;
+        if let Some(target_filename) = &video.target_filename {
+            return Some(target_filename.clone());
+        }
+
+        let video_filename = self.variant_filename(None, "video");
+        Some(Path::new(&video_filename).with_extension("mp4").to_string_lossy().to_string())
+    }
+
+    /// Returns the location of the palette exported in the given format.
+    /// Location is next to `target_filename`, with a "-palette" suffix (ie: ".bingwallpaper-palette.css").
+    pub(crate) fn palette_filename(&self, format: PaletteFormat) -> String {
+        let target_filename_as_path = Path::new(&self.target_filename);
+        let file_stem = target_filename_as_path.file_stem().unwrap_or_default().to_string_lossy();
+        let file_suffix = match format {
+            PaletteFormat::Json => "palette.json",
+            PaletteFormat::Pywal => "palette-wal.json",
+            PaletteFormat::Xresources => "palette.Xresources",
+            PaletteFormat::Css => "palette.css",
+        };
+
+        target_filename_as_path
+            .with_file_name(format!("{}-{}", file_stem, file_suffix))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Returns the directory where downloaded images are archived, if enabled.
+    /// Archiving is enabled by the collage, default directory is next to `target_filename`.
+    pub(crate) fn archive_directory(&self) -> Option<String> {
+        if let Some(archive_directory) = &self.archive_directory {
+            return Some(archive_directory.clone());
+        }
+
+        self.collage.as_ref()?;
+        Path::new(&self.target_filename)
+            .parent()
+            .map(|parent| parent.join(".bingwallpaper-archive").to_string_lossy().to_string())
+    }
+
+    /// Returns the location of a variant of the wallpaper.
+    fn variant_filename(&self, target_filename: Option<&String>, suffix: &str) -> String {
+        if let Some(target_filename) = target_filename {
+            return target_filename.clone();
+        }
+
+        let target_filename_as_path = Path::new(&self.target_filename);
+        let file_stem = target_filename_as_path.file_stem().unwrap_or_default().to_string_lossy();
+        let file_name = match target_filename_as_path.extension() {
+            None => format!("{}-{}", file_stem, suffix),
+            Some(extension) => format!("{}-{}.{}", file_stem, suffix, extension.to_string_lossy()),
+        };
+
+        target_filename_as_path.with_file_name(file_name).to_string_lossy().to_string()
+    }
+
+    /// Converts legacy `text_overlay_position*` settings into the `text_overlay` section.
+    fn migrate_legacy_text_overlay(&mut self) {
+        if self.text_overlay.is_some() {
+            return;
+        }
+
+        if let Some(position) = self.text_overlay_position.take() {
+            let anchor = match position.to_uppercase().as_str() {
+                "BOTTOM_LEFT" => TextOverlayAnchor::BottomLeft,
+                "TOP_RIGHT" => TextOverlayAnchor::TopRight,
+                "BOTTOM_RIGHT" => TextOverlayAnchor::BottomRight,
+                _ => TextOverlayAnchor::TopLeft,
+            };
+
+            self.text_overlay = Some(TextOverlayConfiguration {
+                anchor,
+                offset_x: self.text_overlay_position_offset_x.map(|value| value as i32),
+                offset_y: self.text_overlay_position_offset_y.map(|value| value as i32),
+                ..TextOverlayConfiguration::default()
+            });
         }
     }
 
     /// Resolves file path to use.
     #[allow(deprecated)]
     fn resolve_file_path(file_name: Option<String>) -> String {
+        // Inside a Flatpak sandbox, the home directory and /etc of the host are usually not accessible
+        if let (None, Some((config_directory, _))) = (&file_name, BingWallpaperConfiguration::flatpak_directories()) {
+            return config_directory.join("bingwallpaper.conf").to_string_lossy().to_string();
+        }
+
         match file_name {
             Some(value) => value,
             None => std::env::home_dir()
@@ -135,4 +1042,17 @@ impl BingWallpaperConfiguration {
                 .unwrap_or(String::from("/etc/bingwallpaper.conf"))
         }
     }
+
+    /// Returns the configuration and data directories of the application inside a Flatpak sandbox, none outside.
+    /// Snap sandboxes don't need them, their home directory already belongs to the application.
+    fn flatpak_directories() -> Option<(PathBuf, PathBuf)> {
+        if !Path::new("/.flatpak-info").exists() {
+            return None;
+        }
+
+        let config_directory = std::env::var_os("XDG_CONFIG_HOME")?;
+        let data_directory = std::env::var_os("XDG_DATA_HOME")?;
+
+        Some((PathBuf::from(config_directory), PathBuf::from(data_directory)))
+    }
 }
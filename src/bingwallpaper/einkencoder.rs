@@ -0,0 +1,304 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use image::{DynamicImage, GrayImage, ImageFormat, RgbImage};
+
+use crate::bingwallpaper::configuration::{EinkConfiguration, EinkDithering, EinkFormat, EinkPalette};
+
+/// Colors of a monochrome panel: black and white.
+const MONO_COLORS: [[u8; 3]; 2] = [[0, 0, 0], [255, 255, 255]];
+
+/// Colors of a 4 levels of gray panel, from black to white.
+const GRAY4_COLORS: [[u8; 3]; 4] = [[0, 0, 0], [85, 85, 85], [170, 170, 170], [255, 255, 255]];
+
+/// Colors of a 7 colors ACeP panel, in the order of the panel color indexes:
+/// black, white, green, blue, red, yellow and orange.
+const ACEP7_COLORS: [[u8; 3]; 7] = [
+    [0, 0, 0],
+    [255, 255, 255],
+    [0, 255, 0],
+    [0, 0, 255],
+    [255, 0, 0],
+    [255, 255, 0],
+    [255, 128, 0],
+];
+
+/// Weight of each channel when comparing colors, following the eye sensitivity.
+const CHANNEL_WEIGHTS: [f32; 3] = [0.299, 0.587, 0.114];
+
+/// Floyd-Steinberg error diffusion: offset x, offset y and share of the error.
+const FLOYD_STEINBERG_DIFFUSION: [(isize, usize, f32); 4] = [
+    (1, 0, 7.0 / 16.0),
+    (-1, 1, 3.0 / 16.0),
+    (0, 1, 5.0 / 16.0),
+    (1, 1, 1.0 / 16.0),
+];
+
+/// 8x8 Bayer threshold matrix used by the ordered dithering.
+const BAYER_MATRIX: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Reduces rendered images to the colors of an e-ink panel and encodes them.
+pub struct EinkEncoder {}
+
+impl EinkEncoder {
+    /// Reduces the image to the panel colors, with the configured dithering.
+    ///
+    /// Returns the panel color index of each pixel, row by row.
+    ///
+    /// # Arguments
+    /// * `image` - The image to reduce, at the panel dimensions
+    /// * `eink_configuration` - The e-ink configuration to use
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use einkencoder::EinkEncoder;
+    ///
+    /// let color_indexes = EinkEncoder::dither(&image, &eink_configuration);
+    /// ```
+    pub fn dither(image: &RgbImage, eink_configuration: &EinkConfiguration) -> Vec<u8> {
+        let palette = eink_configuration.palette.unwrap_or(EinkPalette::Mono);
+        let dithering = eink_configuration.dithering.unwrap_or(EinkDithering::FloydSteinberg);
+        let colors = EinkEncoder::colors(palette);
+        let (width, height) = (image.width() as usize, image.height() as usize);
+
+        // Gray panels only render the luminance of the image
+        let mut pixels: Vec<[f32; 3]> = image
+            .pixels()
+            .map(|pixel| {
+                let value = pixel.0.map(|channel| channel as f32);
+                if palette == EinkPalette::Acep7 {
+                    value
+                } else {
+                    let luminance = (0..3).map(|channel| value[channel] * CHANNEL_WEIGHTS[channel]).sum::<f32>();
+                    [luminance; 3]
+                }
+            })
+            .collect();
+
+        // Average distance between two panel colors, used to scale the ordered dithering thresholds
+        let spread = match palette {
+            EinkPalette::Mono => 255.0,
+            EinkPalette::Gray4 => 85.0,
+            EinkPalette::Acep7 => 128.0,
+        };
+
+        let mut color_indexes = vec![0u8; width * height];
+        for y in 0..height {
+            // Error diffusion goes back and forth to avoid directional artifacts
+            let is_reversed = dithering == EinkDithering::FloydSteinberg && y % 2 == 1;
+
+            for step in 0..width {
+                let x = if is_reversed { width - 1 - step } else { step };
+                let mut value = pixels[y * width + x].map(|channel| channel.clamp(0.0, 255.0));
+
+                if dithering == EinkDithering::Ordered {
+                    let threshold = (BAYER_MATRIX[y % 8][x % 8] as f32 + 0.5) / 64.0 - 0.5;
+                    value = value.map(|channel| channel + threshold * spread);
+                }
+
+                let color_index = EinkEncoder::nearest_color(colors, value);
+                color_indexes[y * width + x] = color_index as u8;
+
+                if dithering == EinkDithering::FloydSteinberg {
+                    let error = [0, 1, 2].map(|channel| value[channel] - colors[color_index][channel] as f32);
+
+                    for (offset_x, offset_y, share) in FLOYD_STEINBERG_DIFFUSION {
+                        let offset_x = if is_reversed { -offset_x } else { offset_x };
+                        let (target_x, target_y) = (x as isize + offset_x, y + offset_y);
+                        if target_x < 0 || target_x >= width as isize || target_y >= height {
+                            continue;
+                        }
+
+                        let target = &mut pixels[target_y * width + target_x as usize];
+                        for channel in 0..3 {
+                            target[channel] += error[channel] * share;
+                        }
+                    }
+                }
+            }
+        }
+
+        color_indexes
+    }
+
+    /// Encodes the panel color indexes as PNG, BMP or raw framebuffer.
+    ///
+    /// The raw framebuffer packs the panel color indexes, row by row, most significant bits first:
+    /// 1 bit per pixel for `MONO` (1 is white), 2 bits for `GRAY4` (0 is black, 3 is white)
+    /// and 4 bits for `ACEP7`. Each row starts on a new byte.
+    ///
+    /// # Arguments
+    /// * `color_indexes` - The panel color index of each pixel, as returned by `dither`
+    /// * `dimensions` - Width and height of the image
+    /// * `eink_configuration` - The e-ink configuration to use
+    /// * `filename` - The location of the output, used to deduce the format when not configured
+    pub fn encode(color_indexes: &[u8],
+                  dimensions: (u32, u32),
+                  eink_configuration: &EinkConfiguration,
+                  filename: &str) -> Result<Vec<u8>, String> {
+        let palette = eink_configuration.palette.unwrap_or(EinkPalette::Mono);
+        let colors = EinkEncoder::colors(palette);
+        let (width, height) = dimensions;
+
+        let image_format = match EinkEncoder::resolve_format(filename, eink_configuration) {
+            EinkFormat::Raw => return Ok(EinkEncoder::pack(color_indexes, dimensions, palette)),
+            EinkFormat::Png => ImageFormat::Png,
+            EinkFormat::Bmp => ImageFormat::Bmp,
+        };
+
+        let image = if palette == EinkPalette::Acep7 {
+            let content = color_indexes.iter().flat_map(|color_index| colors[*color_index as usize]).collect();
+            RgbImage::from_raw(width, height, content).map(DynamicImage::ImageRgb8)
+        } else {
+            let content = color_indexes.iter().map(|color_index| colors[*color_index as usize][0]).collect();
+            GrayImage::from_raw(width, height, content).map(DynamicImage::ImageLuma8)
+        };
+
+        let image = match image {
+            None => return Err(format!("Can't encode e-ink image: unexpected size {}x{}", width, height)),
+            Some(image) => image,
+        };
+
+        let mut output_content: Vec<u8> = Vec::new();
+        if let Err(error) = image.write_to(&mut Cursor::new(&mut output_content), image_format) {
+            return Err(format!("Can't encode e-ink image: {:?}", error));
+        }
+
+        Ok(output_content)
+    }
+
+    /// Returns the configured format, or the format deduced from the file extension.
+    fn resolve_format(filename: &str, eink_configuration: &EinkConfiguration) -> EinkFormat {
+        if let Some(format) = eink_configuration.format {
+            return format;
+        }
+
+        let extension = Path::new(filename)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match extension.as_str() {
+            "bmp" => EinkFormat::Bmp,
+            "raw" | "bin" => EinkFormat::Raw,
+            _ => EinkFormat::Png,
+        }
+    }
+
+    /// Returns the colors the panel can display, in the order of the panel color indexes.
+    fn colors(palette: EinkPalette) -> &'static [[u8; 3]] {
+        match palette {
+            EinkPalette::Mono => &MONO_COLORS,
+            EinkPalette::Gray4 => &GRAY4_COLORS,
+            EinkPalette::Acep7 => &ACEP7_COLORS,
+        }
+    }
+
+    /// Returns the index of the panel color closest to the given value.
+    fn nearest_color(colors: &[[u8; 3]], value: [f32; 3]) -> usize {
+        let distance = |color: &[u8; 3]| {
+            (0..3).map(|channel| (value[channel] - color[channel] as f32).powi(2) * CHANNEL_WEIGHTS[channel]).sum::<f32>()
+        };
+
+        (0..colors.len()).min_by(|a, b| distance(&colors[*a]).total_cmp(&distance(&colors[*b]))).unwrap_or(0)
+    }
+
+    /// Packs the panel color indexes into a raw framebuffer.
+    fn pack(color_indexes: &[u8], dimensions: (u32, u32), palette: EinkPalette) -> Vec<u8> {
+        let bits_per_pixel = match palette {
+            EinkPalette::Mono => 1,
+            EinkPalette::Gray4 => 2,
+            EinkPalette::Acep7 => 4,
+        };
+        let pixels_per_byte = 8 / bits_per_pixel;
+        let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+        let row_length = width.div_ceil(pixels_per_byte);
+
+        let mut framebuffer = vec![0u8; row_length * height];
+        for (idx, color_index) in color_indexes.iter().enumerate().take(width * height) {
+            let (x, y) = (idx % width, idx / width);
+            let shift = 8 - bits_per_pixel * (x % pixels_per_byte + 1);
+            framebuffer[y * row_length + x / pixels_per_byte] |= color_index << shift;
+        }
+
+        framebuffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, RgbImage};
+
+    use super::EinkEncoder;
+    use crate::bingwallpaper::configuration::{EinkConfiguration, EinkDithering, EinkPalette};
+
+    fn eink_configuration(palette: EinkPalette, dithering: EinkDithering) -> EinkConfiguration {
+        EinkConfiguration {
+            target_filename: None,
+            image_dimension_width: 8,
+            image_dimension_height: 8,
+            palette: Some(palette),
+            dithering: Some(dithering),
+            format: None,
+            filters: None,
+            text_overlay: None,
+        }
+    }
+
+    #[test]
+    fn pack_mono_starts_each_row_on_a_new_byte() {
+        // 10 pixels per row: 2 bytes per row
+        let color_indexes = [1, 0, 1, 1, 0, 0, 0, 0, 1, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1];
+        let framebuffer = EinkEncoder::pack(&color_indexes, (10, 2), EinkPalette::Mono);
+        assert_eq!(framebuffer, vec![0b1011_0000, 0b1100_0000, 0b0100_0000, 0b0100_0000]);
+
+        assert_eq!(EinkEncoder::pack(&[1; 8], (8, 1), EinkPalette::Mono), vec![0xFF]);
+    }
+
+    #[test]
+    fn pack_gray4_and_acep7() {
+        assert_eq!(EinkEncoder::pack(&[0, 1, 2, 3, 3], (5, 1), EinkPalette::Gray4), vec![0b0001_1011, 0b1100_0000]);
+        assert_eq!(EinkEncoder::pack(&[6, 1, 4], (3, 1), EinkPalette::Acep7), vec![0x61, 0x40]);
+    }
+
+    #[test]
+    fn dither_solid_colors_to_exact_panel_colors() {
+        for dithering in [EinkDithering::FloydSteinberg, EinkDithering::Ordered, EinkDithering::None] {
+            let black = RgbImage::from_pixel(8, 8, Rgb([0, 0, 0]));
+            let white = RgbImage::from_pixel(8, 8, Rgb([255, 255, 255]));
+            let red = RgbImage::from_pixel(8, 8, Rgb([255, 0, 0]));
+
+            let mono = eink_configuration(EinkPalette::Mono, dithering);
+            assert!(EinkEncoder::dither(&black, &mono).iter().all(|color_index| *color_index == 0));
+            assert!(EinkEncoder::dither(&white, &mono).iter().all(|color_index| *color_index == 1));
+
+            let acep7 = eink_configuration(EinkPalette::Acep7, dithering);
+            assert!(EinkEncoder::dither(&red, &acep7).iter().all(|color_index| *color_index == 4));
+        }
+    }
+
+    #[test]
+    fn dither_mid_gray_keeps_average_luminance() {
+        let gray = RgbImage::from_pixel(8, 8, Rgb([128, 128, 128]));
+
+        for dithering in [EinkDithering::FloydSteinberg, EinkDithering::Ordered] {
+            let color_indexes = EinkEncoder::dither(&gray, &eink_configuration(EinkPalette::Mono, dithering));
+            let white_pixels = color_indexes.iter().filter(|color_index| **color_index == 1).count();
+            assert!((28..=36).contains(&white_pixels), "{:?}: {} white pixels", dithering, white_pixels);
+        }
+
+        // Without dithering, each pixel takes the nearest color
+        let color_indexes = EinkEncoder::dither(&gray, &eink_configuration(EinkPalette::Gray4, EinkDithering::None));
+        assert!(color_indexes.iter().all(|color_index| *color_index == 2));
+    }
+}
@@ -0,0 +1,92 @@
+/// FNV-1a 64 bits offset basis.
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// FNV-1a 64 bits prime.
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Fingerprint of everything a stored file depends on (ie: the rendered wallpaper), kept on disk to check if the file
+/// is up to date.
+///
+/// Unlike `DefaultHasher`, the algorithm (FNV-1a, 64 bits) and the input (each value prefixed by its length) are
+/// specified, so a fingerprint stays the same across Rust releases.
+pub struct Fingerprint {
+    hash: u64,
+}
+
+impl Fingerprint {
+    /// Creates a new instance, with the application version as first value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fingerprint::Fingerprint;
+    ///
+    /// let mut fingerprint = Fingerprint::new();
+    /// fingerprint.add("/home/user/.bingwallpaper.jpg").add(1920u32.to_le_bytes());
+    /// let fingerprint_as_str = fingerprint.to_hex();
+    /// ```
+    #[must_use]
+    pub fn new() -> Fingerprint {
+        let mut fingerprint = Fingerprint { hash: FNV_OFFSET_BASIS };
+        fingerprint.add(env!("CARGO_PKG_VERSION"));
+
+        fingerprint
+    }
+
+    /// Adds a value, prefixed by its length so that consecutive values can't be confused (ie: "ab" + "c" and
+    /// "a" + "bc").
+    ///
+    /// # Arguments
+    /// * `value` - The bytes of the value
+    pub fn add(&mut self, value: impl AsRef<[u8]>) -> &mut Fingerprint {
+        let value = value.as_ref();
+        self.write(&(value.len() as u64).to_le_bytes());
+        self.write(value);
+
+        self
+    }
+
+    /// Returns the fingerprint as 16 hexadecimal digits.
+    pub fn to_hex(&self) -> String {
+        format!("{:016x}", self.hash)
+    }
+
+    /// Hashes bytes with FNV-1a.
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.hash ^= u64::from(*byte);
+            self.hash = self.hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+impl Default for Fingerprint {
+    fn default() -> Fingerprint {
+        Fingerprint::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fingerprint, FNV_OFFSET_BASIS};
+
+    #[test]
+    fn fnv1a_reference_values() {
+        let mut fingerprint = Fingerprint { hash: FNV_OFFSET_BASIS };
+        assert_eq!(fingerprint.to_hex(), "cbf29ce484222325");
+
+        fingerprint.write(b"a");
+        assert_eq!(fingerprint.to_hex(), "af63dc4c8601ec8c");
+
+        let mut fingerprint = Fingerprint { hash: FNV_OFFSET_BASIS };
+        fingerprint.write(b"foobar");
+        assert_eq!(fingerprint.to_hex(), "85944171f73967e8");
+    }
+
+    #[test]
+    fn values_are_delimited() {
+        assert_eq!(Fingerprint::new().add("ab").add("c").to_hex(), Fingerprint::new().add("ab").add("c").to_hex());
+        assert_ne!(Fingerprint::new().add("ab").add("c").to_hex(), Fingerprint::new().add("a").add("bc").to_hex());
+        assert_ne!(Fingerprint::new().add("").to_hex(), Fingerprint::new().to_hex());
+    }
+}
@@ -0,0 +1,491 @@
+use std::collections::HashSet;
+use std::fs;
+use std::process::Command;
+
+use ab_glyph::{point, Font, FontVec, Glyph, GlyphId, PxScale, ScaleFont};
+use rustybuzz::{Direction, Face, UnicodeBuffer};
+use unicode_bidi::{bidi_class, BidiClass, ParagraphBidiInfo};
+
+/// Font embedded into the application, used after the configured fonts.
+const EMBEDDED_FONT: &[u8] = include_bytes!("../../res/font/Ubuntu-Regular.ttf");
+
+/// Ordered list of fonts used to render text: configured fonts, then the embedded
+/// font. For each character, the first font containing a glyph is used. Characters
+/// missing from every font are looked up with fontconfig (when available) and the
+/// matching fonts are appended to the chain.
+pub struct FontChain {
+    fonts: Vec<FontChainEntry>,
+    scale: PxScale,
+    looked_up_chars: HashSet<char>,
+    looked_up_files: HashSet<String>,
+}
+
+/// A font of the chain.
+struct FontChainEntry {
+    face_index: u32,
+    font: FontVec,
+}
+
+/// A glyph positioned on a line, with the index of the font it comes from.
+#[derive(Clone)]
+pub struct ShapedGlyph {
+    pub(crate) font_index: usize,
+    pub(crate) glyph: Glyph,
+}
+
+/// A shaped line of text, laid out horizontally from `x = 0` in visual order.
+pub struct ShapedLine {
+    pub(crate) glyphs: Vec<ShapedGlyph>,
+    pub(crate) width: f32,
+}
+
+impl FontChain {
+    /// Creates a new instance.
+    ///
+    /// # Arguments
+    /// * `font_files` - Font files to use first, in order
+    /// * `font_families` - Font families to resolve with fontconfig, in order
+    /// * `font_size` - Font size in pixels
+    #[must_use]
+    pub fn new(font_files: &[String], font_families: &[String], font_size: f32) -> FontChain {
+        let mut font_chain = FontChain {
+            fonts: Vec::new(),
+            scale: PxScale::from(font_size),
+            looked_up_chars: HashSet::new(),
+            looked_up_files: HashSet::new(),
+        };
+
+        for font_file in font_files {
+            if let Err(error) = font_chain.push_file(font_file, 0) {
+                println!("Can't load font {:?}: {}", font_file, error);
+            }
+        }
+
+        for font_family in font_families {
+            if let Some((font_file, face_index)) = FontChain::fontconfig_match(font_family) {
+                if let Err(error) = font_chain.push_file(&font_file, face_index) {
+                    println!("Can't load font {:?}: {}", font_file, error);
+                }
+            }
+        }
+
+        font_chain.push(EMBEDDED_FONT.to_vec(), 0).unwrap();
+        font_chain
+    }
+
+    /// Returns the font at the given index, scaled to the chain font size.
+    pub fn scaled_font(&self, font_index: usize) -> impl ScaleFont<&FontVec> {
+        self.fonts[font_index].font.as_scaled(self.scale)
+    }
+
+    /// Returns the greatest ascent of the fonts used by the given lines.
+    pub fn ascent(&self, lines: &[ShapedLine]) -> f32 {
+        self.used_fonts(lines).map(|idx| self.scaled_font(idx).ascent()).fold(0.0, f32::max)
+    }
+
+    /// Returns the greatest height of the fonts used by the given lines.
+    pub fn height(&self, lines: &[ShapedLine]) -> f32 {
+        self.used_fonts(lines).map(|idx| self.scaled_font(idx).height()).fold(0.0, f32::max)
+    }
+
+    /// Shapes a single line of text: applies bidirectional reordering, selects
+    /// a font for each character and shapes each run with HarfBuzz rules.
+    pub fn shape_line(&mut self, text: &str) -> ShapedLine {
+        self.ensure_coverage(text);
+
+        let mut glyphs: Vec<ShapedGlyph> = Vec::new();
+        let mut caret = 0.0f32;
+
+        if text.is_empty() {
+            return ShapedLine { glyphs, width: caret };
+        }
+
+        let bidi_info = ParagraphBidiInfo::new(text, None);
+        let (levels, runs) = bidi_info.visual_runs(0..text.len());
+
+        for run in runs {
+            let is_rtl = levels[run.start].is_rtl();
+            let mut segments = self.split_by_font(&text[run.clone()]);
+            if is_rtl {
+                segments.reverse();
+            }
+
+            for (font_index, segment) in segments {
+                let entry = &self.fonts[font_index];
+                let face = match Face::from_slice(entry.font.as_slice(), entry.face_index) {
+                    None => continue,
+                    Some(face) => face,
+                };
+
+                let mut buffer = UnicodeBuffer::new();
+                buffer.push_str(segment);
+                buffer.guess_segment_properties();
+                buffer.set_direction(if is_rtl { Direction::RightToLeft } else { Direction::LeftToRight });
+
+                let glyph_buffer = rustybuzz::shape(&face, &[], buffer);
+                let scale_factor = entry.font.as_scaled(self.scale).h_scale_factor();
+
+                for (info, position) in glyph_buffer.glyph_infos().iter().zip(glyph_buffer.glyph_positions()) {
+                    let glyph_position = point(
+                        caret + position.x_offset as f32 * scale_factor,
+                        -position.y_offset as f32 * scale_factor);
+
+                    glyphs.push(ShapedGlyph {
+                        font_index,
+                        glyph: GlyphId(info.glyph_id as u16).with_scale_and_position(self.scale, glyph_position),
+                    });
+                    caret += position.x_advance as f32 * scale_factor;
+                }
+            }
+        }
+
+        ShapedLine { glyphs, width: caret }
+    }
+
+    /// Splits text into segments rendered with the same font. Combining marks
+    /// stay with their base character when the font supports them.
+    fn split_by_font<'a>(&self, text: &'a str) -> Vec<(usize, &'a str)> {
+        let mut segments: Vec<(usize, &'a str)> = Vec::new();
+        let mut segment_start = 0;
+        let mut segment_font: Option<usize> = None;
+
+        for (offset, c) in text.char_indices() {
+            let keep_current = segment_font.is_some_and(|idx| {
+                (bidi_class(c) == BidiClass::NSM || c.is_whitespace() || c == '\u{200D}') && self.has_glyph(idx, c)
+            });
+            if keep_current {
+                continue;
+            }
+
+            let font_index = self.font_for(c);
+            if segment_font.is_some_and(|idx| idx != font_index) {
+                segments.push((segment_font.unwrap(), &text[segment_start..offset]));
+                segment_start = offset;
+            }
+            segment_font = Some(font_index);
+        }
+
+        if let Some(font_index) = segment_font {
+            segments.push((font_index, &text[segment_start..]));
+        }
+
+        segments
+    }
+
+    /// Returns the index of the first font containing a glyph for the given character.
+    fn font_for(&self, c: char) -> usize {
+        (0..self.fonts.len())
+            .find(|idx| self.has_glyph(*idx, c))
+            .unwrap_or(0)
+    }
+
+    /// Checks if the font at the given index contains a glyph for the given character.
+    fn has_glyph(&self, font_index: usize, c: char) -> bool {
+        c.is_control() || self.fonts[font_index].font.glyph_id(c).0 != 0
+    }
+
+    /// Looks up fonts for characters not covered by the chain, with a single fontconfig call.
+    fn ensure_coverage(&mut self, text: &str) {
+        let mut missing_chars: Vec<char> = Vec::new();
+        for c in text.chars() {
+            if c.is_whitespace() || (0..self.fonts.len()).any(|idx| self.has_glyph(idx, c)) {
+                continue;
+            }
+            if self.looked_up_chars.insert(c) {
+                missing_chars.push(c);
+            }
+        }
+
+        if missing_chars.is_empty() {
+            return;
+        }
+
+        for (font_file, face_index) in FontChain::fontconfig_match_chars(&missing_chars) {
+            if self.looked_up_files.insert(format!("{}#{}", font_file, face_index)) {
+                if let Err(error) = self.push_file(&font_file, face_index) {
+                    println!("Can't load font {:?}: {}", font_file, error);
+                }
+            }
+        }
+    }
+
+    /// Returns the indexes of the fonts used by the given lines.
+    fn used_fonts<'a>(&'a self, lines: &'a [ShapedLine]) -> impl Iterator<Item=usize> + 'a {
+        let used: HashSet<usize> = lines
+            .iter()
+            .flat_map(|line| line.glyphs.iter().map(|shaped_glyph| shaped_glyph.font_index))
+            .chain(std::iter::once(0))
+            .collect();
+
+        used.into_iter()
+    }
+
+    /// Loads a font file and appends it to the chain.
+    fn push_file(&mut self, font_file: &str, face_index: u32) -> Result<(), String> {
+        match fs::read(font_file) {
+            Err(error) => Err(format!("{:?}", error)),
+            Ok(data) => self.push(data, face_index),
+        }
+    }
+
+    /// Appends a font to the chain.
+    fn push(&mut self, data: Vec<u8>, face_index: u32) -> Result<(), String> {
+        match FontVec::try_from_vec_and_index(data, face_index) {
+            Err(error) => Err(format!("{:?}", error)),
+            Ok(font) => {
+                self.fonts.push(FontChainEntry { face_index, font });
+                Ok(())
+            }
+        }
+    }
+
+    /// Resolves a font file using fontconfig.
+    ///
+    /// # Arguments
+    /// * `pattern` - The fontconfig pattern (ie: "Noto Sans CJK JP" or ":charset=65e5")
+    fn fontconfig_match(pattern: &str) -> Option<(String, u32)> {
+        let output = Command::new("fc-match")
+            .arg("--format=%{file}\n%{index}")
+            .arg(pattern)
+            .output()
+            .ok()?;
+
+        if !output.status.success() {
+            return None;
+        }
+
+        FontChain::parse_fontconfig_match(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    /// Parses the output of `fc-match --format=%{file}\n%{index}`: the font file, then the face index.
+    fn parse_fontconfig_match(output: &str) -> Option<(String, u32)> {
+        let mut output_lines = output.lines();
+        let font_file = output_lines.next().filter(|value| !value.is_empty())?.to_string();
+        let face_index = output_lines.next().and_then(|value| value.parse::<u32>().ok()).unwrap_or(0);
+
+        Some((font_file, face_index))
+    }
+
+    /// Resolves the font files covering the given characters using fontconfig: for each character, the best
+    /// matching font containing it. Fonts are returned in order of preference, without duplicates.
+    ///
+    /// # Arguments
+    /// * `chars` - The characters to cover
+    fn fontconfig_match_chars(chars: &[char]) -> Vec<(String, u32)> {
+        let charset: Vec<String> = chars.iter().map(|c| format!("{:x}", *c as u32)).collect();
+        let output = match Command::new("fc-match")
+            .arg("--sort")
+            .arg("--format=%{file}\t%{index}\t%{charset}\n")
+            .arg(format!(":charset={}", charset.join(" ")))
+            .output() {
+            Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout).to_string(),
+            _ => return Vec::new(),
+        };
+
+        FontChain::parse_fontconfig_matches(&output, chars)
+    }
+
+    /// Parses the output of `fc-match --sort --format=%{file}\t%{index}\t%{charset}\n`: one font per line, sorted
+    /// by coverage of the requested characters. Only the fonts adding a character not covered by the previous
+    /// ones are kept.
+    fn parse_fontconfig_matches(output: &str, chars: &[char]) -> Vec<(String, u32)> {
+        let mut remaining_chars = chars.to_vec();
+        let mut fonts: Vec<(String, u32)> = Vec::new();
+        for line in output.lines() {
+            let mut fields = line.split('\t');
+            let (font_file, face_index, font_charset) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(font_file), Some(face_index), Some(font_charset)) if !font_file.is_empty() => {
+                    (font_file, face_index.parse::<u32>().unwrap_or(0), font_charset)
+                }
+                _ => continue,
+            };
+
+            let covered_chars = remaining_chars.len();
+            remaining_chars.retain(|c| !FontChain::charset_contains(font_charset, *c));
+            if remaining_chars.len() < covered_chars {
+                fonts.push((font_file.to_string(), face_index));
+            }
+            if remaining_chars.is_empty() {
+                break;
+            }
+        }
+
+        fonts
+    }
+
+    /// Checks if a fontconfig charset (ie: "20-7e a0-17f 2013") contains the given character.
+    fn charset_contains(charset: &str, c: char) -> bool {
+        let code_point = c as u32;
+        charset.split_whitespace().any(|range| {
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            match (u32::from_str_radix(start, 16), u32::from_str_radix(end, 16)) {
+                (Ok(start), Ok(end)) => (start..=end).contains(&code_point),
+                _ => false,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use ab_glyph::{Font, PxScale};
+
+    use super::{FontChain, EMBEDDED_FONT};
+
+    /// Builds a valid font without any glyph mapped: only the required "head", "hhea" and "maxp" tables.
+    fn empty_font() -> Vec<u8> {
+        let mut head = vec![0u8; 54];
+        head[0..4].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+        head[12..16].copy_from_slice(&0x5F0F_3CF5u32.to_be_bytes());
+        head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+        let mut hhea = vec![0u8; 36];
+        hhea[0..4].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+        hhea[34..36].copy_from_slice(&1u16.to_be_bytes());
+        let mut maxp = vec![0u8; 6];
+        maxp[0..4].copy_from_slice(&0x0000_5000u32.to_be_bytes());
+        maxp[4..6].copy_from_slice(&1u16.to_be_bytes());
+
+        let tables = [(b"head", head), (b"hhea", hhea), (b"maxp", maxp)];
+        let mut font = Vec::new();
+        font.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+        font.extend_from_slice(&[0, tables.len() as u8, 0, 32, 0, 1, 0, 16]);
+        let mut offset = 12 + 16 * tables.len();
+        for (tag, table) in &tables {
+            font.extend_from_slice(*tag);
+            font.extend_from_slice(&0u32.to_be_bytes());
+            font.extend_from_slice(&(offset as u32).to_be_bytes());
+            font.extend_from_slice(&(table.len() as u32).to_be_bytes());
+            offset += table.len().next_multiple_of(4);
+        }
+        for (_, table) in &tables {
+            font.extend_from_slice(table);
+            font.resize(font.len().next_multiple_of(4), 0);
+        }
+
+        font
+    }
+
+    /// Builds a chain of the given fonts. Hebrew letters are marked as already looked up, so that shaping
+    /// doesn't depend on the fonts installed.
+    fn chain_of(fonts: Vec<Vec<u8>>) -> FontChain {
+        let mut font_chain = FontChain {
+            fonts: Vec::new(),
+            scale: PxScale::from(20.0),
+            looked_up_chars: "אבג".chars().collect(),
+            looked_up_files: HashSet::new(),
+        };
+        for font in fonts {
+            font_chain.push(font, 0).unwrap();
+        }
+
+        font_chain
+    }
+
+    /// Returns the glyph ids of a shaped line, in visual order.
+    fn glyph_ids(font_chain: &mut FontChain, text: &str) -> Vec<u16> {
+        let shaped_line = font_chain.shape_line(text);
+        let positions: Vec<f32> = shaped_line.glyphs.iter().map(|shaped_glyph| shaped_glyph.glyph.position.x).collect();
+        assert!(positions.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", positions);
+
+        shaped_line.glyphs.iter().map(|shaped_glyph| shaped_glyph.glyph.id.0).collect()
+    }
+
+    #[test]
+    fn bidi_visual_run_ordering() {
+        let mut font_chain = chain_of(vec![EMBEDDED_FONT.to_vec()]);
+        let ids = |text: &str| -> Vec<u16> {
+            let font = &font_chain.fonts[0].font;
+            text.chars().map(|c| font.glyph_id(c).0).collect()
+        };
+        let (latin, digits, missing) = (ids("abc def"), ids("123 "), ids("אבג"));
+        assert!(latin.iter().chain(&digits).all(|id| *id != 0));
+        assert_eq!(missing, [0, 0, 0]);
+
+        // Left-to-right paragraph: the Hebrew run stays between the Latin words
+        let expected = [&latin[..4], &missing, &latin[3..]].concat();
+        assert_eq!(glyph_ids(&mut font_chain, "abc אבג def"), expected);
+
+        // Right-to-left paragraph: the Latin words keep their order on the left of the Hebrew run, as the digits
+        let expected = [&latin[..], &latin[3..4], &missing].concat();
+        assert_eq!(glyph_ids(&mut font_chain, "אבג abc def"), expected);
+        let expected = [&digits[..], &missing].concat();
+        assert_eq!(glyph_ids(&mut font_chain, "אבג 123"), expected);
+
+        // Latin letters forced right-to-left: reversed, after the invisible override glyph
+        let reversed: Vec<u16> = latin[..3].iter().rev().copied().collect();
+        assert_eq!(glyph_ids(&mut font_chain, "\u{202E}abc")[1..], reversed);
+    }
+
+    #[test]
+    fn split_by_font_fallback() {
+        let font_chain = chain_of(vec![EMBEDDED_FONT.to_vec()]);
+        assert_eq!(font_chain.split_by_font("abc אבג"), [(0, "abc אבג")]);
+        assert_eq!(font_chain.split_by_font(""), []);
+
+        // The first font containing the character is used, characters missing from every font use the first one
+        let font_chain = chain_of(vec![empty_font(), EMBEDDED_FONT.to_vec()]);
+        assert_eq!(font_chain.split_by_font("abc"), [(1, "abc")]);
+        assert_eq!(font_chain.split_by_font("aאb"), [(1, "a"), (0, "א"), (1, "b")]);
+
+        // Spaces and combining marks stay with the current segment when its font has them
+        assert_eq!(font_chain.split_by_font("ab cd"), [(1, "ab cd")]);
+        assert_eq!(font_chain.split_by_font("e\u{311}te\u{311}"), [(1, "e\u{311}te\u{311}")]);
+        assert_eq!(font_chain.split_by_font("e\u{301}t"), [(1, "e"), (0, "\u{301}"), (1, "t")]);
+        assert_eq!(font_chain.split_by_font("א b"), [(0, "א"), (1, " b")]);
+    }
+
+    #[test]
+    fn fontconfig_match_output() {
+        assert_eq!(
+            FontChain::parse_fontconfig_match("/usr/share/fonts/noto/NotoSansCJK-Regular.ttc\n2"),
+            Some((String::from("/usr/share/fonts/noto/NotoSansCJK-Regular.ttc"), 2)));
+        assert_eq!(
+            FontChain::parse_fontconfig_match("/usr/share/fonts/DejaVuSans.ttf\n"),
+            Some((String::from("/usr/share/fonts/DejaVuSans.ttf"), 0)));
+        assert_eq!(
+            FontChain::parse_fontconfig_match("/usr/share/fonts/DejaVuSans.ttf\nnone"),
+            Some((String::from("/usr/share/fonts/DejaVuSans.ttf"), 0)));
+        assert_eq!(FontChain::parse_fontconfig_match(""), None);
+        assert_eq!(FontChain::parse_fontconfig_match("\n0"), None);
+    }
+
+    #[test]
+    fn fontconfig_sorted_matches_output() {
+        let output = "/fonts/Latin.ttf\t0\t20-7e a0-17f\n\
+                      /fonts/Broken.ttf\t0\n\
+                      \t0\t20-7e 5d0-5ea\n\
+                      /fonts/LatinToo.ttf\t0\t20-7e\n\
+                      /fonts/Hebrew.ttc\t1\t20-7e 5d0-5ea\n\
+                      /fonts/CJK.ttc\t3\t20-7e 5d0-5ea 65e5\n\
+                      /fonts/Unused.ttf\t0\t20-ffff\n";
+
+        // Fonts adding coverage only, until every character is covered
+        assert_eq!(
+            FontChain::parse_fontconfig_matches(output, &['é', 'א', '日']),
+            [
+                (String::from("/fonts/Latin.ttf"), 0),
+                (String::from("/fonts/Hebrew.ttc"), 1),
+                (String::from("/fonts/CJK.ttc"), 3),
+            ]);
+        assert_eq!(FontChain::parse_fontconfig_matches(output, &['a']), [(String::from("/fonts/Latin.ttf"), 0)]);
+
+        // Characters covered by no font are ignored
+        assert_eq!(FontChain::parse_fontconfig_matches(output, &['\u{10000}']), []);
+        assert_eq!(FontChain::parse_fontconfig_matches("", &['a']), []);
+    }
+
+    #[test]
+    fn charset_contains() {
+        let charset = "20-7e a0-17f 2013";
+        assert!(FontChain::charset_contains(charset, ' '));
+        assert!(FontChain::charset_contains(charset, '~'));
+        assert!(FontChain::charset_contains(charset, 'é'));
+        assert!(FontChain::charset_contains(charset, '\u{2013}'));
+        assert!(!FontChain::charset_contains(charset, '\u{7f}'));
+        assert!(!FontChain::charset_contains(charset, '\u{2014}'));
+        assert!(!FontChain::charset_contains("zz-7e", 'a'));
+        assert!(!FontChain::charset_contains("", 'a'));
+    }
+}
@@ -0,0 +1,337 @@
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::bingwallpaper::configuration::{DisplayManager, GreeterConfiguration};
+use crate::bingwallpaper::Fingerprint;
+
+/// Default location of the greeter background.
+const DEFAULT_TARGET_FILENAME: &str = "/usr/share/backgrounds/bingwallpaper-greeter.jpg";
+
+/// System directory holding the state of the greeter background rendering.
+const STATE_DIRECTORY: &str = "/var/cache/bingwallpaper";
+
+/// Default SDDM theme, used when no theme is configured.
+const DEFAULT_SDDM_THEME: &str = "breeze";
+
+/// Permissions of the files written for the greeter: readable by everyone, as
+/// the greeter runs under its own user.
+#[cfg(unix)]
+const FILE_MODE: u32 = 0o644;
+
+/// Permissions of the directories created for the greeter.
+#[cfg(unix)]
+const DIRECTORY_MODE: u32 = 0o755;
+
+/// Installs the wallpaper as background of the display manager greeter (login screen).
+///
+/// Every system location is resolved inside a filesystem root, "/" unless another
+/// one is given (ie: to check the result before installing it for real).
+///
+/// GDM is not supported: its login screen background is styled by the gnome-shell theme, not by dconf settings.
+pub struct GreeterBackground {}
+
+impl GreeterBackground {
+    /// Returns the location of the greeter background, as seen by the system.
+    ///
+    /// # Arguments
+    /// * `greeter_configuration` - The greeter configuration to use
+    pub fn target_filename(greeter_configuration: &GreeterConfiguration) -> String {
+        greeter_configuration.target_filename.clone().unwrap_or(DEFAULT_TARGET_FILENAME.to_string())
+    }
+
+    /// Returns the location of a system file inside the filesystem root.
+    ///
+    /// # Arguments
+    /// * `root` - The filesystem root
+    /// * `filename` - The absolute location of the file on the system
+    pub fn rooted(root: &str, filename: &str) -> PathBuf {
+        Path::new(root).join(filename.trim_start_matches('/'))
+    }
+
+    /// Returns the location of the file holding the fingerprint of the last rendering of a greeter background. It is
+    /// kept in the system cache directory, like the background, never in the directories of the user running sudo.
+    ///
+    /// # Arguments
+    /// * `root` - The filesystem root
+    /// * `background_filename` - The absolute location of the greeter background on the system
+    pub fn state_filename(root: &str, background_filename: &str) -> String {
+        let location_fingerprint = Fingerprint::new().add(background_filename).to_hex();
+
+        GreeterBackground::rooted(root, &format!("{}/greeter-{}.render", STATE_DIRECTORY, location_fingerprint))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Creates the directory holding a file, readable by the greeter.
+    ///
+    /// # Arguments
+    /// * `filename` - The location of the file
+    pub fn create_parent_directory(filename: &Path) -> Result<(), String> {
+        let parent_directory = match filename.parent() {
+            None => return Ok(()),
+            Some(parent_directory) => parent_directory,
+        };
+
+        // Directories to create, the umask must not restrict their permissions
+        let missing_directories: Vec<&Path> = parent_directory.ancestors().take_while(|path| !path.exists()).collect();
+
+        if let Err(error) = fs::create_dir_all(parent_directory) {
+            return Err(format!("Can't create directory {:?}: {:?}", parent_directory, error));
+        }
+
+        #[cfg(unix)]
+        for directory in missing_directories {
+            if let Err(error) = fs::set_permissions(directory, fs::Permissions::from_mode(DIRECTORY_MODE)) {
+                return Err(format!("Can't set permissions of {:?}: {:?}", directory, error));
+            }
+        }
+
+        #[cfg(not(unix))]
+        let _ = missing_directories;
+
+        Ok(())
+    }
+
+    /// Makes the greeter background readable by the greeter, then updates the
+    /// configuration of the display managers.
+    ///
+    /// # Arguments
+    /// * `greeter_configuration` - The greeter configuration to use
+    /// * `root` - The filesystem root
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use greeterbackground::GreeterBackground;
+    ///
+    /// GreeterBackground::install(&greeter_configuration, "/")?;
+    /// ```
+    pub fn install(greeter_configuration: &GreeterConfiguration, root: &str) -> Result<(), String> {
+        let background_filename = GreeterBackground::target_filename(greeter_configuration);
+        GreeterBackground::set_readable(&GreeterBackground::rooted(root, &background_filename))?;
+
+        let display_managers = match &greeter_configuration.display_managers {
+            Some(display_managers) => display_managers.clone(),
+            None => GreeterBackground::detect_display_managers(root),
+        };
+
+        if display_managers.is_empty() {
+            println!("Greeter: no supported display manager found (LightDM, SDDM)");
+        }
+
+        for display_manager in display_managers {
+            let configuration_filename = match display_manager {
+                DisplayManager::Lightdm => GreeterBackground::configure_lightdm(root, &background_filename)?,
+                DisplayManager::Sddm => GreeterBackground::configure_sddm(
+                    root,
+                    greeter_configuration.sddm_theme.as_deref(),
+                    &background_filename)?,
+            };
+
+            println!("Greeter: {:?} configured ({})", display_manager, configuration_filename.to_string_lossy());
+        }
+
+        Ok(())
+    }
+
+    /// Returns the display managers installed on the system.
+    fn detect_display_managers(root: &str) -> Vec<DisplayManager> {
+        let exists = |filename: &str| GreeterBackground::rooted(root, filename).exists();
+        let mut display_managers = Vec::new();
+
+        if exists("/etc/lightdm") {
+            display_managers.push(DisplayManager::Lightdm);
+        }
+        if exists("/etc/sddm.conf") || exists("/etc/sddm.conf.d") || exists("/usr/share/sddm/themes") {
+            display_managers.push(DisplayManager::Sddm);
+        }
+
+        display_managers
+    }
+
+    /// Sets the background of LightDM GTK greeter.
+    fn configure_lightdm(root: &str, background_filename: &str) -> Result<PathBuf, String> {
+        let configuration_filename = GreeterBackground::rooted(root, "/etc/lightdm/lightdm-gtk-greeter.conf");
+        GreeterBackground::update_ini_file(&configuration_filename, "greeter", "background", background_filename)?;
+
+        Ok(configuration_filename)
+    }
+
+    /// Sets the background of the current SDDM theme.
+    fn configure_sddm(root: &str, theme: Option<&str>, background_filename: &str) -> Result<PathBuf, String> {
+        let theme = match theme {
+            Some(theme) => theme.to_string(),
+            None => GreeterBackground::sddm_current_theme(root).unwrap_or(DEFAULT_SDDM_THEME.to_string()),
+        };
+
+        let theme_directory = GreeterBackground::rooted(root, &format!("/usr/share/sddm/themes/{}", theme));
+        if !theme_directory.is_dir() {
+            return Err(format!("Can't find SDDM theme {:?}", theme_directory));
+        }
+
+        let configuration_filename = theme_directory.join("theme.conf.user");
+        GreeterBackground::update_ini_file(&configuration_filename, "General", "background", background_filename)?;
+
+        Ok(configuration_filename)
+    }
+
+    /// Returns the SDDM theme in use, as configured in `sddm.conf.d` directories and `sddm.conf`.
+    fn sddm_current_theme(root: &str) -> Option<String> {
+        let mut configuration_filenames = Vec::new();
+        for directory in ["/usr/lib/sddm/sddm.conf.d", "/etc/sddm.conf.d"] {
+            if let Ok(dir_entries) = fs::read_dir(GreeterBackground::rooted(root, directory)) {
+                let mut filenames: Vec<PathBuf> = dir_entries
+                    .filter_map(|dir_entry| dir_entry.ok().map(|dir_entry| dir_entry.path()))
+                    .filter(|path| path.extension().is_some_and(|extension| extension == "conf"))
+                    .collect();
+                filenames.sort();
+                configuration_filenames.extend(filenames);
+            }
+        }
+        configuration_filenames.push(GreeterBackground::rooted(root, "/etc/sddm.conf"));
+
+        // Last definition wins
+        configuration_filenames
+            .iter()
+            .rev()
+            .filter_map(|filename| fs::read_to_string(filename).ok())
+            .filter_map(|content| GreeterBackground::ini_value(&content, "Theme", "Current"))
+            .find(|theme| !theme.is_empty())
+    }
+
+    /// Sets a value in an INI file, keeping the rest of the file untouched.
+    fn update_ini_file(filename: &Path, section: &str, key: &str, value: &str) -> Result<(), String> {
+        let content = fs::read_to_string(filename).unwrap_or_default();
+
+        GreeterBackground::write_readable(filename, &GreeterBackground::set_ini_value(&content, section, key, value))
+    }
+
+    /// Writes a file readable by the greeter.
+    fn write_readable(filename: &Path, content: &str) -> Result<(), String> {
+        GreeterBackground::create_parent_directory(filename)?;
+
+        if let Err(error) = fs::write(filename, content) {
+            return Err(format!("Can't write {:?}: {:?}", filename, error));
+        }
+
+        GreeterBackground::set_readable(filename)
+    }
+
+    /// Makes a file readable by everyone.
+    fn set_readable(filename: &Path) -> Result<(), String> {
+        #[cfg(unix)]
+        if let Err(error) = fs::set_permissions(filename, fs::Permissions::from_mode(FILE_MODE)) {
+            return Err(format!("Can't set permissions of {:?}: {:?}", filename, error));
+        }
+
+        #[cfg(not(unix))]
+        let _ = filename;
+
+        Ok(())
+    }
+
+    /// Returns the value of a key in INI content.
+    fn ini_value(content: &str, section: &str, key: &str) -> Option<String> {
+        let mut current_section = "";
+
+        for line in content.lines().map(str::trim) {
+            if let Some(section_name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                current_section = section_name.trim();
+            } else if current_section == section {
+                if let Some((line_key, line_value)) = line.split_once('=') {
+                    if line_key.trim() == key {
+                        return Some(line_value.trim().to_string());
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Sets the value of a key in INI content. The key is replaced if it already exists,
+    /// otherwise it is added at the end of the section (the section is created if needed).
+    fn set_ini_value(content: &str, section: &str, key: &str, value: &str) -> String {
+        let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+        let new_line = format!("{}={}", key, value);
+
+        let section_start = lines.iter().position(|line| line.trim() == format!("[{}]", section));
+        let section_start = match section_start {
+            Some(section_start) => section_start,
+            None => {
+                if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                    lines.push(String::new());
+                }
+                lines.push(format!("[{}]", section));
+                lines.push(new_line);
+
+                return lines.join("\n") + "\n";
+            }
+        };
+
+        let section_end = lines[section_start + 1..]
+            .iter()
+            .position(|line| line.trim().starts_with('['))
+            .map(|idx| section_start + 1 + idx)
+            .unwrap_or(lines.len());
+
+        let existing_line = lines[section_start + 1..section_end]
+            .iter()
+            .position(|line| line.split_once('=').is_some_and(|(line_key, _)| line_key.trim() == key));
+
+        match existing_line {
+            Some(idx) => lines[section_start + 1 + idx] = new_line,
+            None => {
+                // Inserts after the last non-empty line of the section
+                let insert_at = (section_start + 1..section_end)
+                    .rev()
+                    .find(|idx| !lines[*idx].trim().is_empty())
+                    .map(|idx| idx + 1)
+                    .unwrap_or(section_start + 1);
+                lines.insert(insert_at, new_line);
+            }
+        }
+
+        lines.join("\n") + "\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::process;
+
+    use super::GreeterBackground;
+    use crate::bingwallpaper::configuration::GreeterConfiguration;
+
+    #[test]
+    fn install_into_greeter_root() {
+        let root = std::env::temp_dir().join(format!("bingwallpaper-greeter-{}", process::id()));
+        let root_as_str = root.to_string_lossy().to_string();
+        let _ = fs::remove_dir_all(&root);
+
+        // A system with LightDM and SDDM, using the "maya" theme
+        let background_filename = root.join("usr/share/backgrounds/bingwallpaper-greeter.jpg");
+        GreeterBackground::create_parent_directory(&background_filename).unwrap();
+        fs::write(&background_filename, b"jpeg").unwrap();
+        fs::create_dir_all(root.join("etc/lightdm")).unwrap();
+        fs::write(root.join("etc/lightdm/lightdm-gtk-greeter.conf"), "[greeter]\ntheme-name=Adwaita\n").unwrap();
+        fs::create_dir_all(root.join("usr/share/sddm/themes/maya")).unwrap();
+        fs::create_dir_all(root.join("etc/sddm.conf.d")).unwrap();
+        fs::write(root.join("etc/sddm.conf.d/10-theme.conf"), "[Theme]\nCurrent=maya\n").unwrap();
+
+        GreeterBackground::install(&GreeterConfiguration::default(), &root_as_str).unwrap();
+
+        let lightdm_configuration = fs::read_to_string(root.join("etc/lightdm/lightdm-gtk-greeter.conf")).unwrap();
+        assert_eq!(
+            lightdm_configuration,
+            "[greeter]\ntheme-name=Adwaita\nbackground=/usr/share/backgrounds/bingwallpaper-greeter.jpg\n");
+
+        let sddm_configuration = fs::read_to_string(root.join("usr/share/sddm/themes/maya/theme.conf.user")).unwrap();
+        assert_eq!(sddm_configuration, "[General]\nbackground=/usr/share/backgrounds/bingwallpaper-greeter.jpg\n");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
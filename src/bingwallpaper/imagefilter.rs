@@ -0,0 +1,224 @@
+use image::{imageops, Rgb, RgbImage};
+
+use crate::bingwallpaper::configuration::ImageFilterConfiguration;
+
+/// Default amount of light removed by the dim filter (0.0 - 1.0).
+const DEFAULT_DIM_AMOUNT: f32 = 0.3;
+
+/// Default standard deviation, in pixels, of the blur filter.
+const DEFAULT_BLUR_SIGMA: f32 = 8.0;
+
+/// Default saturation factor (0.0 is grayscale, 1.0 leaves the image unchanged).
+const DEFAULT_SATURATION_FACTOR: f32 = 0.5;
+
+/// Default contrast factor (1.0 leaves the image unchanged).
+const DEFAULT_CONTRAST_FACTOR: f32 = 0.8;
+
+/// Default darkening of the vignette filter in the corners (0.0 - 1.0).
+const DEFAULT_VIGNETTE_STRENGTH: f32 = 0.5;
+
+/// Default distance from the center, relative to the corners, at which the vignette starts (0.0 - 1.0).
+const DEFAULT_VIGNETTE_RADIUS: f32 = 0.5;
+
+/// Maximum change of the red and blue channels applied by the tint filter.
+const MAX_TINT_SHIFT: f32 = 0.2;
+
+/// Applies adjustment filters (dim, blur, saturation, ...) to the wallpaper.
+pub struct ImageFilter {}
+
+impl ImageFilter {
+    /// Applies the filters, in the configured order.
+    ///
+    /// # Arguments
+    /// * `image` - The image to modify
+    /// * `filters` - The filters to apply
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use imagefilter::ImageFilter;
+    ///
+    /// ImageFilter::apply_filters(&mut image, &[ImageFilterConfiguration::Grayscale]);
+    /// ```
+    pub fn apply_filters(image: &mut RgbImage, filters: &[ImageFilterConfiguration]) {
+        for filter in filters {
+            match filter {
+                ImageFilterConfiguration::Dim { amount } => {
+                    let factor = 1.0 - amount.unwrap_or(DEFAULT_DIM_AMOUNT).clamp(0.0, 1.0);
+                    ImageFilter::map_pixels(image, |pixel| pixel.map(|channel| channel * factor));
+                }
+                ImageFilterConfiguration::Blur { sigma } => {
+                    let sigma = sigma.unwrap_or(DEFAULT_BLUR_SIGMA);
+                    if sigma > 0.0 {
+                        *image = imageops::fast_blur(image, sigma);
+                    }
+                }
+                ImageFilterConfiguration::Grayscale => {
+                    ImageFilter::saturate(image, 0.0);
+                }
+                ImageFilterConfiguration::Saturation { factor } => {
+                    ImageFilter::saturate(image, factor.unwrap_or(DEFAULT_SATURATION_FACTOR).max(0.0));
+                }
+                ImageFilterConfiguration::Contrast { factor } => {
+                    let factor = factor.unwrap_or(DEFAULT_CONTRAST_FACTOR).max(0.0);
+                    ImageFilter::map_pixels(image, |pixel| pixel.map(|channel| (channel - 0.5) * factor + 0.5));
+                }
+                ImageFilterConfiguration::Vignette { strength, radius } => {
+                    ImageFilter::vignette(
+                        image,
+                        strength.unwrap_or(DEFAULT_VIGNETTE_STRENGTH).clamp(0.0, 1.0),
+                        radius.unwrap_or(DEFAULT_VIGNETTE_RADIUS).clamp(0.0, 0.99));
+                }
+                ImageFilterConfiguration::Tint { temperature } => {
+                    let shift = temperature.clamp(-1.0, 1.0) * MAX_TINT_SHIFT;
+                    ImageFilter::map_pixels(image, |[r, g, b]| [r * (1.0 + shift), g, b * (1.0 - shift)]);
+                }
+            }
+        }
+    }
+
+    /// Changes the saturation of the image (0.0 is grayscale, 1.0 leaves the image unchanged).
+    fn saturate(image: &mut RgbImage, factor: f32) {
+        ImageFilter::map_pixels(image, |[r, g, b]| {
+            let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+            [r, g, b].map(|channel| luma + (channel - luma) * factor)
+        });
+    }
+
+    /// Darkens the image progressively from the given distance from the center to the corners.
+    fn vignette(image: &mut RgbImage, strength: f32, radius: f32) {
+        let (width, height) = image.dimensions();
+        let (center_x, center_y) = (width as f32 / 2.0, height as f32 / 2.0);
+        let corner_distance = (center_x * center_x + center_y * center_y).sqrt().max(1.0);
+
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            let (dx, dy) = (x as f32 + 0.5 - center_x, y as f32 + 0.5 - center_y);
+            let distance = (dx * dx + dy * dy).sqrt() / corner_distance;
+            if distance <= radius {
+                continue;
+            }
+
+            // Smoothstep between the vignette radius and the corners
+            let t = ((distance - radius) / (1.0 - radius)).min(1.0);
+            let factor = 1.0 - strength * t * t * (3.0 - 2.0 * t);
+            pixel.0 = pixel.0.map(|channel| (channel as f32 * factor).round() as u8);
+        }
+    }
+
+    /// Applies a function to each pixel, with channels expressed between 0.0 and 1.0.
+    fn map_pixels(image: &mut RgbImage, function: impl Fn([f32; 3]) -> [f32; 3]) {
+        for pixel in image.pixels_mut() {
+            let Rgb(channels) = *pixel;
+            let channels = function(channels.map(|channel| channel as f32 / 255.0));
+            pixel.0 = channels.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, RgbImage};
+
+    use super::ImageFilter;
+    use crate::bingwallpaper::configuration::ImageFilterConfiguration;
+
+    /// Applies the filters to a 1x1 image of the given color, and returns the resulting color.
+    fn filtered(color: [u8; 3], filters: &[ImageFilterConfiguration]) -> [u8; 3] {
+        let mut image = RgbImage::from_pixel(1, 1, Rgb(color));
+        ImageFilter::apply_filters(&mut image, filters);
+        image.get_pixel(0, 0).0
+    }
+
+    #[test]
+    fn dim() {
+        assert_eq!(filtered([200, 100, 0], &[ImageFilterConfiguration::Dim { amount: Some(0.5) }]), [100, 50, 0]);
+        assert_eq!(filtered([100, 100, 100], &[ImageFilterConfiguration::Dim { amount: None }]), [70, 70, 70]);
+        assert_eq!(filtered([100, 100, 100], &[ImageFilterConfiguration::Dim { amount: Some(2.0) }]), [0, 0, 0]);
+        assert_eq!(filtered([100, 100, 100], &[ImageFilterConfiguration::Dim { amount: Some(-1.0) }]), [100, 100, 100]);
+    }
+
+    #[test]
+    fn blur() {
+        // A uniform image stays uniform
+        let mut image = RgbImage::from_pixel(9, 9, Rgb([10, 20, 30]));
+        ImageFilter::apply_filters(&mut image, &[ImageFilterConfiguration::Blur { sigma: Some(2.0) }]);
+        assert!(image.pixels().all(|pixel| pixel.0 == [10, 20, 30]));
+
+        // A white dot spreads symmetrically around its location
+        let mut image = RgbImage::new(9, 9);
+        image.put_pixel(4, 4, Rgb([255, 255, 255]));
+        ImageFilter::apply_filters(&mut image, &[ImageFilterConfiguration::Blur { sigma: Some(1.0) }]);
+        let center = image.get_pixel(4, 4)[0];
+        let neighbors = [(3, 4), (5, 4), (4, 3), (4, 5)].map(|(x, y)| image.get_pixel(x, y)[0]);
+        assert!(center < 255 && center > 0, "{}", center);
+        assert!(neighbors.iter().all(|neighbor| *neighbor == neighbors[0] && *neighbor > 0 && *neighbor < center));
+        assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0]);
+
+        // No blur without a positive sigma
+        let mut image = RgbImage::new(3, 3);
+        image.put_pixel(1, 1, Rgb([255, 255, 255]));
+        ImageFilter::apply_filters(&mut image, &[ImageFilterConfiguration::Blur { sigma: Some(0.0) }]);
+        assert_eq!(image.get_pixel(1, 1).0, [255, 255, 255]);
+        assert_eq!(image.get_pixel(0, 1).0, [0, 0, 0]);
+    }
+
+    #[test]
+    fn saturation() {
+        assert_eq!(filtered([255, 0, 0], &[ImageFilterConfiguration::Grayscale]), [54, 54, 54]);
+        assert_eq!(filtered([0, 255, 0], &[ImageFilterConfiguration::Saturation { factor: Some(0.0) }]), [182, 182, 182]);
+        assert_eq!(filtered([255, 0, 0], &[ImageFilterConfiguration::Saturation { factor: None }]), [155, 27, 27]);
+        assert_eq!(filtered([255, 0, 0], &[ImageFilterConfiguration::Saturation { factor: Some(1.0) }]), [255, 0, 0]);
+        let negative_saturation = [ImageFilterConfiguration::Saturation { factor: Some(-1.0) }];
+        assert_eq!(filtered([200, 100, 50], &negative_saturation), [118, 118, 118]);
+    }
+
+    #[test]
+    fn contrast() {
+        let contrast = |factor| [ImageFilterConfiguration::Contrast { factor }];
+        assert_eq!(filtered([255, 0, 128], &contrast(Some(0.5))), [191, 64, 128]);
+        assert_eq!(filtered([255, 0, 128], &contrast(None)), [230, 25, 128]);
+        assert_eq!(filtered([255, 0, 100], &contrast(Some(0.0))), [128, 128, 128]);
+        assert_eq!(filtered([192, 64, 128], &contrast(Some(3.0))), [255, 0, 129]);
+    }
+
+    #[test]
+    fn vignette() {
+        let vignette = |strength, radius| [ImageFilterConfiguration::Vignette { strength, radius }];
+
+        // Corners darkened, the center kept
+        let mut image = RgbImage::from_pixel(10, 10, Rgb([255, 255, 255]));
+        ImageFilter::apply_filters(&mut image, &vignette(Some(1.0), Some(0.5)));
+        assert_eq!(image.get_pixel(4, 4).0, [255, 255, 255]);
+        assert_eq!(image.get_pixel(5, 5).0, [255, 255, 255]);
+        for (x, y) in [(0, 0), (9, 0), (0, 9), (9, 9)] {
+            assert_eq!(image.get_pixel(x, y).0, [27, 27, 27]);
+        }
+        assert!(image.get_pixel(0, 4)[0] > 27 && image.get_pixel(0, 4)[0] < 255);
+
+        // Without strength, unchanged
+        let mut image = RgbImage::from_pixel(10, 10, Rgb([255, 255, 255]));
+        ImageFilter::apply_filters(&mut image, &vignette(Some(0.0), None));
+        assert!(image.pixels().all(|pixel| pixel.0 == [255, 255, 255]));
+    }
+
+    #[test]
+    fn tint() {
+        let tint = |temperature| [ImageFilterConfiguration::Tint { temperature }];
+        assert_eq!(filtered([100, 100, 100], &tint(1.0)), [120, 100, 80]);
+        assert_eq!(filtered([100, 100, 100], &tint(-1.0)), [80, 100, 120]);
+        assert_eq!(filtered([100, 100, 100], &tint(5.0)), [120, 100, 80]);
+        assert_eq!(filtered([250, 100, 100], &tint(0.5)), [255, 100, 90]);
+        assert_eq!(filtered([100, 100, 100], &tint(0.0)), [100, 100, 100]);
+    }
+
+    #[test]
+    fn filters_are_applied_in_order() {
+        let grayscale_then_tint = [ImageFilterConfiguration::Grayscale, ImageFilterConfiguration::Tint { temperature: 1.0 }];
+        assert_eq!(filtered([255, 0, 0], &grayscale_then_tint), [65, 54, 43]);
+
+        let tint_then_grayscale = [ImageFilterConfiguration::Tint { temperature: 1.0 }, ImageFilterConfiguration::Grayscale];
+        assert_eq!(filtered([255, 0, 0], &tint_then_grayscale), [54, 54, 54]);
+
+        assert_eq!(filtered([10, 20, 30], &[]), [10, 20, 30]);
+    }
+}
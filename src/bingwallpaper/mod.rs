@@ -1,11 +1,45 @@
 pub use self::arguments::BingWallpaperArguments;
+pub use self::backgroundprocess::BackgroundProcess;
 pub use self::bingapiclient::BingAPIClient;
 pub use self::bingwallpaperchanger::BingWallpaperChanger;
+pub use self::collage::Collage;
+pub use self::colorpalette::ColorPalette;
+pub use self::commandrunner::CommandRunner;
 pub use self::configuration::BingWallpaperConfiguration;
-pub use self::textoverlay::TextOverlay;
+pub use self::einkencoder::EinkEncoder;
+pub use self::fingerprint::Fingerprint;
+pub use self::fontchain::FontChain;
+pub use self::greeterbackground::GreeterBackground;
+pub use self::imagefilter::ImageFilter;
+pub use self::outputencoder::OutputEncoder;
+pub use self::overlaytemplate::OverlayTemplate;
+pub use self::renderpipeline::RenderPipeline;
+pub use self::suncalculator::SunCalculator;
+pub use self::textoverlay::{TextOverlay, TextOverlayFonts};
+pub use self::videowallpaper::VideoWallpaper;
+pub use self::wallpaperarchive::WallpaperArchive;
+pub use self::wallpapermetadata::WallpaperMetadata;
 
 mod arguments;
+mod backgroundprocess;
 mod bingapiclient;
 mod bingwallpaperchanger;
+mod collage;
+mod colorpalette;
+mod commandrunner;
 mod configuration;
+mod einkencoder;
+mod fingerprint;
+mod fontchain;
+mod greeterbackground;
+mod imagefilter;
+mod outputencoder;
+mod overlaytemplate;
+mod renderpipeline;
+mod suncalculator;
 mod textoverlay;
+mod videowallpaper;
+mod wallpaperarchive;
+mod wallpapermetadata;
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
+mod wallpapersetter;
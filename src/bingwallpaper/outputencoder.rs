@@ -0,0 +1,184 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use image::codecs::avif::AvifEncoder;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::{ExtendedColorType, ImageEncoder, RgbImage};
+
+use crate::bingwallpaper::configuration::OutputFormatConfiguration;
+
+/// Default JPEG quality (1-100).
+const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+/// Default PNG compression level (1-9).
+const DEFAULT_PNG_COMPRESSION_LEVEL: u8 = 6;
+
+/// Default WebP quality (0-100) when lossy.
+const DEFAULT_WEBP_QUALITY: f32 = 85.0;
+
+/// Default AVIF quality (1-100).
+const DEFAULT_AVIF_QUALITY: u8 = 80;
+
+/// Default AVIF encoder speed (1 slowest - 10 fastest).
+const DEFAULT_AVIF_SPEED: u8 = 6;
+
+/// Encodes rendered wallpapers.
+pub struct OutputEncoder {}
+
+impl OutputEncoder {
+    /// Resolves the output format to use for the given file. When no format is configured,
+    /// the format is deduced from the file extension. Otherwise, the file extension must
+    /// match the configured format.
+    ///
+    /// # Arguments
+    /// * `filename` - The location of the output file
+    /// * `output_format` - The configured output format
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use outputencoder::OutputEncoder;
+    ///
+    /// let output_format = OutputEncoder::resolve_format("/tmp/wallpaper.jpg", None)?;
+    /// ```
+    pub fn resolve_format(filename: &str,
+                          output_format: Option<&OutputFormatConfiguration>) -> Result<OutputFormatConfiguration, String> {
+        let output_format = match output_format {
+            None => return OutputFormatConfiguration::from_filename(filename),
+            Some(output_format) => output_format,
+        };
+
+        let extension = Path::new(filename)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        if output_format.extensions().contains(&extension.as_str()) {
+            Ok(output_format.clone())
+        } else {
+            Err(format!(
+                "File extension of {:?} does not match output format, expected: {}",
+                filename,
+                output_format.extensions().join(", ")))
+        }
+    }
+
+    /// Checks if a JPEG image, as downloaded, can be written as is.
+    ///
+    /// # Arguments
+    /// * `output_format` - The resolved output format
+    pub fn accepts_jpeg_as_is(output_format: &OutputFormatConfiguration) -> bool {
+        matches!(output_format, OutputFormatConfiguration::Jpeg { quality: None, progressive: None | Some(false) })
+    }
+
+    /// Encodes the image.
+    ///
+    /// # Arguments
+    /// * `image` - The image to encode
+    /// * `output_format` - The resolved output format
+    pub fn encode(image: &RgbImage, output_format: &OutputFormatConfiguration) -> Result<Vec<u8>, String> {
+        let (width, height) = image.dimensions();
+        let mut output_content: Vec<u8> = Vec::new();
+
+        match output_format {
+            OutputFormatConfiguration::Jpeg { quality, progressive } => {
+                if width > u16::MAX as u32 || height > u16::MAX as u32 {
+                    return Err(format!("Image is too large to be encoded as JPEG: {}x{}", width, height));
+                }
+
+                let mut encoder = jpeg_encoder::Encoder::new(
+                    &mut output_content,
+                    quality.unwrap_or(DEFAULT_JPEG_QUALITY).clamp(1, 100));
+                encoder.set_progressive(progressive.unwrap_or(false));
+
+                let color_type = jpeg_encoder::ColorType::Rgb;
+                if let Err(error) = encoder.encode(image.as_raw(), width as u16, height as u16, color_type) {
+                    return Err(format!("Can't encode JPEG image: {:?}", error));
+                }
+            }
+            OutputFormatConfiguration::Png { compression_level } => {
+                let encoder = PngEncoder::new_with_quality(
+                    Cursor::new(&mut output_content),
+                    CompressionType::Level(compression_level.unwrap_or(DEFAULT_PNG_COMPRESSION_LEVEL).clamp(1, 9)),
+                    FilterType::Adaptive);
+
+                if let Err(error) = encoder.write_image(image.as_raw(), width, height, ExtendedColorType::Rgb8) {
+                    return Err(format!("Can't encode PNG image: {:?}", error));
+                }
+            }
+            OutputFormatConfiguration::Webp { lossless, quality } => {
+                let encoder = webp::Encoder::from_rgb(image.as_raw(), width, height);
+                let encoded_image = if lossless.unwrap_or(false) {
+                    encoder.encode_lossless()
+                } else {
+                    encoder.encode(quality.unwrap_or(DEFAULT_WEBP_QUALITY).clamp(0.0, 100.0))
+                };
+
+                output_content.extend_from_slice(&encoded_image);
+            }
+            OutputFormatConfiguration::Avif { quality, speed } => {
+                let encoder = AvifEncoder::new_with_speed_quality(
+                    Cursor::new(&mut output_content),
+                    speed.unwrap_or(DEFAULT_AVIF_SPEED).clamp(1, 10),
+                    quality.unwrap_or(DEFAULT_AVIF_QUALITY).clamp(1, 100));
+
+                if let Err(error) = encoder.write_image(image.as_raw(), width, height, ExtendedColorType::Rgb8) {
+                    return Err(format!("Can't encode AVIF image: {:?}", error));
+                }
+            }
+        }
+
+        Ok(output_content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OutputEncoder;
+    use crate::bingwallpaper::configuration::OutputFormatConfiguration;
+
+    #[test]
+    fn resolve_format() {
+        let jpeg = OutputFormatConfiguration::Jpeg { quality: None, progressive: None };
+        let png = OutputFormatConfiguration::Png { compression_level: None };
+
+        // Without configured format: deduced from the file extension
+        assert!(OutputEncoder::resolve_format("/tmp/wallpaper.jpg", None).unwrap() == jpeg);
+        assert!(OutputEncoder::resolve_format("/tmp/wallpaper.JPEG", None).unwrap() == jpeg);
+        assert!(OutputEncoder::resolve_format("/tmp/wallpaper.png", None).unwrap() == png);
+        assert!(OutputEncoder::resolve_format("/tmp/wallpaper.webp", None).unwrap()
+            == OutputFormatConfiguration::Webp { lossless: None, quality: None });
+        assert!(OutputEncoder::resolve_format("/tmp/wallpaper.avif", None).unwrap()
+            == OutputFormatConfiguration::Avif { quality: None, speed: None });
+        assert_eq!(
+            OutputEncoder::resolve_format("/tmp/wallpaper.bmp", None).err().unwrap(),
+            "Unsupported wallpaper file extension: \"/tmp/wallpaper.bmp\"");
+        assert!(OutputEncoder::resolve_format("/tmp/wallpaper", None).is_err());
+
+        // With configured format: kept as is when the file extension matches
+        let webp = OutputFormatConfiguration::Webp { lossless: Some(true), quality: Some(70.0) };
+        assert!(OutputEncoder::resolve_format("/tmp/wallpaper.WebP", Some(&webp)).unwrap() == webp);
+        let progressive_jpeg = OutputFormatConfiguration::Jpeg { quality: Some(95), progressive: Some(true) };
+        assert!(OutputEncoder::resolve_format("/tmp/wallpaper.jpeg", Some(&progressive_jpeg)).unwrap() == progressive_jpeg);
+
+        // Otherwise rejected
+        assert_eq!(
+            OutputEncoder::resolve_format("/tmp/wallpaper.jpg", Some(&webp)).err().unwrap(),
+            "File extension of \"/tmp/wallpaper.jpg\" does not match output format, expected: webp");
+        assert_eq!(
+            OutputEncoder::resolve_format("/tmp/wallpaper.png", Some(&jpeg)).err().unwrap(),
+            "File extension of \"/tmp/wallpaper.png\" does not match output format, expected: jpg, jpeg");
+        assert!(OutputEncoder::resolve_format("/tmp/wallpaper", Some(&png)).is_err());
+    }
+
+    #[test]
+    fn accepts_jpeg_as_is() {
+        let jpeg = |quality, progressive| OutputFormatConfiguration::Jpeg { quality, progressive };
+        assert!(OutputEncoder::accepts_jpeg_as_is(&jpeg(None, None)));
+        assert!(OutputEncoder::accepts_jpeg_as_is(&jpeg(None, Some(false))));
+        assert!(!OutputEncoder::accepts_jpeg_as_is(&jpeg(None, Some(true))));
+        assert!(!OutputEncoder::accepts_jpeg_as_is(&jpeg(Some(90), None)));
+        assert!(!OutputEncoder::accepts_jpeg_as_is(&OutputFormatConfiguration::Png { compression_level: None }));
+        assert!(!OutputEncoder::accepts_jpeg_as_is(&OutputFormatConfiguration::Webp { lossless: None, quality: None }));
+    }
+}
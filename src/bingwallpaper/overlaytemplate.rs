@@ -0,0 +1,208 @@
+use chrono::format::{Item, StrftimeItems};
+
+use crate::bingwallpaper::WallpaperMetadata;
+
+/// Default template: the title, then the copyright.
+pub const DEFAULT_TEMPLATE: &str = "{title}\n{copyright}";
+
+/// Text overlay template.
+///
+/// Supported syntax:
+/// * `{title}`, `{copyright}`, `{copyright_link}`, `{photographer}`, `{location}`, `{market}`, `{source}`
+/// * `{date}` or `{date:<format>}` with a `strftime` format (ie: `{date:%A %d %B}`)
+/// * `{?field}...{/field}` to only keep the enclosed text when the field is not empty
+/// * `{{` and `}}` to write literal braces
+pub struct OverlayTemplate {}
+
+impl OverlayTemplate {
+    /// Renders the template. Lines left empty after rendering are removed.
+    ///
+    /// # Arguments
+    /// * `template` - The template to render
+    /// * `metadata` - The wallpaper information
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use overlaytemplate::OverlayTemplate;
+    ///
+    /// let lines = OverlayTemplate::render("{title}\n{?photographer}Photo: {photographer}{/photographer}", &metadata);
+    /// ```
+    pub fn render(template: &str, metadata: &WallpaperMetadata) -> Vec<String> {
+        OverlayTemplate::render_section(template, metadata)
+            .lines()
+            .map(|line| line.trim_end().to_string())
+            .filter(|line| !line.trim().is_empty())
+            .collect()
+    }
+
+    /// Renders a section of the template, recursively handling conditional sections.
+    fn render_section(template: &str, metadata: &WallpaperMetadata) -> String {
+        let mut output = String::new();
+        let mut remaining = template;
+
+        while !remaining.is_empty() {
+            if let Some(rest) = remaining.strip_prefix("{{") {
+                output.push('{');
+                remaining = rest;
+            } else if let Some(rest) = remaining.strip_prefix("}}") {
+                output.push('}');
+                remaining = rest;
+            } else if remaining.starts_with('{') && remaining.contains('}') {
+                let tag_end = remaining.find('}').unwrap();
+                let tag = &remaining[1..tag_end];
+                remaining = &remaining[tag_end + 1..];
+
+                if let Some(field_name) = tag.strip_prefix('?') {
+                    let (section, rest) = OverlayTemplate::split_section(remaining, field_name);
+                    if !OverlayTemplate::resolve(field_name, metadata).unwrap_or_default().trim().is_empty() {
+                        output.push_str(&OverlayTemplate::render_section(section, metadata));
+                    }
+                    remaining = rest;
+                } else if tag.starts_with('/') {
+                    // Closing tag without matching opening tag, ignored
+                } else {
+                    match OverlayTemplate::resolve(tag, metadata) {
+                        Some(value) => output.push_str(&value),
+                        None => {
+                            println!("Unknown text overlay template variable: {{{}}}", tag);
+                            output.push_str(&format!("{{{}}}", tag));
+                        }
+                    }
+                }
+            } else {
+                let c = remaining.chars().next().unwrap();
+                output.push(c);
+                remaining = &remaining[c.len_utf8()..];
+            }
+        }
+
+        output
+    }
+
+    /// Splits the remaining template into the content of the conditional
+    /// section and the text following its closing tag.
+    fn split_section<'a>(template: &'a str, field_name: &str) -> (&'a str, &'a str) {
+        let opening_tag = format!("{{?{}}}", field_name);
+        let closing_tag = format!("{{/{}}}", field_name);
+        let mut depth = 0;
+        let mut offset = 0;
+
+        while offset < template.len() {
+            let remaining = &template[offset..];
+            if remaining.starts_with(&opening_tag) {
+                depth += 1;
+                offset += opening_tag.len();
+            } else if remaining.starts_with(&closing_tag) {
+                if depth == 0 {
+                    return (&template[..offset], &remaining[closing_tag.len()..]);
+                }
+                depth -= 1;
+                offset += closing_tag.len();
+            } else {
+                offset += remaining.chars().next().unwrap().len_utf8();
+            }
+        }
+
+        (template, "")
+    }
+
+    /// Resolves the value of a template variable.
+    fn resolve(variable: &str, metadata: &WallpaperMetadata) -> Option<String> {
+        let (name, format) = match variable.split_once(':') {
+            None => (variable.trim(), None),
+            Some((name, format)) => (name.trim(), Some(format)),
+        };
+
+        if name != "date" {
+            return metadata.field(name).map(str::to_string);
+        }
+
+        let date = match metadata.date() {
+            None => return Some(String::new()),
+            Some(date) => date,
+        };
+
+        let format = format.unwrap_or("%Y-%m-%d");
+        let format_items: Vec<Item> = StrftimeItems::new(format).collect();
+        if format_items.contains(&Item::Error) {
+            println!("Invalid date format in text overlay template: {:?}", format);
+            return Some(String::new());
+        }
+
+        Some(date.format_with_items(format_items.into_iter()).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OverlayTemplate, DEFAULT_TEMPLATE};
+    use crate::bingwallpaper::WallpaperMetadata;
+
+    /// Returns the information of an image, without photographer.
+    fn metadata() -> WallpaperMetadata {
+        WallpaperMetadata {
+            url: "/th?id=OHR.LakeLouise".to_string(),
+            title: "Lake Louise".to_string(),
+            copyright: "Lake Louise, Canada".to_string(),
+            copyright_link: String::new(),
+            photographer: String::new(),
+            location: "Lake Louise, Canada".to_string(),
+            start_date: "20250621".to_string(),
+            market: "en-US".to_string(),
+            source: "Bing".to_string(),
+            video_url: String::new(),
+        }
+    }
+
+    fn render(template: &str) -> Vec<String> {
+        OverlayTemplate::render(template, &metadata())
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(render(DEFAULT_TEMPLATE), ["Lake Louise", "Lake Louise, Canada"]);
+        assert_eq!(render("{source} ({market})"), ["Bing (en-US)"]);
+
+        // Unknown variables are kept as is
+        assert_eq!(render("{title} {unknown}"), ["Lake Louise {unknown}"]);
+
+        // Empty lines are removed
+        assert_eq!(render("{title}\n{photographer}\n\n{source}"), ["Lake Louise", "Bing"]);
+    }
+
+    #[test]
+    fn conditional_sections() {
+        assert_eq!(render("{title}{?photographer} by {photographer}{/photographer}"), ["Lake Louise"]);
+        assert_eq!(render("{?location}In {location}{/location}"), ["In Lake Louise, Canada"]);
+
+        // Nested sections, of other fields and of the same field
+        assert_eq!(render("{?title}{title}{?photographer} by {photographer}{/photographer}!{/title}"), ["Lake Louise!"]);
+        assert_eq!(render("{?title}a{?title}b{/title}c{/title}d"), ["abcd"]);
+        assert_eq!(render("{?photographer}a{?title}b{/title}c{/photographer}d"), ["d"]);
+
+        // An unclosed section runs until the end, a closing tag without section is ignored
+        assert_eq!(render("{title}{?photographer}\nPhoto: {photographer}"), ["Lake Louise"]);
+        assert_eq!(render("{title}{?location}\n{location}"), ["Lake Louise", "Lake Louise, Canada"]);
+        assert_eq!(render("{title}{/photographer}"), ["Lake Louise"]);
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(render("{{title}}"), ["{title}"]);
+        assert_eq!(render("{{{title}}}"), ["{Lake Louise}"]);
+        assert_eq!(render("a } b { c"), ["a } b { c"]);
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(render("{date}"), ["2025-06-21"]);
+        assert_eq!(render("{date:%A %d %B}"), ["Saturday 21 June"]);
+
+        // Invalid format, or unknown date: nothing is written
+        assert_eq!(render("{date:%Q}|"), ["|"]);
+        let mut metadata = metadata();
+        metadata.start_date = String::from("unknown");
+        assert_eq!(OverlayTemplate::render("{date}|{?date}Today{/date}", &metadata), ["|"]);
+    }
+}
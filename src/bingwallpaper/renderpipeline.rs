@@ -0,0 +1,516 @@
+use std::borrow::Cow;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, ImageReader, RgbImage};
+
+use crate::bingwallpaper::configuration::{
+    CollageConfiguration, EinkConfiguration, ImageFilterConfiguration, OutputFormatConfiguration,
+    TextOverlayConfiguration, WallpaperVariantConfiguration,
+};
+use crate::bingwallpaper::wallpaperarchive::ArchivedImage;
+use crate::bingwallpaper::{
+    BingWallpaperConfiguration, Collage, EinkEncoder, Fingerprint, ImageFilter, OutputEncoder, TextOverlay,
+    TextOverlayFonts, WallpaperMetadata,
+};
+
+/// Renders the wallpaper, and its variants, from the pristine downloaded image.
+///
+/// The downloaded image is never modified. Rendered outputs are regenerated from it
+/// only when the downloaded image or the render settings changed since the last rendering.
+/// The image is decoded once, then every step (resize, filters, overlay, encode) runs in memory
+/// and each output is written exactly once.
+pub struct RenderPipeline {}
+
+/// Output rendered from the downloaded image: the wallpaper or one of its variants.
+struct RenderTarget<'a> {
+    name: &'static str,
+    filename: String,
+    state_filename: String,
+    dimensions: (u32, u32),
+    output_format: OutputFormatConfiguration,
+    filters: Vec<ImageFilterConfiguration>,
+    text_overlay: Option<Cow<'a, TextOverlayConfiguration>>,
+    eink: Option<&'a EinkConfiguration>,
+}
+
+/// Duration of each step of the render pipeline.
+struct RenderPipelineTimings {
+    stages: Vec<(String, Duration)>,
+}
+
+impl RenderTarget<'_> {
+    /// Returns the name of a step of the render pipeline for this output.
+    fn stage_name(&self, stage_name: &str) -> String {
+        if self.name.is_empty() {
+            stage_name.to_string()
+        } else {
+            format!("{} {}", self.name, stage_name)
+        }
+    }
+}
+
+impl RenderPipelineTimings {
+    /// Runs a step of the render pipeline and records its duration.
+    fn measure<T>(&mut self, stage_name: impl Into<String>, stage: impl FnOnce() -> T) -> T {
+        let started_at = Instant::now();
+        let result = stage();
+        self.stages.push((stage_name.into(), started_at.elapsed()));
+
+        result
+    }
+
+    /// Logs the duration of each step.
+    fn log(&self) {
+        let stages: Vec<String> = self.stages
+            .iter()
+            .map(|(stage_name, duration)| format!("{} {}ms", stage_name, duration.as_millis()))
+            .collect();
+
+        println!("Render pipeline: {}", stages.join(", "));
+    }
+}
+
+impl RenderPipeline {
+    /// Renders the wallpaper and its variants, if needed.
+    ///
+    /// Returns the location of the outputs which have been rendered, outputs already
+    /// up to date are not included.
+    ///
+    /// # Arguments
+    /// * `configuration` - The Bing Wallpaper configuration to use
+    /// * `metadata` - The wallpaper information
+    /// * `original_content` - The downloaded image content, if already in memory
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use renderpipeline::RenderPipeline;
+    ///
+    /// let rendered_filenames = RenderPipeline::render(configuration, &metadata, None)?;
+    /// ```
+    pub fn render(configuration: &BingWallpaperConfiguration,
+                  metadata: &WallpaperMetadata,
+                  original_content: Option<&[u8]>) -> Result<Vec<String>, String> {
+        let targets = RenderPipeline::targets(configuration)?;
+
+        RenderPipeline::render_targets(configuration, targets, metadata, original_content)
+    }
+
+    /// Renders the greeter (login screen) background from the downloaded image, if needed.
+    ///
+    /// Returns `true` if the background has been rendered, `false` if it was already up to date.
+    ///
+    /// # Arguments
+    /// * `configuration` - The Bing Wallpaper configuration to use
+    /// * `filename` - The location of the greeter background
+    /// * `state_filename` - The location of the file holding the fingerprint of the last rendering
+    /// * `metadata` - The wallpaper information
+    pub fn render_greeter(configuration: &BingWallpaperConfiguration,
+                          filename: &str,
+                          state_filename: &str,
+                          metadata: &WallpaperMetadata) -> Result<bool, String> {
+        let greeter_configuration = configuration.greeter.clone().unwrap_or_default();
+        let variant = WallpaperVariantConfiguration {
+            target_filename: None,
+            filters: greeter_configuration.filters,
+            text_overlay: greeter_configuration.text_overlay,
+        };
+
+        let mut target = RenderPipeline::variant_target(
+            configuration,
+            "greeter",
+            filename.to_string(),
+            &variant,
+            &[])?;
+        target.dimensions = (
+            greeter_configuration.image_dimension_width.unwrap_or(configuration.image_dimension_width),
+            greeter_configuration.image_dimension_height.unwrap_or(configuration.image_dimension_height));
+        target.state_filename = state_filename.to_string();
+
+        let rendered_filenames = RenderPipeline::render_targets(configuration, vec![target], metadata, None)?;
+
+        Ok(!rendered_filenames.is_empty())
+    }
+
+    /// Renders the collage of the latest archived images, if needed.
+    ///
+    /// Returns `true` if the collage has been rendered, `false` if it was already up to date.
+    ///
+    /// # Arguments
+    /// * `configuration` - The Bing Wallpaper configuration to use
+    /// * `collage_configuration` - The collage configuration to use
+    /// * `filename` - The location of the collage
+    /// * `archived_images` - The images to lay out, most recent first
+    pub fn render_collage(configuration: &BingWallpaperConfiguration,
+                          collage_configuration: &CollageConfiguration,
+                          filename: &str,
+                          archived_images: &[ArchivedImage]) -> Result<bool, String> {
+        let output_format = RenderPipeline::output_format(configuration, filename)?;
+        let dimensions = (configuration.image_dimension_width, configuration.image_dimension_height);
+
+        // Checks if the collage is up to date
+        let mut fingerprint = Fingerprint::new();
+        for archived_image in archived_images {
+            let archived_image_len = fs::metadata(&archived_image.filename).map(|metadata| metadata.len());
+            fingerprint
+                .add(&archived_image.filename)
+                .add(archived_image_len.unwrap_or_default().to_le_bytes())
+                .add(serde_json::to_string(&archived_image.metadata).unwrap_or_default());
+        }
+        fingerprint
+            .add(serde_json::to_string(collage_configuration).unwrap_or_default())
+            .add(serde_json::to_string(&output_format).unwrap_or_default())
+            .add(dimensions.0.to_le_bytes())
+            .add(dimensions.1.to_le_bytes());
+        let fingerprint = fingerprint.to_hex();
+
+        let state_filename = RenderPipeline::state_filename(filename);
+        let is_up_to_date = Path::new(filename).exists()
+            && fs::read_to_string(&state_filename).is_ok_and(|previous_fingerprint| previous_fingerprint == fingerprint);
+        if is_up_to_date {
+            return Ok(false);
+        }
+
+        let mut timings = RenderPipelineTimings { stages: Vec::new() };
+        let image = timings.measure(
+            "collage",
+            || Collage::compose(collage_configuration, archived_images, dimensions))?;
+        let output_content = timings.measure("encode", || OutputEncoder::encode(&image, &output_format))?;
+
+        if let Err(error) = timings.measure("write", || fs::write(filename, output_content)) {
+            return Err(format!("Can't store collage {:?}: {:?}", filename, error));
+        }
+
+        timings.log();
+
+        if let Err(error) = fs::write(&state_filename, fingerprint) {
+            println!("Can't store render state: {:?}", error);
+        }
+
+        Ok(true)
+    }
+
+    /// Renders the outputs which are not up to date.
+    fn render_targets(configuration: &BingWallpaperConfiguration,
+                      targets: Vec<RenderTarget>,
+                      metadata: &WallpaperMetadata,
+                      original_content: Option<&[u8]>) -> Result<Vec<String>, String> {
+        let original_filename = configuration.original_filename();
+
+        // Keeps outputs which are not up to date
+        let mut pending_targets = Vec::new();
+        for target in targets {
+            let state_filename = target.state_filename.clone();
+            let fingerprint = RenderPipeline::fingerprint(&target, metadata, &original_filename)?;
+
+            let is_up_to_date = Path::new(&target.filename).exists()
+                && fs::read_to_string(&state_filename).is_ok_and(|previous_fingerprint| previous_fingerprint == fingerprint);
+            if !is_up_to_date {
+                pending_targets.push((target, state_filename, fingerprint));
+            }
+        }
+
+        if pending_targets.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut timings = RenderPipelineTimings { stages: Vec::new() };
+
+        // Reads the original image, unless it is already in memory
+        let original_content: Cow<[u8]> = match original_content {
+            Some(original_content) => Cow::Borrowed(original_content),
+            None => match timings.measure("read", || fs::read(&original_filename)) {
+                Err(error) => return Err(format!("Can't read original image: {:?}", error)),
+                Ok(original_content) => Cow::Owned(original_content),
+            },
+        };
+
+        // Renders each output in memory, from the same decoded image
+        let mut decoded_image: Option<DynamicImage> = None;
+        let mut resized_images: Vec<((u32, u32), RgbImage)> = Vec::new();
+        let mut rendered_filenames = Vec::new();
+        let mut fonts = TextOverlayFonts::new();
+        let pending_target_count = pending_targets.len();
+
+        for (idx, (target, state_filename, fingerprint)) in pending_targets.into_iter().enumerate() {
+            let is_passthrough = RenderPipeline::is_passthrough(&target, &original_content);
+            let output_content: Cow<[u8]> = if is_passthrough {
+                Cow::Borrowed(&original_content)
+            } else {
+                if !resized_images.iter().any(|(dimensions, _)| *dimensions == target.dimensions) {
+                    if decoded_image.is_none() {
+                        decoded_image = match timings.measure("decode", || image::load_from_memory(&original_content)) {
+                            Err(error) => return Err(format!("Can't decode original image: {:?}", error)),
+                            Ok(image) => Some(image),
+                        };
+                    }
+
+                    let decoded_image = decoded_image.as_ref().unwrap();
+                    let image = timings.measure(
+                        target.stage_name("resize"),
+                        || RenderPipeline::resize(decoded_image, target.dimensions));
+                    resized_images.push((target.dimensions, image));
+                }
+
+                // The last output takes the resized image, others work on a copy
+                let resized_image_idx = resized_images
+                    .iter()
+                    .position(|(dimensions, _)| *dimensions == target.dimensions)
+                    .unwrap();
+                let mut image = if idx + 1 == pending_target_count {
+                    resized_images.swap_remove(resized_image_idx).1
+                } else {
+                    resized_images[resized_image_idx].1.clone()
+                };
+
+                if !target.filters.is_empty() {
+                    timings.measure(
+                        target.stage_name("filters"),
+                        || ImageFilter::apply_filters(&mut image, &target.filters));
+                }
+
+                if let Some(overlay_configuration) = &target.text_overlay {
+                    timings.measure(
+                        target.stage_name("overlay"),
+                        || TextOverlay::apply_overlay(&mut image, overlay_configuration, metadata, &mut fonts));
+                }
+
+                match target.eink {
+                    None => Cow::Owned(timings.measure(
+                        target.stage_name("encode"),
+                        || OutputEncoder::encode(&image, &target.output_format))?),
+                    Some(eink_configuration) => {
+                        let color_indexes = timings.measure(
+                            target.stage_name("dither"),
+                            || EinkEncoder::dither(&image, eink_configuration));
+                        Cow::Owned(timings.measure(target.stage_name("encode"), || EinkEncoder::encode(
+                            &color_indexes,
+                            image.dimensions(),
+                            eink_configuration,
+                            &target.filename))?)
+                    }
+                }
+            };
+
+            // Writes the output
+            if let Err(error) = timings.measure(target.stage_name("write"), || fs::write(&target.filename, output_content)) {
+                return Err(format!("Can't store wallpaper {:?}: {:?}", target.filename, error));
+            }
+
+            if let Some(state_directory) = Path::new(&state_filename).parent() {
+                let _ = fs::create_dir_all(state_directory);
+            }
+            if let Err(error) = fs::write(&state_filename, fingerprint) {
+                println!("Can't store render state: {:?}", error);
+            }
+
+            rendered_filenames.push(target.filename);
+        }
+
+        timings.log();
+
+        Ok(rendered_filenames)
+    }
+
+    /// Returns the outputs to render: the wallpaper, then the enabled variants.
+    fn targets(configuration: &BingWallpaperConfiguration) -> Result<Vec<RenderTarget<'_>>, String> {
+        let output_format = RenderPipeline::output_format(configuration, &configuration.target_filename)?;
+
+        let mut targets = vec![RenderTarget {
+            name: "",
+            filename: configuration.target_filename.clone(),
+            state_filename: RenderPipeline::state_filename(&configuration.target_filename),
+            dimensions: (configuration.image_dimension_width, configuration.image_dimension_height),
+            output_format: output_format.clone(),
+            filters: configuration.filters.clone().unwrap_or_default(),
+            text_overlay: configuration.text_overlay.as_ref().map(Cow::Borrowed),
+            eink: None,
+        }];
+
+        if let (Some(dark_variant), Some(filename)) = (&configuration.dark_variant, configuration.dark_variant_filename()) {
+            let default_filters = [
+                ImageFilterConfiguration::Dim { amount: Some(0.4) },
+                ImageFilterConfiguration::Saturation { factor: Some(0.7) },
+            ];
+
+            targets.push(RenderPipeline::variant_target(
+                configuration,
+                "dark",
+                filename,
+                dark_variant,
+                &default_filters)?);
+        }
+
+        let night_variant_filename = configuration.night_variant_filename();
+        if let (Some(night_variant), Some(filename)) = (&configuration.night_variant, night_variant_filename) {
+            let default_filters = [
+                ImageFilterConfiguration::Dim { amount: Some(0.35) },
+                ImageFilterConfiguration::Tint { temperature: 0.5 },
+            ];
+
+            targets.push(RenderPipeline::variant_target(
+                configuration,
+                "night",
+                filename,
+                night_variant,
+                &default_filters)?);
+        }
+
+        let lock_screen_variant_filename = configuration.lock_screen_variant_filename();
+        if let (Some(lock_screen_variant), Some(filename)) = (&configuration.lock_screen_variant,
+                                                             lock_screen_variant_filename) {
+            let default_filters = [
+                ImageFilterConfiguration::Blur { sigma: Some(12.0) },
+                ImageFilterConfiguration::Dim { amount: Some(0.3) },
+            ];
+
+            let mut target = RenderPipeline::variant_target(
+                configuration,
+                "lock screen",
+                filename,
+                lock_screen_variant,
+                &default_filters)?;
+
+            // Leaves the clock area clear
+            if let Some(overlay_configuration) = target.text_overlay.as_mut() {
+                let anchor = overlay_configuration.anchor.away_from_clock();
+                overlay_configuration.to_mut().anchor = anchor;
+            }
+
+            targets.push(target);
+        }
+
+        if let (Some(eink), Some(filename)) = (&configuration.eink, configuration.eink_filename()) {
+            let mut filters = configuration.filters.clone().unwrap_or_default();
+            filters.extend_from_slice(eink.filters.as_deref().unwrap_or_default());
+
+            // Encoded by the e-ink encoder, the output format is not used
+            targets.push(RenderTarget {
+                name: "e-ink",
+                state_filename: RenderPipeline::state_filename(&filename),
+                filename,
+                dimensions: (eink.image_dimension_width, eink.image_dimension_height),
+                output_format: OutputFormatConfiguration::Png { compression_level: None },
+                filters,
+                text_overlay: eink.text_overlay.as_ref().or(configuration.text_overlay.as_ref()).map(Cow::Borrowed),
+                eink: Some(eink),
+            });
+        }
+
+        Ok(targets)
+    }
+
+    /// Returns the output format of a file: the wallpaper output format, unless the file extension
+    /// requires another one.
+    fn output_format(configuration: &BingWallpaperConfiguration,
+                     filename: &str) -> Result<OutputFormatConfiguration, String> {
+        let output_format = OutputEncoder::resolve_format(
+            &configuration.target_filename,
+            configuration.output_format.as_ref())?;
+
+        match OutputEncoder::resolve_format(filename, Some(&output_format)) {
+            Err(_) => OutputEncoder::resolve_format(filename, None),
+            Ok(output_format) => Ok(output_format),
+        }
+    }
+
+    /// Returns the output of a variant: the wallpaper filters are applied first, then the variant ones.
+    fn variant_target<'a>(configuration: &'a BingWallpaperConfiguration,
+                          name: &'static str,
+                          filename: String,
+                          variant: &WallpaperVariantConfiguration,
+                          default_filters: &[ImageFilterConfiguration]) -> Result<RenderTarget<'a>, String> {
+        let output_format = RenderPipeline::output_format(configuration, &filename)?;
+
+        let mut filters = configuration.filters.clone().unwrap_or_default();
+        filters.extend_from_slice(variant.filters.as_deref().unwrap_or(default_filters));
+
+        Ok(RenderTarget {
+            name,
+            state_filename: RenderPipeline::state_filename(&filename),
+            filename,
+            dimensions: (configuration.image_dimension_width, configuration.image_dimension_height),
+            output_format,
+            filters,
+            text_overlay: configuration.text_overlay
+                .as_ref()
+                .filter(|_| variant.text_overlay.unwrap_or(true))
+                .map(Cow::Borrowed),
+            eink: None,
+        })
+    }
+
+    /// Checks if the original image can be used as is: no filters, no text overlay, expected
+    /// dimensions and a JPEG image written without specific encoding settings.
+    fn is_passthrough(target: &RenderTarget, original_content: &[u8]) -> bool {
+        let has_rendering = !target.filters.is_empty() || target.text_overlay.is_some() || target.eink.is_some();
+        if has_rendering || !OutputEncoder::accepts_jpeg_as_is(&target.output_format) {
+            return false;
+        }
+
+        let image_reader = match ImageReader::new(Cursor::new(original_content)).with_guessed_format() {
+            Err(_) => return false,
+            Ok(image_reader) => image_reader,
+        };
+
+        image_reader.format() == Some(ImageFormat::Jpeg)
+            && image_reader.into_dimensions().is_ok_and(|dimensions| dimensions == target.dimensions)
+    }
+
+    /// Resizes the image to fill the given dimensions, cropping it if the aspect ratio differs.
+    fn resize(image: &DynamicImage, dimensions: (u32, u32)) -> RgbImage {
+        let (width, height) = dimensions;
+        if (image.width(), image.height()) == (width, height) || width == 0 || height == 0 {
+            return image.to_rgb8();
+        }
+
+        image.resize_to_fill(width, height, FilterType::Lanczos3).into_rgb8()
+    }
+
+    /// Returns the location of the file holding the fingerprint of the last rendering.
+    fn state_filename(target_filename: &str) -> String {
+        let target_filename_as_path = Path::new(target_filename);
+        let file_name = target_filename_as_path.file_name().unwrap_or_default().to_string_lossy();
+
+        target_filename_as_path
+            .with_file_name(format!(".{}.render", file_name.trim_start_matches('.')))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Computes a fingerprint of everything the rendered output depends on:
+    /// the original image, the wallpaper information and the render settings.
+    fn fingerprint(target: &RenderTarget,
+                   metadata: &WallpaperMetadata,
+                   original_filename: &str) -> Result<String, String> {
+        let original_metadata = match fs::metadata(original_filename) {
+            Err(error) => return Err(format!("Can't read original image: {:?}", error)),
+            Ok(original_metadata) => original_metadata,
+        };
+
+        let original_modified = original_metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| modified.as_nanos())
+            .unwrap_or_default();
+
+        let fingerprint = Fingerprint::new()
+            .add(original_metadata.len().to_le_bytes())
+            .add(original_modified.to_le_bytes())
+            .add(serde_json::to_string(metadata).unwrap_or_default())
+            .add(serde_json::to_string(&target.filters).unwrap_or_default())
+            .add(serde_json::to_string(&target.text_overlay).unwrap_or_default())
+            .add(serde_json::to_string(&target.output_format).unwrap_or_default())
+            .add(serde_json::to_string(&target.eink).unwrap_or_default())
+            .add(target.dimensions.0.to_le_bytes())
+            .add(target.dimensions.1.to_le_bytes())
+            .to_hex();
+
+        Ok(fingerprint)
+    }
+}
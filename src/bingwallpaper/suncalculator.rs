@@ -0,0 +1,220 @@
+use chrono::{DateTime, Days, NaiveDate, TimeDelta, Utc};
+
+/// Solar zenith angle, in degrees, at sunrise and sunset (includes atmospheric refraction).
+const SUNRISE_ZENITH: f64 = 90.833;
+
+/// Sunrise and sunset of a day.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SunEvents {
+    Regular {
+        sunrise: DateTime<Utc>,
+        sunset: DateTime<Utc>,
+    },
+    PolarDay,
+    PolarNight,
+}
+
+/// Computes sunrise and sunset times offline, following the NOAA solar calculations.
+pub struct SunCalculator {}
+
+impl SunCalculator {
+    /// Computes sunrise and sunset of the given day.
+    ///
+    /// # Arguments
+    /// * `date` - The day, at the given location
+    /// * `latitude` - Latitude in degrees, positive to the north
+    /// * `longitude` - Longitude in degrees, positive to the east
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suncalculator::SunCalculator;
+    ///
+    /// let sun_events = SunCalculator::sun_events(NaiveDate::from_ymd_opt(2025, 6, 21).unwrap(), 48.85, 2.35);
+    /// ```
+    pub fn sun_events(date: NaiveDate, latitude: f64, longitude: f64) -> SunEvents {
+        let midnight = date.and_hms_opt(0, 0, 0).unwrap().and_utc();
+
+        // Julian century, around the local solar noon
+        let julian_day = midnight.timestamp() as f64 / 86400.0 + 2440587.5 + 0.5 - longitude / 360.0;
+        let t = (julian_day - 2451545.0) / 36525.0;
+
+        let mean_longitude = (280.46646 + t * (36000.76983 + t * 0.0003032)).rem_euclid(360.0);
+        let mean_anomaly = 357.52911 + t * (35999.05029 - 0.0001537 * t);
+        let eccentricity = 0.016708634 - t * (0.000042037 + 0.0000001267 * t);
+
+        let equation_of_center = mean_anomaly.to_radians().sin() * (1.914602 - t * (0.004817 + 0.000014 * t))
+            + (2.0 * mean_anomaly).to_radians().sin() * (0.019993 - 0.000101 * t)
+            + (3.0 * mean_anomaly).to_radians().sin() * 0.000289;
+        let omega = 125.04 - 1934.136 * t;
+        let apparent_longitude = mean_longitude + equation_of_center - 0.00569 - 0.00478 * omega.to_radians().sin();
+
+        let mean_obliquity = 23.0 + (26.0 + (21.448 - t * (46.815 + t * (0.00059 - t * 0.001813))) / 60.0) / 60.0;
+        let obliquity = mean_obliquity + 0.00256 * omega.to_radians().cos();
+        let declination = (obliquity.to_radians().sin() * apparent_longitude.to_radians().sin()).asin();
+
+        // Equation of time, in minutes
+        let y = (obliquity.to_radians() / 2.0).tan().powi(2);
+        let (l0, m) = (mean_longitude.to_radians(), mean_anomaly.to_radians());
+        let equation_of_time = 4.0 * (y * (2.0 * l0).sin() - 2.0 * eccentricity * m.sin()
+            + 4.0 * eccentricity * y * m.sin() * (2.0 * l0).cos()
+            - 0.5 * y * y * (4.0 * l0).sin()
+            - 1.25 * eccentricity * eccentricity * (2.0 * m).sin()).to_degrees();
+
+        // Hour angle of the sunrise, in degrees
+        let latitude = latitude.to_radians();
+        let cos_hour_angle = SUNRISE_ZENITH.to_radians().cos() / (latitude.cos() * declination.cos())
+            - latitude.tan() * declination.tan();
+        if cos_hour_angle > 1.0 {
+            return SunEvents::PolarNight;
+        } else if cos_hour_angle < -1.0 {
+            return SunEvents::PolarDay;
+        }
+
+        let hour_angle = cos_hour_angle.acos().to_degrees();
+        let solar_noon = 720.0 - 4.0 * longitude - equation_of_time;
+        let at_minutes = |minutes: f64| midnight + TimeDelta::seconds((minutes * 60.0).round() as i64);
+
+        SunEvents::Regular {
+            sunrise: at_minutes(solar_noon - 4.0 * hour_angle),
+            sunset: at_minutes(solar_noon + 4.0 * hour_angle),
+        }
+    }
+
+    /// Checks if the sun is down at the given time and location.
+    ///
+    /// # Arguments
+    /// * `now` - The time to check
+    /// * `latitude` - Latitude in degrees, positive to the north
+    /// * `longitude` - Longitude in degrees, positive to the east
+    pub fn is_night(now: DateTime<Utc>, latitude: f64, longitude: f64) -> bool {
+        match SunCalculator::sun_events(SunCalculator::solar_date(now, longitude), latitude, longitude) {
+            SunEvents::Regular { sunrise, sunset } => now < sunrise || now >= sunset,
+            SunEvents::PolarDay => false,
+            SunEvents::PolarNight => true,
+        }
+    }
+
+    /// Returns the next sunrise or sunset following the given time, if any in the coming days.
+    ///
+    /// # Arguments
+    /// * `now` - The reference time
+    /// * `latitude` - Latitude in degrees, positive to the north
+    /// * `longitude` - Longitude in degrees, positive to the east
+    pub fn next_event(now: DateTime<Utc>, latitude: f64, longitude: f64) -> Option<DateTime<Utc>> {
+        let solar_date = SunCalculator::solar_date(now, longitude);
+
+        (0..3)
+            .filter_map(|days| solar_date.checked_add_days(Days::new(days)))
+            .flat_map(|date| match SunCalculator::sun_events(date, latitude, longitude) {
+                SunEvents::Regular { sunrise, sunset } => vec![sunrise, sunset],
+                SunEvents::PolarDay | SunEvents::PolarNight => Vec::new(),
+            })
+            .find(|event| *event > now)
+    }
+
+    /// Returns the day at the given location, based on the mean solar time.
+    fn solar_date(now: DateTime<Utc>, longitude: f64) -> NaiveDate {
+        (now + TimeDelta::seconds((longitude * 240.0) as i64)).date_naive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, NaiveDate, TimeDelta, TimeZone, Utc};
+
+    use super::{SunCalculator, SunEvents};
+
+    /// Paris, France.
+    const PARIS: (f64, f64) = (48.8566, 2.3522);
+
+    /// Auckland, New Zealand, a few degrees west of the date line.
+    const AUCKLAND: (f64, f64) = (-36.8485, 174.7633);
+
+    /// Longyearbyen, Svalbard.
+    const LONGYEARBYEN: (f64, f64) = (78.2232, 15.6267);
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap()
+    }
+
+    fn assert_within_a_minute(actual: DateTime<Utc>, expected: DateTime<Utc>) {
+        assert!((actual - expected).num_seconds().abs() <= 60, "{} isn't {}", actual, expected);
+    }
+
+    #[test]
+    fn sun_events_of_known_cities() {
+        // Published times: 05:47 and 21:58 CEST in Paris, 07:16 and 16:32 EST in New York
+        match SunCalculator::sun_events(date(2025, 6, 21), PARIS.0, PARIS.1) {
+            SunEvents::Regular { sunrise, sunset } => {
+                assert_within_a_minute(sunrise, utc(2025, 6, 21, 3, 47));
+                assert_within_a_minute(sunset, utc(2025, 6, 21, 19, 58));
+            }
+            sun_events => panic!("{:?}", sun_events),
+        }
+
+        match SunCalculator::sun_events(date(2025, 12, 21), 40.7128, -74.0060) {
+            SunEvents::Regular { sunrise, sunset } => {
+                assert_within_a_minute(sunrise, utc(2025, 12, 21, 12, 16));
+                assert_within_a_minute(sunset, utc(2025, 12, 21, 21, 32));
+            }
+            sun_events => panic!("{:?}", sun_events),
+        }
+    }
+
+    #[test]
+    fn polar_day_and_night() {
+        let (latitude, longitude) = LONGYEARBYEN;
+        assert_eq!(SunCalculator::sun_events(date(2025, 6, 21), latitude, longitude), SunEvents::PolarDay);
+        assert_eq!(SunCalculator::sun_events(date(2025, 12, 21), latitude, longitude), SunEvents::PolarNight);
+
+        assert!(!SunCalculator::is_night(utc(2025, 6, 21, 0, 0), latitude, longitude));
+        assert!(SunCalculator::is_night(utc(2025, 12, 21, 12, 0), latitude, longitude));
+        assert_eq!(SunCalculator::next_event(utc(2025, 12, 21, 12, 0), latitude, longitude), None);
+    }
+
+    #[test]
+    fn is_night_around_sun_events() {
+        let (latitude, longitude) = PARIS;
+        let (sunrise, sunset) = match SunCalculator::sun_events(date(2025, 6, 21), latitude, longitude) {
+            SunEvents::Regular { sunrise, sunset } => (sunrise, sunset),
+            sun_events => panic!("{:?}", sun_events),
+        };
+
+        let one_minute = TimeDelta::minutes(1);
+        assert!(SunCalculator::is_night(sunrise - one_minute, latitude, longitude));
+        assert!(!SunCalculator::is_night(sunrise, latitude, longitude));
+        assert!(!SunCalculator::is_night(sunset - one_minute, latitude, longitude));
+        assert!(SunCalculator::is_night(sunset, latitude, longitude));
+    }
+
+    #[test]
+    fn next_event_rolls_over_to_the_next_day() {
+        let (latitude, longitude) = PARIS;
+
+        let next_event = SunCalculator::next_event(utc(2025, 6, 21, 12, 0), latitude, longitude).unwrap();
+        assert_within_a_minute(next_event, utc(2025, 6, 21, 19, 58));
+
+        // After sunset: the sunrise of the next day
+        let next_event = SunCalculator::next_event(utc(2025, 6, 21, 20, 0), latitude, longitude).unwrap();
+        assert_within_a_minute(next_event, utc(2025, 6, 22, 3, 47));
+    }
+
+    #[test]
+    fn solar_date_near_the_date_line() {
+        // 13:00 UTC is past midnight in Auckland, 10:00 UTC is still the previous evening on the other side
+        assert_eq!(SunCalculator::solar_date(utc(2025, 1, 14, 13, 0), AUCKLAND.1), date(2025, 1, 15));
+        assert_eq!(SunCalculator::solar_date(utc(2025, 1, 14, 11, 0), AUCKLAND.1), date(2025, 1, 14));
+        assert_eq!(SunCalculator::solar_date(utc(2025, 1, 15, 10, 0), -179.5), date(2025, 1, 14));
+        assert_eq!(SunCalculator::solar_date(utc(2025, 1, 15, 13, 0), -179.5), date(2025, 1, 15));
+
+        // The events of the local day are used, the sunrise in Auckland is the previous day in UTC
+        let (latitude, longitude) = AUCKLAND;
+        assert!(!SunCalculator::is_night(utc(2025, 1, 14, 17, 30), latitude, longitude));
+        assert!(SunCalculator::is_night(utc(2025, 1, 15, 8, 0), latitude, longitude));
+    }
+}
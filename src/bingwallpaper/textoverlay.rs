@@ -1,55 +1,624 @@
-use crate::bingwallpaper::BingWallpaperConfiguration;
-use ab_glyph::{FontRef, PxScale};
-use image::{ImageReader, Rgb, RgbImage};
-use imageproc::drawing::draw_text;
+use crate::bingwallpaper::configuration::{
+    TextOverlayAlignment, TextOverlayAnchor, TextOverlayColor, TextOverlayConfiguration, TextOverlayLength,
+};
+use crate::bingwallpaper::fontchain::ShapedLine;
+use crate::bingwallpaper::overlaytemplate::DEFAULT_TEMPLATE;
+use crate::bingwallpaper::{FontChain, OverlayTemplate, WallpaperMetadata};
+use ab_glyph::{point, ScaleFont};
+use image::{Rgb, RgbImage};
+
+/// Default font size (in pixels).
+const DEFAULT_FONT_SIZE: f32 = 30.0;
+
+/// Space (in pixels) between two lines of text.
+const LINE_SPACING: f32 = 5.0;
+
+/// Default margin (in pixels) between the text block and the image borders.
+const DEFAULT_MARGIN: TextOverlayLength = TextOverlayLength::Pixels(60);
+
+/// Default minimum contrast ratio between text and background when text color is automatic (WCAG AA).
+const DEFAULT_MIN_CONTRAST_RATIO: f32 = 4.5;
+
+/// Space (in pixels) between the text and the border of the background box.
+const BACKGROUND_BOX_PADDING: i32 = 12;
+
+/// Maximum opacity of the background box.
+const MAX_BACKGROUND_BOX_OPACITY: f32 = 0.85;
+
+/// Locations evaluated when the anchor is `AUTO`.
+const AUTO_ANCHOR_CANDIDATES: [TextOverlayAnchor; 6] = [
+    TextOverlayAnchor::TopLeft,
+    TextOverlayAnchor::TopCenter,
+    TextOverlayAnchor::TopRight,
+    TextOverlayAnchor::BottomLeft,
+    TextOverlayAnchor::BottomCenter,
+    TextOverlayAnchor::BottomRight,
+];
+
+/// Locations evaluated when the anchor is `AUTO_BOTTOM`.
+const AUTO_BOTTOM_ANCHOR_CANDIDATES: [TextOverlayAnchor; 3] = [
+    TextOverlayAnchor::BottomLeft,
+    TextOverlayAnchor::BottomCenter,
+    TextOverlayAnchor::BottomRight,
+];
+
+/// Font settings of a text overlay: font files, font families and font size (as bits).
+type FontSettings = (Vec<String>, Vec<String>, u32);
 
 /// Text overlay
 pub struct TextOverlay {}
 
+/// Fonts used by the text overlays of a render, loaded once per font settings (files, families and size),
+/// so that images drawn with the same settings share the fonts and the fontconfig lookups.
+pub struct TextOverlayFonts {
+    font_chains: Vec<(FontSettings, FontChain)>,
+}
+
+/// Luminance and detail statistics of an area of the image.
+struct RegionStatistics {
+    bounds: (u32, u32, u32, u32),
+    luminance_mean: f32,
+    luminance_deviation: f32,
+    luminance_low: f32,
+    luminance_high: f32,
+    detail: f32,
+}
+
+impl RegionStatistics {
+    /// Returns how busy the area is: the higher, the harder it is to read text on it.
+    fn busyness(&self) -> f32 {
+        self.detail + self.luminance_deviation * 0.5
+    }
+
+    /// Returns the worst contrast ratio (WCAG definition) between a text of the
+    /// given luminance and the area. Brightest parts are used for light text,
+    /// darkest parts for dark text.
+    fn contrast_ratio(&self, text_luminance: f32) -> f32 {
+        let background_luminance = if text_luminance >= 0.5 { self.luminance_high } else { self.luminance_low };
+        let (lighter, darker) = if text_luminance >= background_luminance {
+            (text_luminance, background_luminance)
+        } else {
+            (background_luminance, text_luminance)
+        };
+
+        (lighter + 0.05) / (darker + 0.05)
+    }
+}
+
+impl TextOverlayFonts {
+    /// Creates a new instance, without font loaded.
+    #[must_use]
+    pub fn new() -> TextOverlayFonts {
+        TextOverlayFonts { font_chains: Vec::new() }
+    }
+
+    /// Returns the fonts of a text overlay configuration, loading them the first time.
+    fn font_chain(&mut self, overlay_configuration: &TextOverlayConfiguration) -> &mut FontChain {
+        let font_files = overlay_configuration.font_files.clone().unwrap_or_default();
+        let font_families = overlay_configuration.font_families.clone().unwrap_or_default();
+        let font_size = overlay_configuration.font_size.unwrap_or(DEFAULT_FONT_SIZE);
+        let font_settings: FontSettings = (font_files, font_families, font_size.to_bits());
+
+        let idx = match self.font_chains.iter().position(|(settings, _)| *settings == font_settings) {
+            Some(idx) => idx,
+            None => {
+                let font_chain = FontChain::new(&font_settings.0, &font_settings.1, font_size);
+                self.font_chains.push((font_settings, font_chain));
+                self.font_chains.len() - 1
+            }
+        };
+
+        &mut self.font_chains[idx].1
+    }
+}
+
+impl Default for TextOverlayFonts {
+    fn default() -> TextOverlayFonts {
+        TextOverlayFonts::new()
+    }
+}
+
 impl TextOverlay {
     /// Applies text overlay.
     ///
     /// # Arguments
-    /// * `configuration - The Bing Wallpaper configuration to use
-    /// * `line1` - First line of text
-    /// * `line2` - Second line of text
+    /// * `image` - The image to draw on
+    /// * `overlay_configuration` - The text overlay configuration to use
+    /// * `metadata` - The wallpaper information used to render the template
+    /// * `fonts` - The fonts of the render, shared by its text overlays
     ///
     /// # Examples
     ///
     /// ```
-    /// use textoverlay::TextOverlay;
+    /// use textoverlay::{TextOverlay, TextOverlayFonts};
     ///
-    /// TextOverlay::apply_overlay(configuration, "Title", "Description")
+    /// let mut fonts = TextOverlayFonts::new();
+    /// TextOverlay::apply_overlay(&mut image, overlay_configuration, &metadata, &mut fonts)
     /// ```
-    pub fn apply_overlay(configuration: &BingWallpaperConfiguration, line1: String, line2: String) {
-        if configuration.text_overlay_position.is_none() {
+    pub fn apply_overlay(image: &mut RgbImage,
+                         overlay_configuration: &TextOverlayConfiguration,
+                         metadata: &WallpaperMetadata,
+                         fonts: &mut TextOverlayFonts) {
+        let font_chain = fonts.font_chain(overlay_configuration);
+        let (image_width, image_height) = image.dimensions();
+
+        // Computes the available width and wraps lines accordingly
+        let margin_x = overlay_configuration.margin_x.unwrap_or(DEFAULT_MARGIN).resolve(image_width);
+        let margin_y = overlay_configuration.margin_y.unwrap_or(DEFAULT_MARGIN).resolve(image_height);
+        let max_line_width = overlay_configuration.max_line_width
+            .map(|max_line_width| max_line_width.resolve(image_width))
+            .unwrap_or(image_width as i32 - 2 * margin_x)
+            .max(1) as f32;
+
+        let template = overlay_configuration.template.as_deref().unwrap_or(DEFAULT_TEMPLATE);
+        let mut lines: Vec<ShapedLine> = Vec::new();
+        for text in OverlayTemplate::render(template, metadata) {
+            for wrapped_text in TextOverlay::wrap_text(font_chain, &text, max_line_width) {
+                lines.push(font_chain.shape_line(&wrapped_text));
+            }
+        }
+
+        if lines.is_empty() {
             return;
         }
 
-        let font = FontRef::try_from_slice(include_bytes!("../../res/font/Ubuntu-Regular.ttf")).unwrap();
-        let font_scale = PxScale::from(30.0);
-        let (mut pos_x, mut pos_y) = match configuration.text_overlay_position.clone().unwrap().to_uppercase().as_str() {
-            "TOP_LEFT" => (60i32, 60i32),
-            "BOTTOM_LEFT" => (60i32,
-                              configuration.image_dimension_height as i32 - (font_scale.x as i32 * 2) - 60 - 5),
-            "TOP_RIGHT" => (configuration.image_dimension_width as i32 - (12i32 * line2.len() as i32) - 55,
-                            60i32),
-            "BOTTOM_RIGHT" => (configuration.image_dimension_width as i32 - (12i32 * line2.len() as i32) - 55,
-                               configuration.image_dimension_height as i32 - (font_scale.x as i32 * 2) - 60 - 5),
-            _ => (60i32, 60i32),
+        // Computes text block position
+        let ascent = font_chain.ascent(&lines);
+        let line_height = font_chain.height(&lines) + LINE_SPACING;
+        let block_width = lines.iter().map(|line| line.width).fold(0.0, f32::max).ceil() as i32;
+        let block_height = (line_height * lines.len() as f32 - LINE_SPACING).ceil() as i32;
+
+        // Resolves the text block location
+        let block_margin = (margin_x, margin_y);
+        let block_size = (block_width, block_height);
+        let anchor = match overlay_configuration.anchor {
+            TextOverlayAnchor::Auto =>
+                TextOverlay::find_calmest_anchor(image, &AUTO_ANCHOR_CANDIDATES, block_margin, block_size),
+            TextOverlayAnchor::AutoBottom =>
+                TextOverlay::find_calmest_anchor(image, &AUTO_BOTTOM_ANCHOR_CANDIDATES, block_margin, block_size),
+            anchor => anchor,
+        };
+
+        let (mut pos_x, mut pos_y) = TextOverlay::block_position(anchor, image.dimensions(), block_margin, block_size);
+        pos_x += overlay_configuration.offset_x.unwrap_or(0);
+        pos_y += overlay_configuration.offset_y.unwrap_or(0);
+
+        // Keeps the text block inside the image
+        pos_x = pos_x.clamp(0, (image_width as i32 - block_width).max(0));
+        pos_y = pos_y.clamp(0, (image_height as i32 - block_height).max(0));
+
+        // Resolves text color and, if contrast is too low, draws a background box
+        let statistics = TextOverlay::region_statistics(image, pos_x, pos_y, block_width, block_height);
+        let (color, contrast_ratio) = match overlay_configuration.color.unwrap_or(TextOverlayColor::Rgb(255, 255, 255)) {
+            TextOverlayColor::Rgb(red, green, blue) => {
+                let color = Rgb([red, green, blue]);
+                (color, statistics.contrast_ratio(TextOverlay::relative_luminance(color)))
+            }
+            TextOverlayColor::Auto => {
+                let light_contrast_ratio = statistics.contrast_ratio(1.0);
+                let dark_contrast_ratio = statistics.contrast_ratio(0.0);
+                if light_contrast_ratio >= dark_contrast_ratio {
+                    (Rgb([255u8, 255u8, 255u8]), light_contrast_ratio)
+                } else {
+                    (Rgb([0u8, 0u8, 0u8]), dark_contrast_ratio)
+                }
+            }
+        };
+
+        let min_contrast_ratio = match overlay_configuration.color {
+            Some(TextOverlayColor::Auto) =>
+                Some(overlay_configuration.min_contrast_ratio.unwrap_or(DEFAULT_MIN_CONTRAST_RATIO)),
+            _ => overlay_configuration.min_contrast_ratio,
+        };
+
+        let mut box_opacity = 0.0;
+        if let Some(min_contrast_ratio) = min_contrast_ratio {
+            if contrast_ratio < min_contrast_ratio {
+                box_opacity = TextOverlay::draw_background_box(image, &statistics, color, min_contrast_ratio);
+            }
+        }
+
+        let is_auto_color = overlay_configuration.color == Some(TextOverlayColor::Auto);
+        let is_auto_anchor = matches!(overlay_configuration.anchor, TextOverlayAnchor::Auto | TextOverlayAnchor::AutoBottom);
+        if is_auto_anchor || is_auto_color {
+            println!(
+                "Text overlay: {:?} at {}x{} (luminance {:.2}, detail {:.3}), color {}, contrast {:.1}:1, box opacity {:.2}",
+                anchor,
+                pos_x,
+                pos_y,
+                statistics.luminance_mean,
+                statistics.detail,
+                String::from(TextOverlayColor::Rgb(color.0[0], color.0[1], color.0[2])),
+                contrast_ratio,
+                box_opacity);
+        }
+
+        // Draws each line according to the requested alignment
+        let alignment = overlay_configuration.alignment.unwrap_or(anchor.default_alignment());
+        for (idx, line) in lines.iter().enumerate() {
+            let line_x = pos_x as f32 + match alignment {
+                TextOverlayAlignment::Left => 0.0,
+                TextOverlayAlignment::Center => (block_width as f32 - line.width) / 2.0,
+                TextOverlayAlignment::Right => block_width as f32 - line.width,
+            };
+            let line_y = pos_y as f32 + line_height * idx as f32 + ascent;
+
+            TextOverlay::draw_line(image, font_chain, line, line_x.round(), line_y.round(), color);
+        }
+    }
+
+    /// Computes the position of the text block for the given anchor.
+    ///
+    /// # Arguments
+    /// * `anchor` - The text block anchor
+    /// * `image_size` - Width and height of the image
+    /// * `block_margin` - Horizontal and vertical margins
+    /// * `block_size` - Width and height of the text block
+    fn block_position(anchor: TextOverlayAnchor,
+                      image_size: (u32, u32),
+                      block_margin: (i32, i32),
+                      block_size: (i32, i32)) -> (i32, i32) {
+        let (image_width, image_height) = (image_size.0 as i32, image_size.1 as i32);
+        let (margin_x, margin_y) = block_margin;
+        let (block_width, block_height) = block_size;
+
+        let pos_x = match anchor {
+            TextOverlayAnchor::Auto
+            | TextOverlayAnchor::AutoBottom
+            | TextOverlayAnchor::TopLeft
+            | TextOverlayAnchor::MiddleLeft
+            | TextOverlayAnchor::BottomLeft => margin_x,
+            TextOverlayAnchor::TopCenter | TextOverlayAnchor::Center | TextOverlayAnchor::BottomCenter =>
+                (image_width - block_width) / 2,
+            TextOverlayAnchor::TopRight | TextOverlayAnchor::MiddleRight | TextOverlayAnchor::BottomRight =>
+                image_width - margin_x - block_width,
+        };
+        let pos_y = match anchor {
+            TextOverlayAnchor::Auto
+            | TextOverlayAnchor::TopLeft
+            | TextOverlayAnchor::TopCenter
+            | TextOverlayAnchor::TopRight => margin_y,
+            TextOverlayAnchor::MiddleLeft | TextOverlayAnchor::Center | TextOverlayAnchor::MiddleRight =>
+                (image_height - block_height) / 2,
+            TextOverlayAnchor::AutoBottom
+            | TextOverlayAnchor::BottomLeft
+            | TextOverlayAnchor::BottomCenter
+            | TextOverlayAnchor::BottomRight =>
+                image_height - margin_y - block_height,
+        };
+
+        (pos_x, pos_y)
+    }
+
+    /// Finds, among the candidates, the location where the text block covers the least busy area of the image.
+    fn find_calmest_anchor(image: &RgbImage,
+                           candidates: &[TextOverlayAnchor],
+                           block_margin: (i32, i32),
+                           block_size: (i32, i32)) -> TextOverlayAnchor {
+        let mut calmest_anchor = candidates[0];
+        let mut calmest_score = f32::MAX;
+
+        for &anchor in candidates {
+            let (pos_x, pos_y) = TextOverlay::block_position(anchor, image.dimensions(), block_margin, block_size);
+            let statistics = TextOverlay::region_statistics(image, pos_x, pos_y, block_size.0, block_size.1);
+            let score = statistics.busyness();
+
+            if score < calmest_score {
+                calmest_anchor = anchor;
+                calmest_score = score;
+            }
+        }
+
+        calmest_anchor
+    }
+
+    /// Computes luminance and detail statistics of an area of the image (background box padding included).
+    fn region_statistics(image: &RgbImage, x: i32, y: i32, width: i32, height: i32) -> RegionStatistics {
+        let (image_width, image_height) = image.dimensions();
+        let min_x = (x - BACKGROUND_BOX_PADDING).clamp(0, image_width as i32 - 1) as u32;
+        let min_y = (y - BACKGROUND_BOX_PADDING).clamp(0, image_height as i32 - 1) as u32;
+        let max_x = (x + width + BACKGROUND_BOX_PADDING).clamp(min_x as i32 + 1, image_width as i32) as u32;
+        let max_y = (y + height + BACKGROUND_BOX_PADDING).clamp(min_y as i32 + 1, image_height as i32) as u32;
+
+        // Samples the area, only a subset of pixels is needed to get accurate statistics
+        let step = ((max_x - min_x) * (max_y - min_y) / 40_000).max(1).isqrt().max(1);
+        let mut luminances: Vec<f32> = Vec::new();
+        let mut detail_sum = 0.0f32;
+
+        for py in (min_y..max_y).step_by(step as usize) {
+            for px in (min_x..max_x).step_by(step as usize) {
+                let luminance = TextOverlay::relative_luminance(*image.get_pixel(px, py));
+                let right = TextOverlay::relative_luminance(*image.get_pixel((px + 1).min(max_x - 1), py));
+                let below = TextOverlay::relative_luminance(*image.get_pixel(px, (py + 1).min(max_y - 1)));
+
+                detail_sum += (luminance - right).abs() + (luminance - below).abs();
+                luminances.push(luminance);
+            }
+        }
+
+        luminances.sort_by(f32::total_cmp);
+        let sample_count = luminances.len() as f32;
+        let luminance_mean = luminances.iter().sum::<f32>() / sample_count;
+        let luminance_variance = luminances.iter().map(|value| (value - luminance_mean).powi(2)).sum::<f32>() / sample_count;
+
+        RegionStatistics {
+            bounds: (min_x, min_y, max_x, max_y),
+            luminance_mean,
+            luminance_deviation: luminance_variance.sqrt(),
+            luminance_low: luminances[(luminances.len() - 1) / 10],
+            luminance_high: luminances[(luminances.len() - 1) * 9 / 10],
+            detail: detail_sum / sample_count,
+        }
+    }
+
+    /// Draws a translucent box behind the text to reach the requested contrast ratio.
+    ///
+    /// Returns the opacity of the box.
+    fn draw_background_box(image: &mut RgbImage,
+                           statistics: &RegionStatistics,
+                           text_color: Rgb<u8>,
+                           min_contrast_ratio: f32) -> f32 {
+        let text_luminance = TextOverlay::relative_luminance(text_color);
+        let is_light_text = text_luminance >= 0.5;
+
+        // Luminance the background must reach, then the opacity needed to get there
+        let opacity = if is_light_text {
+            let target = (text_luminance + 0.05) / min_contrast_ratio - 0.05;
+            1.0 - target.max(0.0) / statistics.luminance_high.max(f32::EPSILON)
+        } else {
+            let target = min_contrast_ratio * (text_luminance + 0.05) - 0.05;
+            (target.min(1.0) - statistics.luminance_low) / (1.0 - statistics.luminance_low).max(f32::EPSILON)
+        }.clamp(0.0, MAX_BACKGROUND_BOX_OPACITY);
+
+        let box_color = if is_light_text { 0.0 } else { 1.0 };
+        let (min_x, min_y, max_x, max_y) = statistics.bounds;
+
+        for py in min_y..max_y {
+            for px in min_x..max_x {
+                let pixel = image.get_pixel_mut(px, py);
+                for channel in 0..3 {
+                    let linear = TextOverlay::srgb_to_linear(pixel.0[channel]);
+                    pixel.0[channel] = TextOverlay::linear_to_srgb(linear * (1.0 - opacity) + box_color * opacity);
+                }
+            }
+        }
+
+        opacity
+    }
+
+    /// Returns the relative luminance (WCAG definition) of a color.
+    pub(crate) fn relative_luminance(color: Rgb<u8>) -> f32 {
+        0.2126 * TextOverlay::srgb_to_linear(color.0[0])
+            + 0.7152 * TextOverlay::srgb_to_linear(color.0[1])
+            + 0.0722 * TextOverlay::srgb_to_linear(color.0[2])
+    }
+
+    /// Converts a sRGB channel value into linear light.
+    fn srgb_to_linear(value: u8) -> f32 {
+        let value = value as f32 / 255.0;
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    /// Converts linear light into a sRGB channel value.
+    fn linear_to_srgb(value: f32) -> u8 {
+        let value = if value <= 0.0031308 {
+            value * 12.92
+        } else {
+            1.055 * value.powf(1.0 / 2.4) - 0.055
         };
 
-        if configuration.text_overlay_position_offset_x.is_some() {
-            pos_x += configuration.text_overlay_position_offset_x.unwrap() as i32
+        (value.clamp(0.0, 1.0) * 255.0).round() as u8
+    }
+
+    /// Splits text into lines not exceeding the given width. Words are kept
+    /// together unless a single word is wider than the allowed width.
+    fn wrap_text(font_chain: &mut FontChain, text: &str, max_width: f32) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        let mut current_line = String::new();
+
+        for word in text.split_whitespace() {
+            let candidate = if current_line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", current_line, word)
+            };
+
+            if font_chain.shape_line(&candidate).width <= max_width {
+                current_line = candidate;
+                continue;
+            }
+
+            if !current_line.is_empty() {
+                lines.push(current_line);
+                current_line = String::new();
+            }
+
+            // Word is too wide to fit on a single line, breaks it on characters
+            for c in word.chars() {
+                current_line.push(c);
+                if current_line.chars().count() > 1 && font_chain.shape_line(&current_line).width > max_width {
+                    current_line.pop();
+                    lines.push(current_line);
+                    current_line = c.to_string();
+                }
+            }
+        }
+
+        if !current_line.is_empty() {
+            lines.push(current_line);
         }
-        if configuration.text_overlay_position_offset_y.is_some() {
-            pos_y += configuration.text_overlay_position_offset_y.unwrap() as i32;
+
+        lines
+    }
+
+    /// Draws a laid out line of text.
+    ///
+    /// # Arguments
+    /// * `image` - The image to draw on
+    /// * `font_chain` - The fonts used to shape the line
+    /// * `line` - The line to draw
+    /// * `x` - Position of the line start on the X-axis
+    /// * `baseline_y` - Position of the line baseline on the Y-axis
+    /// * `color` - The text color
+    fn draw_line(image: &mut RgbImage,
+                 font_chain: &FontChain,
+                 line: &ShapedLine,
+                 x: f32,
+                 baseline_y: f32,
+                 color: Rgb<u8>) {
+        let (image_width, image_height) = image.dimensions();
+
+        for shaped_glyph in &line.glyphs {
+            let mut glyph = shaped_glyph.glyph.clone();
+            glyph.position = point(glyph.position.x + x, glyph.position.y + baseline_y);
+
+            if let Some(outlined_glyph) = font_chain.scaled_font(shaped_glyph.font_index).outline_glyph(glyph) {
+                let bounds = outlined_glyph.px_bounds();
+                outlined_glyph.draw(|gx, gy, coverage| {
+                    let px = gx as i32 + bounds.min.x as i32;
+                    let py = gy as i32 + bounds.min.y as i32;
+
+                    if px >= 0 && py >= 0 && (px as u32) < image_width && (py as u32) < image_height {
+                        let coverage = coverage.clamp(0.0, 1.0);
+                        let pixel = image.get_pixel_mut(px as u32, py as u32);
+                        for channel in 0..3 {
+                            pixel.0[channel] = (pixel.0[channel] as f32 * (1.0 - coverage)
+                                + color.0[channel] as f32 * coverage).round() as u8;
+                        }
+                    }
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, RgbImage};
+
+    use super::{RegionStatistics, TextOverlay, TextOverlayFonts, AUTO_ANCHOR_CANDIDATES, AUTO_BOTTOM_ANCHOR_CANDIDATES};
+    use crate::bingwallpaper::configuration::{TextOverlayAnchor, TextOverlayConfiguration};
+    use crate::bingwallpaper::FontChain;
+
+    #[test]
+    fn wrap_text() {
+        let mut font_chain = FontChain::new(&[], &[], 30.0);
+        let width = |font_chain: &mut FontChain, text: &str| font_chain.shape_line(text).width;
+
+        // Words are kept together
+        let max_width = width(&mut font_chain, "Lake Louise,") + 1.0;
+        assert_eq!(
+            TextOverlay::wrap_text(&mut font_chain, "Lake Louise, Banff  National Park", max_width),
+            ["Lake Louise,", "Banff", "National", "Park"]);
+        let max_width = width(&mut font_chain, "Banff National Park") + 1.0;
+        assert_eq!(
+            TextOverlay::wrap_text(&mut font_chain, " Lake Louise, Banff National Park ", max_width),
+            ["Lake Louise, Banff", "National Park"]);
+
+        // A word wider than the line is broken on characters
+        let max_width = width(&mut font_chain, "Lake") + 1.0;
+        let lines = TextOverlay::wrap_text(&mut font_chain, "Lake Minnewanka", max_width);
+        assert_eq!(lines[0], "Lake");
+        assert!(lines.len() > 2, "{:?}", lines);
+        assert_eq!(lines[1..].concat(), "Minnewanka");
+        assert!(lines.iter().all(|line| width(&mut font_chain, line) <= max_width), "{:?}", lines);
+
+        // At least one character per line, even when it doesn't fit
+        assert_eq!(TextOverlay::wrap_text(&mut font_chain, "Lake", 1.0), ["L", "a", "k", "e"]);
+        assert!(TextOverlay::wrap_text(&mut font_chain, " ", 100.0).is_empty());
+    }
+
+    #[test]
+    fn block_position() {
+        // 1000x500 image, 10x20 margins, 100x50 block
+        let anchor_positions = [
+            (TextOverlayAnchor::TopLeft, (10, 20)),
+            (TextOverlayAnchor::TopCenter, (450, 20)),
+            (TextOverlayAnchor::TopRight, (890, 20)),
+            (TextOverlayAnchor::MiddleLeft, (10, 225)),
+            (TextOverlayAnchor::Center, (450, 225)),
+            (TextOverlayAnchor::MiddleRight, (890, 225)),
+            (TextOverlayAnchor::BottomLeft, (10, 430)),
+            (TextOverlayAnchor::BottomCenter, (450, 430)),
+            (TextOverlayAnchor::BottomRight, (890, 430)),
+            (TextOverlayAnchor::Auto, (10, 20)),
+            (TextOverlayAnchor::AutoBottom, (10, 430)),
+        ];
+
+        for (anchor, position) in anchor_positions {
+            assert_eq!(TextOverlay::block_position(anchor, (1000, 500), (10, 20), (100, 50)), position, "{:?}", anchor);
         }
+    }
+
+    #[test]
+    fn find_calmest_anchor() {
+        // Noisy image, except a flat area
+        let noisy_image = |flat_x: u32, flat_y: u32| RgbImage::from_fn(300, 200, |x, y| {
+            if x >= flat_x && x < flat_x + 100 && y >= flat_y && y < flat_y + 100 {
+                Rgb([40, 80, 120])
+            } else if (x + y) % 2 == 0 {
+                Rgb([255, 255, 255])
+            } else {
+                Rgb([0, 0, 0])
+            }
+        });
+
+        // Blocks of 60x40, at 20 pixels from the borders
+        let image = noisy_image(200, 0);
+        let anchor = TextOverlay::find_calmest_anchor(&image, &AUTO_ANCHOR_CANDIDATES, (20, 20), (60, 40));
+        assert_eq!(anchor, TextOverlayAnchor::TopRight);
+
+        let image = noisy_image(100, 100);
+        let anchor = TextOverlay::find_calmest_anchor(&image, &AUTO_ANCHOR_CANDIDATES, (20, 20), (60, 40));
+        assert_eq!(anchor, TextOverlayAnchor::BottomCenter);
+
+        // Only the bottom of the image when requested
+        let image = noisy_image(0, 0);
+        let anchor = TextOverlay::find_calmest_anchor(&image, &AUTO_ANCHOR_CANDIDATES, (20, 20), (60, 40));
+        assert_eq!(anchor, TextOverlayAnchor::TopLeft);
+        let anchor = TextOverlay::find_calmest_anchor(&image, &AUTO_BOTTOM_ANCHOR_CANDIDATES, (20, 20), (60, 40));
+        assert_eq!(anchor, TextOverlayAnchor::BottomLeft);
+    }
+
+    #[test]
+    fn contrast_ratio() {
+        assert_eq!(TextOverlay::relative_luminance(Rgb([0, 0, 0])), 0.0);
+        assert!((TextOverlay::relative_luminance(Rgb([255, 255, 255])) - 1.0).abs() < 1e-6);
+
+        // WCAG reference values: 21:1 for white on black, 4.48:1 for #777777 on white
+        let statistics = |luminance: f32| RegionStatistics {
+            bounds: (0, 0, 1, 1),
+            luminance_mean: luminance,
+            luminance_deviation: 0.0,
+            luminance_low: luminance,
+            luminance_high: luminance,
+            detail: 0.0,
+        };
+        assert!((statistics(0.0).contrast_ratio(1.0) - 21.0).abs() < 1e-4);
+        assert!((statistics(1.0).contrast_ratio(0.0) - 21.0).abs() < 1e-4);
+        let gray_luminance = TextOverlay::relative_luminance(Rgb([0x77, 0x77, 0x77]));
+        assert!((statistics(1.0).contrast_ratio(gray_luminance) - 4.48).abs() < 0.01);
+        assert!((statistics(0.0).contrast_ratio(0.0) - 1.0).abs() < 1e-6);
+
+        // The brightest parts for light text, the darkest ones for dark text
+        let statistics = RegionStatistics { luminance_low: 0.0, luminance_high: 1.0, ..statistics(0.5) };
+        assert!((statistics.contrast_ratio(1.0) - 1.0).abs() < 1e-6);
+        assert!((statistics.contrast_ratio(0.0) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn fonts_are_loaded_once_per_settings() {
+        let mut fonts = TextOverlayFonts::new();
+        let overlay_configuration = TextOverlayConfiguration::default();
+        let large_overlay_configuration = TextOverlayConfiguration { font_size: Some(60.0), ..Default::default() };
 
-        let image = ImageReader::open(&configuration.target_filename).unwrap().decode().unwrap();
-        let image = RgbImage::from(image);
-        let image = draw_text(&image, Rgb([255u8, 255u8, 255u8]), pos_x, pos_y, font_scale, &font, &line1);
-        let image = draw_text(&image, Rgb([255u8, 255u8, 255u8]), pos_x, pos_y + font_scale.x as i32 + 5, font_scale, &font, &line2);
-        image.save(&configuration.target_filename).unwrap();
+        fonts.font_chain(&overlay_configuration);
+        fonts.font_chain(&large_overlay_configuration);
+        fonts.font_chain(&overlay_configuration);
+        assert_eq!(fonts.font_chains.len(), 2);
     }
 }
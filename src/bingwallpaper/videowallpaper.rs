@@ -0,0 +1,156 @@
+use std::env;
+use std::fs;
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::bingwallpaper::configuration::{VideoBackend, VideoConfiguration, WallpaperFitMode};
+use crate::bingwallpaper::{BackgroundProcess, CommandRunner};
+
+/// The video player started by this instance, stopped when the wallpaper changes again.
+static VIDEO_PLAYER_PROCESS: BackgroundProcess = BackgroundProcess::new("video", &["mpvpaper", "xwinwrap"]);
+
+/// The video played by the last successful `exec_apply_video`, which manages its own player.
+static VIDEO_COMMAND_KEY: Mutex<Option<String>> = Mutex::new(None);
+
+/// Plays the video wallpaper over the still one, with mpvpaper (Wayland), xwinwrap and mpv (X11), or a command.
+///
+/// Players display the video as long as they run: a new process is started when the video changes, then the previous
+/// one is stopped. `exec_apply_video` only runs when the video changes too.
+pub struct VideoWallpaper {
+    video_configuration: VideoConfiguration,
+    fit_mode: Option<WallpaperFitMode>,
+}
+
+impl VideoWallpaper {
+    /// Creates a new instance.
+    ///
+    /// # Arguments
+    /// * `video_configuration` - The video configuration to use
+    /// * `fit_mode` - How the video fills the screen, `ZOOM` if not specified
+    pub fn new(video_configuration: VideoConfiguration, fit_mode: Option<WallpaperFitMode>) -> VideoWallpaper {
+        VideoWallpaper { video_configuration, fit_mode }
+    }
+
+    /// Plays the video, unless it is already playing. Returns `false` when no animated wallpaper backend is available.
+    ///
+    /// # Arguments
+    /// * `video_filename` - The location of the video
+    /// * `variables` - The variables of `exec_apply_video`, by name (ie: "video_filename"), and their value
+    /// * `timeout` - The maximum time to wait for `exec_apply_video`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use videowallpaper::VideoWallpaper;
+    ///
+    /// let video_wallpaper = VideoWallpaper::new(video_configuration, None);
+    /// if !video_wallpaper.play("/home/user/.bingwallpaper-video.mp4", &variables, None)? {
+    ///     VideoWallpaper::stop();
+    /// }
+    /// ```
+    pub fn play(&self,
+                video_filename: &str,
+                variables: &[(&str, String)],
+                timeout: Option<Duration>) -> Result<bool, String> {
+        let backend = match self.backend() {
+            None => return Ok(false),
+            Some(backend) => backend,
+        };
+
+        // The same video keeps playing, restarting it would be noticeable
+        let modified = fs::metadata(video_filename).and_then(|metadata| metadata.modified()).ok();
+        let video_key = format!("{:?} {} {:?}", backend, video_filename, modified);
+
+        let mut command = match backend {
+            VideoBackend::Mpvpaper => {
+                let mut command = Command::new("mpvpaper");
+                command.args(["-o", &self.mpv_options("").join(" "), "ALL", video_filename]);
+                command
+            }
+            VideoBackend::Xwinwrap => {
+                // xwinwrap replaces "WID" with the window embedding the player
+                let mut command = Command::new("xwinwrap");
+                command.args(["-fs", "-ov", "-ni", "-s", "-nf", "-b", "-un", "--", "mpv", "-wid", "WID"]);
+                command.args(self.mpv_options("--")).arg(video_filename);
+                command
+            }
+            VideoBackend::Command => {
+                let exec_apply_video = match &self.video_configuration.exec_apply_video {
+                    None => return Err("Can't play video wallpaper: `exec_apply_video` is not configured".to_string()),
+                    Some(exec_apply_video) => exec_apply_video,
+                };
+
+                let mut video_command_key = match VIDEO_COMMAND_KEY.lock() {
+                    Err(error) => return Err(format!("Can't access video command state: {:?}", error)),
+                    Ok(video_command_key) => video_command_key,
+                };
+                if video_command_key.as_deref() == Some(video_key.as_str()) {
+                    return Ok(true);
+                }
+
+                // The command manages its own player
+                VIDEO_PLAYER_PROCESS.stop();
+                CommandRunner::run(exec_apply_video, variables, timeout)?;
+                *video_command_key = Some(video_key);
+
+                return Ok(true);
+            }
+        };
+
+        if !VIDEO_PLAYER_PROCESS.is_running(&video_key) {
+            VIDEO_PLAYER_PROCESS.start(&mut command, video_key)?;
+        }
+
+        Ok(true)
+    }
+
+    /// Stops the video player, if running, so that the still wallpaper is visible again.
+    pub fn stop() {
+        VIDEO_PLAYER_PROCESS.stop();
+        if let Ok(mut video_command_key) = VIDEO_COMMAND_KEY.lock() {
+            *video_command_key = None;
+        }
+    }
+
+    /// Returns the configured backend, else the one available in the session: mpvpaper on Wayland, xwinwrap on X11.
+    fn backend(&self) -> Option<VideoBackend> {
+        if let Some(backend) = self.video_configuration.backend {
+            return Some(backend);
+        }
+
+        if self.video_configuration.exec_apply_video.is_some() {
+            Some(VideoBackend::Command)
+        } else if env::var_os("WAYLAND_DISPLAY").is_some() && VideoWallpaper::is_installed("mpvpaper") {
+            Some(VideoBackend::Mpvpaper)
+        } else if env::var_os("DISPLAY").is_some()
+            && VideoWallpaper::is_installed("xwinwrap")
+            && VideoWallpaper::is_installed("mpv") {
+            Some(VideoBackend::Xwinwrap)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the mpv options: silent, looping, and scaled with the fit mode.
+    ///
+    /// # Arguments
+    /// * `prefix` - The prefix of each option ("--" on mpv command line, none in mpvpaper options)
+    fn mpv_options(&self, prefix: &str) -> Vec<String> {
+        let mut options = vec!["no-audio", "loop-file=inf", "no-osc", "no-osd-bar", "no-input-default-bindings"];
+        match self.fit_mode.unwrap_or(WallpaperFitMode::Zoom) {
+            WallpaperFitMode::Zoom | WallpaperFitMode::Span => options.push("panscan=1.0"),
+            WallpaperFitMode::Stretch => options.push("keepaspect=no"),
+            WallpaperFitMode::Fit | WallpaperFitMode::Center | WallpaperFitMode::Tile => {}
+        }
+
+        options.into_iter().map(|option| format!("{}{}", prefix, option)).collect()
+    }
+
+    /// Checks whether a program is found in `PATH`.
+    fn is_installed(program: &str) -> bool {
+        env::var_os("PATH")
+            .map(|path| env::split_paths(&path).any(|directory| directory.join(program).is_file()))
+            .unwrap_or(false)
+    }
+}
@@ -0,0 +1,184 @@
+use std::fs;
+use std::path::Path;
+
+use crate::bingwallpaper::WallpaperMetadata;
+
+/// Image kept in the archive.
+pub struct ArchivedImage {
+    /// Location of the image.
+    pub filename: String,
+
+    /// Information about the image.
+    pub metadata: WallpaperMetadata,
+}
+
+/// Archive of the downloaded images, with their information, one file per day and market.
+pub struct WallpaperArchive {}
+
+impl WallpaperArchive {
+    /// Copies the downloaded image and its information into the archive, unless already archived.
+    /// The oldest images are removed when the archive holds more than `max_images` images.
+    ///
+    /// # Arguments
+    /// * `directory` - The archive directory
+    /// * `original_filename` - The location of the downloaded image
+    /// * `metadata` - The information about the downloaded image
+    /// * `max_images` - The maximum number of images to keep
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wallpaperarchive::WallpaperArchive;
+    ///
+    /// WallpaperArchive::store("/home/user/.bingwallpaper-archive", &original_filename, &metadata, 60)?;
+    /// ```
+    pub fn store(directory: &str,
+                 original_filename: &str,
+                 metadata: &WallpaperMetadata,
+                 max_images: usize) -> Result<(), String> {
+        let archived_filename = WallpaperArchive::archived_filename(directory, metadata);
+        if Path::new(&archived_filename).exists() && WallpaperMetadata::load(&archived_filename).is_some() {
+            return Ok(());
+        }
+
+        if let Err(error) = fs::create_dir_all(directory) {
+            return Err(format!("Can't create archive directory: {:?}", error));
+        }
+
+        // Copies the image (no hard link, the downloaded image is overwritten in place every day)
+        if let Err(error) = fs::copy(original_filename, &archived_filename) {
+            return Err(format!("Can't archive image: {:?}", error));
+        }
+
+        metadata.save(&archived_filename)?;
+        println!("Wallpaper archived: {}", archived_filename);
+
+        // Removes the oldest images
+        for archived_image in WallpaperArchive::latest(directory, usize::MAX).iter().skip(max_images.max(1)) {
+            let _ = fs::remove_file(&archived_image.filename);
+            let _ = fs::remove_file(format!("{}.json", archived_image.filename));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the latest archived images, most recent first.
+    ///
+    /// # Arguments
+    /// * `directory` - The archive directory
+    /// * `count` - The maximum number of images to return
+    pub fn latest(directory: &str, count: usize) -> Vec<ArchivedImage> {
+        let dir_entries = match fs::read_dir(directory) {
+            Err(_) => return Vec::new(),
+            Ok(dir_entries) => dir_entries,
+        };
+
+        let mut archived_images: Vec<ArchivedImage> = dir_entries
+            .filter_map(|dir_entry| dir_entry.ok().map(|dir_entry| dir_entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "jpg"))
+            .filter_map(|path| {
+                let filename = path.to_string_lossy().to_string();
+                WallpaperMetadata::load(&filename).map(|metadata| ArchivedImage { filename, metadata })
+            })
+            .collect();
+
+        archived_images.sort_by(|a, b| {
+            b.metadata.start_date.cmp(&a.metadata.start_date).then_with(|| b.filename.cmp(&a.filename))
+        });
+        archived_images.truncate(count);
+
+        archived_images
+    }
+
+    /// Returns the location of an image in the archive (ie: "20250621-en-US.jpg").
+    fn archived_filename(directory: &str, metadata: &WallpaperMetadata) -> String {
+        let mut file_stem = metadata.start_date.clone();
+        if !metadata.market.is_empty() {
+            file_stem.push('-');
+            file_stem.push_str(&metadata.market);
+        }
+
+        // Keeps only safe characters, information comes from Bing API
+        let file_stem: String = file_stem
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+            .collect();
+
+        Path::new(directory).join(format!("{}.jpg", file_stem)).to_string_lossy().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use super::WallpaperArchive;
+    use crate::bingwallpaper::WallpaperMetadata;
+
+    fn metadata(start_date: &str, market: &str) -> WallpaperMetadata {
+        WallpaperMetadata {
+            url: format!("/th?id=OHR.{}", start_date),
+            title: String::new(),
+            copyright: String::new(),
+            copyright_link: String::new(),
+            photographer: String::new(),
+            location: String::new(),
+            start_date: start_date.to_string(),
+            market: market.to_string(),
+            source: String::from("Bing"),
+            video_url: String::new(),
+        }
+    }
+
+    #[test]
+    fn store_and_prune_oldest_images() {
+        let directory = env::temp_dir().join(format!("bingwallpaper-archive-{}", process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let archive_directory = directory.join("archive").to_string_lossy().to_string();
+        fs::create_dir_all(&directory).unwrap();
+        let original_filename = directory.join("bingwallpaper.jpg").to_string_lossy().to_string();
+
+        let archived_start_dates = |count| -> Vec<String> {
+            WallpaperArchive::latest(&archive_directory, count)
+                .into_iter()
+                .map(|archived_image| archived_image.metadata.start_date)
+                .collect()
+        };
+
+        // Stored in any order, returned most recent first
+        for start_date in ["20250620", "20250622", "20250621"] {
+            fs::write(&original_filename, start_date).unwrap();
+            WallpaperArchive::store(&archive_directory, &original_filename, &metadata(start_date, "en-US"), 3).unwrap();
+        }
+        assert_eq!(archived_start_dates(10), ["20250622", "20250621", "20250620"]);
+        assert_eq!(archived_start_dates(2), ["20250622", "20250621"]);
+
+        // Already archived: kept as is
+        fs::write(&original_filename, "changed").unwrap();
+        WallpaperArchive::store(&archive_directory, &original_filename, &metadata("20250622", "en-US"), 3).unwrap();
+        let archived_filename = format!("{}/20250622-en-US.jpg", archive_directory);
+        assert_eq!(fs::read_to_string(&archived_filename).unwrap(), "20250622");
+
+        // Beyond the maximum, the oldest images and their information are removed
+        fs::write(&original_filename, "20250623").unwrap();
+        WallpaperArchive::store(&archive_directory, &original_filename, &metadata("20250623", "en-US"), 2).unwrap();
+        assert_eq!(archived_start_dates(10), ["20250623", "20250622"]);
+        let mut filenames: Vec<String> = fs::read_dir(&archive_directory)
+            .unwrap()
+            .map(|dir_entry| dir_entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        filenames.sort();
+        assert_eq!(
+            filenames,
+            ["20250622-en-US.jpg", "20250622-en-US.jpg.json", "20250623-en-US.jpg", "20250623-en-US.jpg.json"]);
+
+        // Without market, unsafe characters removed
+        fs::write(&original_filename, "20250624").unwrap();
+        WallpaperArchive::store(&archive_directory, &original_filename, &metadata("2025/06/24", ""), 3).unwrap();
+        assert!(directory.join("archive/20250624.jpg").exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
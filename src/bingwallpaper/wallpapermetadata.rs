@@ -0,0 +1,167 @@
+use std::fs;
+
+use chrono::NaiveDate;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::bingwallpaper::bingapiclient::BingAPIImagesArchiveImage;
+
+/// Information about the wallpaper of the day.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WallpaperMetadata {
+    /// URL of the image without endpoint information (ie : /th?id=OHR...).
+    pub url: String,
+
+    /// Title of the image.
+    pub title: String,
+
+    /// Copyright information, as provided by Bing (ie: "Lake Louise, Canada (© John Doe/Getty Images)").
+    pub copyright: String,
+
+    /// Link (URL) to the copyright information page.
+    pub copyright_link: String,
+
+    /// Photographer (and agency) extracted from the copyright information.
+    pub photographer: String,
+
+    /// Location extracted from the copyright information.
+    pub location: String,
+
+    /// Date on which the image is proposed as wallpaper of the day ("%Y%m%d").
+    pub start_date: String,
+
+    /// Market (ie: "en-US") used to retrieve the image. Empty if not specified.
+    pub market: String,
+
+    /// Name of the wallpaper provider.
+    pub source: String,
+
+    /// URL of the video published next to the image. Empty if there is none.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub video_url: String,
+}
+
+impl WallpaperMetadata {
+    /// Creates a new instance from the Bing API image information.
+    ///
+    /// # Arguments
+    /// * `image` - The image retrieved from Bing API
+    /// * `market` - The market used to retrieve the image
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bingwallpaper::WallpaperMetadata;
+    /// let metadata = WallpaperMetadata::from_bing_image(&image, Some("en-US"));
+    /// ```
+    #[must_use]
+    pub fn from_bing_image(image: &BingAPIImagesArchiveImage, market: Option<&str>) -> WallpaperMetadata {
+        let (location, photographer) = WallpaperMetadata::split_copyright(&image.copyright);
+
+        WallpaperMetadata {
+            url: image.url.clone(),
+            title: image.title.clone(),
+            copyright: image.copyright.clone(),
+            copyright_link: image.copyrightlink.clone(),
+            photographer,
+            location,
+            start_date: image.startdate.clone(),
+            market: market.unwrap_or_default().to_string(),
+            source: String::from("Bing"),
+            video_url: image.video_url.clone().unwrap_or_default(),
+        }
+    }
+
+    /// Loads metadata stored alongside a downloaded image.
+    ///
+    /// # Arguments
+    /// * `image_filename` - The location of the downloaded image
+    pub fn load(image_filename: &str) -> Option<WallpaperMetadata> {
+        let content = fs::read_to_string(WallpaperMetadata::filename(image_filename)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Stores metadata alongside a downloaded image.
+    ///
+    /// # Arguments
+    /// * `image_filename` - The location of the downloaded image
+    pub fn save(&self, image_filename: &str) -> Result<(), String> {
+        let content = match serde_json::to_string_pretty(self) {
+            Err(error) => return Err(format!("Can't serialize wallpaper metadata: {:?}", error)),
+            Ok(content) => content,
+        };
+
+        match fs::write(WallpaperMetadata::filename(image_filename), content) {
+            Err(error) => Err(format!("Can't store wallpaper metadata: {:?}", error)),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Returns the location of the metadata file of a downloaded image.
+    pub fn filename(image_filename: &str) -> String {
+        format!("{}.json", image_filename)
+    }
+
+    /// Returns the date on which the image is proposed as wallpaper of the day.
+    pub fn date(&self) -> Option<NaiveDate> {
+        NaiveDate::parse_from_str(&self.start_date, "%Y%m%d").ok()
+    }
+
+    /// Returns the value of the field with the given name.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the field (ie: "title")
+    pub fn field(&self, name: &str) -> Option<&str> {
+        match name {
+            "title" => Some(&self.title),
+            "copyright" => Some(&self.copyright),
+            "copyright_link" => Some(&self.copyright_link),
+            "photographer" => Some(&self.photographer),
+            "location" => Some(&self.location),
+            "market" => Some(&self.market),
+            "source" => Some(&self.source),
+            _ => None,
+        }
+    }
+
+    /// Splits Bing copyright information into location and photographer.
+    ///
+    /// "Lake Louise, Canada (© John Doe/Getty Images)" gives "Lake Louise, Canada" and "John Doe/Getty Images".
+    fn split_copyright(copyright: &str) -> (String, String) {
+        match copyright.rfind('(') {
+            None => (copyright.trim().to_string(), String::new()),
+            Some(idx) => {
+                let location = copyright[..idx].trim().to_string();
+                let photographer = copyright[idx + 1..]
+                    .trim_end()
+                    .trim_end_matches(')')
+                    .trim_start_matches('©')
+                    .trim()
+                    .to_string();
+
+                (location, photographer)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WallpaperMetadata;
+
+    #[test]
+    fn split_copyright() {
+        let copyright_parts = [
+            ("Lake Louise, Canada (© John Doe/Getty Images)", ("Lake Louise, Canada", "John Doe/Getty Images")),
+            ("Lake Louise, Canada (©John Doe) ", ("Lake Louise, Canada", "John Doe")),
+            ("Lake Louise (Alberta), Canada (© John Doe)", ("Lake Louise (Alberta), Canada", "John Doe")),
+            ("Lake Louise, Canada", ("Lake Louise, Canada", "")),
+            ("© John Doe", ("© John Doe", "")),
+            ("", ("", "")),
+        ];
+
+        for (copyright, (location, photographer)) in copyright_parts {
+            let parts = WallpaperMetadata::split_copyright(copyright);
+            assert_eq!(parts, (location.to_string(), photographer.to_string()), "{:?}", copyright);
+        }
+    }
+}
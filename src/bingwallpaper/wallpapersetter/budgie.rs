@@ -0,0 +1,34 @@
+use crate::bingwallpaper::configuration::{GnomeConfiguration, WallpaperFitMode};
+use crate::bingwallpaper::wallpapersetter::{GnomeWallpaperSetter, WallpaperSetter};
+
+/// Applies the wallpaper on Budgie, which reads the GNOME background settings.
+pub struct BudgieWallpaperSetter {
+    gnome_wallpaper_setter: GnomeWallpaperSetter,
+}
+
+impl BudgieWallpaperSetter {
+    /// Creates a new instance.
+    ///
+    /// # Arguments
+    /// * `gnome_configuration` - The GNOME configuration to use
+    /// * `fit_mode` - How the wallpaper fills the screen, unchanged if not specified
+    pub fn new(gnome_configuration: GnomeConfiguration, fit_mode: Option<WallpaperFitMode>) -> BudgieWallpaperSetter {
+        BudgieWallpaperSetter {
+            gnome_wallpaper_setter: GnomeWallpaperSetter::new(gnome_configuration, fit_mode),
+        }
+    }
+}
+
+impl WallpaperSetter for BudgieWallpaperSetter {
+    fn name(&self) -> &'static str {
+        "Budgie"
+    }
+
+    fn set_wallpaper(&self, wallpaper_filename: &str, dark_wallpaper_filename: Option<&str>) -> Result<(), String> {
+        self.gnome_wallpaper_setter.set_wallpaper(wallpaper_filename, dark_wallpaper_filename)
+    }
+
+    fn set_lock_screen(&self, lock_screen_filename: &str) -> Result<(), String> {
+        self.gnome_wallpaper_setter.set_lock_screen(lock_screen_filename)
+    }
+}
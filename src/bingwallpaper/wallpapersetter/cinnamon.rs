@@ -0,0 +1,35 @@
+use crate::bingwallpaper::configuration::{GnomeConfiguration, WallpaperFitMode};
+use crate::bingwallpaper::wallpapersetter::{DesktopCommand, GSettings, GnomeWallpaperSetter, WallpaperSetter};
+
+/// Applies the wallpaper on Cinnamon through GSettings.
+pub struct CinnamonWallpaperSetter {
+    gnome_configuration: GnomeConfiguration,
+    fit_mode: Option<WallpaperFitMode>,
+}
+
+impl CinnamonWallpaperSetter {
+    /// Creates a new instance.
+    ///
+    /// # Arguments
+    /// * `gnome_configuration` - The GNOME configuration to use, Cinnamon shares its background settings
+    /// * `fit_mode` - How the wallpaper fills the screen, unchanged if not specified
+    pub fn new(gnome_configuration: GnomeConfiguration, fit_mode: Option<WallpaperFitMode>) -> CinnamonWallpaperSetter {
+        CinnamonWallpaperSetter { gnome_configuration, fit_mode }
+    }
+}
+
+impl WallpaperSetter for CinnamonWallpaperSetter {
+    fn name(&self) -> &'static str {
+        "Cinnamon"
+    }
+
+    fn set_wallpaper(&self, wallpaper_filename: &str, _dark_wallpaper_filename: Option<&str>) -> Result<(), String> {
+        let mut values = vec![("picture-uri", DesktopCommand::file_uri(wallpaper_filename))];
+        values.extend(GnomeWallpaperSetter::background_values(
+            wallpaper_filename,
+            &self.gnome_configuration,
+            self.fit_mode)?);
+
+        GSettings::set("org.cinnamon.desktop.background", &values)
+    }
+}
@@ -0,0 +1,154 @@
+use std::env;
+use std::path::PathBuf;
+
+use zbus::blocking::fdo::DBusProxy;
+use zbus::blocking::Connection;
+use zbus::names::BusName;
+
+use crate::bingwallpaper::wallpapersetter::GvdbReader;
+
+/// Well-known name of the dconf service, started on demand by D-Bus.
+const DCONF_SERVICE_NAME: &str = "ca.desrt.dconf";
+
+/// Writes keys to the dconf user database over D-Bus, as `gsettings` does with the dconf backend.
+pub struct Dconf {}
+
+impl Dconf {
+    /// Connects to the session bus, if the dconf service is available (running or activatable).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wallpapersetter::Dconf;
+    ///
+    /// if let Some(connection) = Dconf::connect() {
+    ///     Dconf::write(&connection, &[("/org/gnome/desktop/background/picture-options".to_string(), "zoom".to_string())])?;
+    /// }
+    /// ```
+    pub fn connect() -> Option<Connection> {
+        let connection = Connection::session().ok()?;
+        let dbus_proxy = DBusProxy::new(&connection).ok()?;
+        let service_name = BusName::try_from(DCONF_SERVICE_NAME).ok()?;
+
+        let is_available = dbus_proxy.name_has_owner(service_name).unwrap_or(false)
+            || dbus_proxy
+                .list_activatable_names()
+                .is_ok_and(|names| names.iter().any(|name| name.as_str() == DCONF_SERVICE_NAME));
+
+        is_available.then_some(connection)
+    }
+
+    /// Writes string values in a single change, then reads them back from the user database.
+    ///
+    /// # Arguments
+    /// * `connection` - The session bus connection, as returned by `connect`
+    /// * `changes` - The full keys (ie: "/org/gnome/desktop/background/picture-uri") and their new value
+    pub fn write(connection: &Connection, changes: &[(String, String)]) -> Result<(), String> {
+        let reply = connection.call_method(
+            Some(DCONF_SERVICE_NAME),
+            "/ca/desrt/dconf/Writer/user",
+            Some("ca.desrt.dconf.Writer"),
+            "Change",
+            &Dconf::serialize(changes));
+        if let Err(error) = reply {
+            return Err(format!("Can't write dconf settings: {:?}", error));
+        }
+
+        // The change is stored before the reply is sent
+        let user_database = Dconf::user_database_filename();
+        let gvdb_reader = GvdbReader::open(&user_database.to_string_lossy())?;
+        for (key, value) in changes {
+            let stored_value = gvdb_reader.string(key);
+            if stored_value.as_deref() != Some(value.as_str()) {
+                return Err(format!("Can't verify dconf setting {:?}: expected {:?}, found {:?}", key, value, stored_value));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the location of the dconf user database.
+    fn user_database_filename() -> PathBuf {
+        let config_directory = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .unwrap_or_default();
+
+        config_directory.join("dconf").join("user")
+    }
+
+    /// Serializes the changes as expected by the dconf writer: a GVariant `a{smv}`, each key holding a string.
+    fn serialize(changes: &[(String, String)]) -> Vec<u8> {
+        let mut array = Vec::new();
+        let mut entry_ends = Vec::new();
+
+        for (key, value) in changes {
+            // Entries are aligned on 8 bytes, like the variant they hold
+            array.resize(array.len().next_multiple_of(8), 0);
+
+            let mut entry = Vec::new();
+            entry.extend_from_slice(key.as_bytes());
+            entry.push(0);
+            let key_end = entry.len();
+            entry.resize(entry.len().next_multiple_of(8), 0);
+
+            // Variant: the string, a zero byte and its type, then the zero byte marking a non empty maybe
+            entry.extend_from_slice(value.as_bytes());
+            entry.extend_from_slice(&[0, 0, b's', 0]);
+            Dconf::append_framing_offsets(&mut entry, &[key_end]);
+
+            array.extend_from_slice(&entry);
+            entry_ends.push(array.len());
+        }
+
+        Dconf::append_framing_offsets(&mut array, &entry_ends);
+        array
+    }
+
+    /// Appends the framing offsets of a GVariant container, their size depends on the size of the container.
+    fn append_framing_offsets(content: &mut Vec<u8>, offsets: &[usize]) {
+        let offset_size = [1, 2, 4]
+            .into_iter()
+            .find(|offset_size| content.len() + offset_size * offsets.len() < 1 << (8 * offset_size))
+            .unwrap_or(8);
+
+        for offset in offsets {
+            content.extend_from_slice(&(*offset as u64).to_le_bytes()[..offset_size]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dconf;
+
+    #[test]
+    fn serialize_changes_as_gvariant() {
+        let changes = vec![
+            ("/a/b".to_string(), "x".to_string()),
+            ("/c".to_string(), "yz".to_string()),
+        ];
+
+        let mut expected: Vec<u8> = Vec::new();
+        // First entry: key, padding, variant, key framing offset
+        expected.extend_from_slice(b"/a/b\0\0\0\0x\0\0s\0\x05");
+        // Second entry, aligned on 8 bytes
+        expected.extend_from_slice(b"\0\0/c\0\0\0\0\0\0yz\0\0s\0\x03");
+        // Array framing offsets: end of each entry
+        expected.extend_from_slice(&[14, 31]);
+
+        assert_eq!(Dconf::serialize(&changes), expected);
+        assert!(Dconf::serialize(&[]).is_empty());
+    }
+
+    #[test]
+    fn framing_offsets_grow_with_the_container() {
+        let mut small = vec![0u8; 200];
+        Dconf::append_framing_offsets(&mut small, &[100, 200]);
+        assert_eq!(&small[200..], &[100, 200]);
+
+        let mut large = vec![0u8; 300];
+        Dconf::append_framing_offsets(&mut large, &[300]);
+        assert_eq!(&large[300..], &[0x2c, 0x01]);
+    }
+}
@@ -0,0 +1,288 @@
+use std::env;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use crate::bingwallpaper::configuration::WallpaperSetterBackend;
+use crate::bingwallpaper::wallpapersetter::{
+    BudgieWallpaperSetter, CinnamonWallpaperSetter, GnomeWallpaperSetter, HyprlandWallpaperSetter, KdeWallpaperSetter,
+    MateWallpaperSetter, PcmanfmWallpaperSetter, PortalWallpaperSetter, SwayWallpaperSetter, SwaybgWallpaperSetter,
+    SwwwWallpaperSetter, WallpaperSetter, X11WallpaperSetter, XfceWallpaperSetter,
+};
+use crate::bingwallpaper::BingWallpaperConfiguration;
+
+/// Finds the wallpaper setter matching the running desktop environment.
+pub struct WallpaperSetterDetector {}
+
+impl WallpaperSetterDetector {
+    /// Returns the wallpaper setter of the configured backend, or of the detected desktop environment.
+    ///
+    /// Inside a sandbox (ie: Flatpak, Snap), the desktop portal is used. Otherwise the desktop environment is looked
+    /// up in `XDG_CURRENT_DESKTOP`, then `DESKTOP_SESSION`, then in the running processes (these variables are not set
+    /// under systemd user services or ssh). On Wayland compositors, swww is used instead when its daemon is running.
+    /// Other X11 sessions (ie: bare window managers) get the root window wallpaper.
+    ///
+    /// # Arguments
+    /// * `configuration` - The Bing Wallpaper configuration to use
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wallpapersetter::WallpaperSetterDetector;
+    ///
+    /// let wallpaper_setter = WallpaperSetterDetector::detect(&configuration)?;
+    /// wallpaper_setter.set_wallpaper("/home/user/.bingwallpaper.jpg", None)?;
+    /// ```
+    pub fn detect(configuration: &BingWallpaperConfiguration) -> Result<Box<dyn WallpaperSetter>, String> {
+        let backend = configuration.wallpaper_setter.or_else(WallpaperSetterDetector::detect_backend);
+        if let Some(backend) = backend {
+            return Ok(WallpaperSetterDetector::create(configuration, backend));
+        }
+
+        let session_type = env::var("XDG_SESSION_TYPE").unwrap_or_default();
+        Err(format!(
+            "Can't detect desktop environment (session type: {:?}), set `wallpaper_setter` in configuration file",
+            session_type))
+    }
+
+    /// Creates the wallpaper setter of a backend.
+    fn create(configuration: &BingWallpaperConfiguration, backend: WallpaperSetterBackend) -> Box<dyn WallpaperSetter> {
+        let fit_mode = configuration.fit_mode;
+        let gnome_configuration = configuration.gnome.clone().unwrap_or_default();
+        let wayland_configuration = configuration.wayland.clone().unwrap_or_default();
+
+        match backend {
+            WallpaperSetterBackend::Gnome => Box::new(GnomeWallpaperSetter::new(gnome_configuration, fit_mode)),
+            WallpaperSetterBackend::Cinnamon => Box::new(CinnamonWallpaperSetter::new(gnome_configuration, fit_mode)),
+            WallpaperSetterBackend::Kde => {
+                Box::new(KdeWallpaperSetter::new(configuration.plasma.clone().unwrap_or_default(), fit_mode))
+            }
+            WallpaperSetterBackend::Xfce => Box::new(XfceWallpaperSetter::new(fit_mode)),
+            WallpaperSetterBackend::Mate => Box::new(MateWallpaperSetter::new(fit_mode)),
+            WallpaperSetterBackend::Lxqt => Box::new(PcmanfmWallpaperSetter::lxqt(fit_mode)),
+            WallpaperSetterBackend::Lxde => Box::new(PcmanfmWallpaperSetter::lxde(fit_mode)),
+            WallpaperSetterBackend::Budgie => Box::new(BudgieWallpaperSetter::new(gnome_configuration, fit_mode)),
+            WallpaperSetterBackend::Sway => Box::new(SwayWallpaperSetter::new(wayland_configuration, fit_mode)),
+            WallpaperSetterBackend::Hyprland => Box::new(HyprlandWallpaperSetter::new(wayland_configuration, fit_mode)),
+            WallpaperSetterBackend::Swww => Box::new(SwwwWallpaperSetter::new(wayland_configuration, fit_mode)),
+            WallpaperSetterBackend::Swaybg => Box::new(SwaybgWallpaperSetter::new(wayland_configuration, fit_mode)),
+            WallpaperSetterBackend::X11 => Box::new(X11WallpaperSetter::new(fit_mode)),
+            WallpaperSetterBackend::Portal => {
+                Box::new(PortalWallpaperSetter::new(configuration.portal.clone().unwrap_or_default()))
+            }
+        }
+    }
+
+    /// Detects the backend matching the running desktop environment.
+    fn detect_backend() -> Option<WallpaperSetterBackend> {
+        // Sandboxes can't reach the desktop settings, only the desktop portal
+        if Path::new("/.flatpak-info").exists() || env::var_os("SNAP").is_some() {
+            return Some(WallpaperSetterBackend::Portal);
+        }
+
+        let backend = WallpaperSetterDetector::detect_desktop_backend();
+
+        // swww draws over the compositor wallpaper, its daemon is only running when it is used
+        match backend {
+            Some(WallpaperSetterBackend::Sway) | Some(WallpaperSetterBackend::Hyprland)
+            | Some(WallpaperSetterBackend::Swaybg)
+                if WallpaperSetterDetector::running_processes().iter().any(|process_name| process_name == "swww-daemon") =>
+            {
+                Some(WallpaperSetterBackend::Swww)
+            }
+            _ => backend,
+        }
+    }
+
+    /// Detects the backend matching the desktop environment, or the Wayland compositor.
+    fn detect_desktop_backend() -> Option<WallpaperSetterBackend> {
+        let current_desktops = env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+        if let Some(backend) = WallpaperSetterDetector::from_current_desktops(&current_desktops) {
+            return Some(backend);
+        }
+
+        let desktop_session = env::var("DESKTOP_SESSION").unwrap_or_default();
+        if let Some(backend) = WallpaperSetterDetector::from_desktop_session(&desktop_session) {
+            return Some(backend);
+        }
+
+        // Only a text session (ie: ssh), desktop environment can still be running
+        let backend = WallpaperSetterDetector::running_processes()
+            .iter()
+            .find_map(|process_name| WallpaperSetterDetector::from_process_name(process_name));
+        if backend.is_some() {
+            return backend;
+        }
+
+        // Any other X11 session, ie: a window manager unknown to this list
+        let session_type = env::var("XDG_SESSION_TYPE").unwrap_or_default();
+        if env::var_os("DISPLAY").is_some() && session_type != "wayland" && env::var_os("WAYLAND_DISPLAY").is_none() {
+            return Some(WallpaperSetterBackend::X11);
+        }
+
+        None
+    }
+
+    /// Returns the backend matching the value of `XDG_CURRENT_DESKTOP`: desktop names, most specific first
+    /// (ie: "ubuntu:GNOME"). Unknown names are skipped.
+    fn from_current_desktops(current_desktops: &str) -> Option<WallpaperSetterBackend> {
+        current_desktops.split(':').find_map(WallpaperSetterDetector::from_desktop_name)
+    }
+
+    /// Returns the backend matching the value of `DESKTOP_SESSION`: the session name, sometimes the location of the
+    /// session file (ie: "/usr/share/xsessions/cinnamon").
+    fn from_desktop_session(desktop_session: &str) -> Option<WallpaperSetterBackend> {
+        WallpaperSetterDetector::from_desktop_name(desktop_session.rsplit('/').next().unwrap_or_default())
+    }
+
+    /// Returns the backend matching a desktop name, as found in `XDG_CURRENT_DESKTOP` or `DESKTOP_SESSION`.
+    fn from_desktop_name(desktop_name: &str) -> Option<WallpaperSetterBackend> {
+        match desktop_name.trim().to_lowercase().as_str() {
+            "x-cinnamon" | "cinnamon" | "cinnamon2d" => Some(WallpaperSetterBackend::Cinnamon),
+            "kde" | "plasma" | "plasmawayland" | "plasmax11" | "kde-plasma" => Some(WallpaperSetterBackend::Kde),
+            "xfce" | "xfce4" | "xubuntu" => Some(WallpaperSetterBackend::Xfce),
+            "mate" => Some(WallpaperSetterBackend::Mate),
+            "lxqt" | "lubuntu" => Some(WallpaperSetterBackend::Lxqt),
+            "lxde" => Some(WallpaperSetterBackend::Lxde),
+            "budgie" | "budgie-desktop" => Some(WallpaperSetterBackend::Budgie),
+            "sway" => Some(WallpaperSetterBackend::Sway),
+            "hyprland" => Some(WallpaperSetterBackend::Hyprland),
+            "river" | "labwc" | "wayfire" | "niri" => Some(WallpaperSetterBackend::Swaybg),
+            "i3" | "bspwm" | "awesome" | "dwm" | "openbox" | "fluxbox" | "herbstluftwm" | "qtile" | "xmonad"
+            | "spectrwm" | "icewm" | "jwm" | "leftwm" => Some(WallpaperSetterBackend::X11),
+            "gnome" | "gnome-classic" | "gnome-xorg" | "gnome-flashback" | "ubuntu" | "ubuntu-xorg" | "unity"
+            | "pantheon" | "pop" => Some(WallpaperSetterBackend::Gnome),
+            _ => None,
+        }
+    }
+
+    /// Returns the backend matching the name of a desktop environment process.
+    fn from_process_name(process_name: &str) -> Option<WallpaperSetterBackend> {
+        match process_name {
+            "cinnamon" | "cinnamon-session" => Some(WallpaperSetterBackend::Cinnamon),
+            "plasmashell" => Some(WallpaperSetterBackend::Kde),
+            "xfdesktop" | "xfce4-session" => Some(WallpaperSetterBackend::Xfce),
+            "mate-session" | "mate-panel" => Some(WallpaperSetterBackend::Mate),
+            "lxqt-session" | "pcmanfm-qt" => Some(WallpaperSetterBackend::Lxqt),
+            "lxsession" | "pcmanfm" => Some(WallpaperSetterBackend::Lxde),
+            "budgie-panel" | "budgie-wm" => Some(WallpaperSetterBackend::Budgie),
+            "sway" => Some(WallpaperSetterBackend::Sway),
+            "Hyprland" => Some(WallpaperSetterBackend::Hyprland),
+            "river" | "labwc" | "wayfire" | "niri" => Some(WallpaperSetterBackend::Swaybg),
+            "i3" | "bspwm" | "awesome" | "dwm" | "openbox" | "fluxbox" | "herbstluftwm" | "qtile" | "spectrwm"
+            | "icewm" | "jwm" | "leftwm" => Some(WallpaperSetterBackend::X11),
+            "gnome-shell" | "gnome-session-b" => Some(WallpaperSetterBackend::Gnome),
+            _ => None,
+        }
+    }
+
+    /// Returns the names of the processes running as the current user, empty if `/proc` is not available.
+    ///
+    /// Processes of other users are ignored: the login screen of GDM runs a `gnome-shell` of its own, as `gdm` user.
+    fn running_processes() -> Vec<String> {
+        let (dir_entries, uid) = match (fs::read_dir("/proc"), fs::metadata("/proc/self")) {
+            (Ok(dir_entries), Ok(metadata)) => (dir_entries, metadata.uid()),
+            _ => return Vec::new(),
+        };
+
+        dir_entries
+            .filter_map(|dir_entry| dir_entry.ok())
+            .filter(|dir_entry| dir_entry.file_name().to_string_lossy().bytes().all(|byte| byte.is_ascii_digit()))
+            .filter(|dir_entry| dir_entry.metadata().is_ok_and(|metadata| metadata.uid() == uid))
+            .filter_map(|dir_entry| fs::read_to_string(dir_entry.path().join("comm")).ok())
+            .map(|process_name| process_name.trim().to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WallpaperSetterDetector;
+    use crate::bingwallpaper::configuration::WallpaperSetterBackend;
+
+    #[test]
+    fn from_desktop_name() {
+        let desktop_backends = [
+            ("GNOME", Some(WallpaperSetterBackend::Gnome)),
+            ("ubuntu", Some(WallpaperSetterBackend::Gnome)),
+            ("X-Cinnamon", Some(WallpaperSetterBackend::Cinnamon)),
+            ("KDE", Some(WallpaperSetterBackend::Kde)),
+            ("plasmawayland", Some(WallpaperSetterBackend::Kde)),
+            ("XFCE", Some(WallpaperSetterBackend::Xfce)),
+            ("MATE", Some(WallpaperSetterBackend::Mate)),
+            ("LXQt", Some(WallpaperSetterBackend::Lxqt)),
+            ("LXDE", Some(WallpaperSetterBackend::Lxde)),
+            ("Budgie", Some(WallpaperSetterBackend::Budgie)),
+            ("sway", Some(WallpaperSetterBackend::Sway)),
+            ("Hyprland", Some(WallpaperSetterBackend::Hyprland)),
+            ("river", Some(WallpaperSetterBackend::Swaybg)),
+            ("i3", Some(WallpaperSetterBackend::X11)),
+            (" openbox ", Some(WallpaperSetterBackend::X11)),
+            ("", None),
+            ("unknown", None),
+        ];
+
+        for (desktop_name, backend) in desktop_backends {
+            assert_eq!(WallpaperSetterDetector::from_desktop_name(desktop_name), backend, "{:?}", desktop_name);
+        }
+    }
+
+    #[test]
+    fn from_current_desktops() {
+        let current_desktop_backends = [
+            ("ubuntu:GNOME", Some(WallpaperSetterBackend::Gnome)),
+            ("Unity7:Budgie:GNOME", Some(WallpaperSetterBackend::Budgie)),
+            ("KDE", Some(WallpaperSetterBackend::Kde)),
+            ("XFCE:", Some(WallpaperSetterBackend::Xfce)),
+            ("unknown:Unity7", None),
+            ("", None),
+        ];
+
+        for (current_desktops, backend) in current_desktop_backends {
+            assert_eq!(WallpaperSetterDetector::from_current_desktops(current_desktops), backend, "{:?}", current_desktops);
+        }
+    }
+
+    #[test]
+    fn from_desktop_session() {
+        let desktop_session_backends = [
+            ("/usr/share/xsessions/cinnamon", Some(WallpaperSetterBackend::Cinnamon)),
+            ("plasmawayland", Some(WallpaperSetterBackend::Kde)),
+            ("xubuntu", Some(WallpaperSetterBackend::Xfce)),
+            ("/usr/share/xsessions/", None),
+            ("", None),
+        ];
+
+        for (desktop_session, backend) in desktop_session_backends {
+            assert_eq!(WallpaperSetterDetector::from_desktop_session(desktop_session), backend, "{:?}", desktop_session);
+        }
+    }
+
+    #[test]
+    fn from_process_name() {
+        let process_backends = [
+            ("gnome-shell", Some(WallpaperSetterBackend::Gnome)),
+            ("cinnamon", Some(WallpaperSetterBackend::Cinnamon)),
+            ("plasmashell", Some(WallpaperSetterBackend::Kde)),
+            ("xfdesktop", Some(WallpaperSetterBackend::Xfce)),
+            ("mate-panel", Some(WallpaperSetterBackend::Mate)),
+            ("pcmanfm-qt", Some(WallpaperSetterBackend::Lxqt)),
+            ("pcmanfm", Some(WallpaperSetterBackend::Lxde)),
+            ("budgie-panel", Some(WallpaperSetterBackend::Budgie)),
+            ("Hyprland", Some(WallpaperSetterBackend::Hyprland)),
+            ("hyprland", None),
+            ("bash", None),
+        ];
+
+        for (process_name, backend) in process_backends {
+            assert_eq!(WallpaperSetterDetector::from_process_name(process_name), backend, "{:?}", process_name);
+        }
+    }
+
+    #[test]
+    fn running_processes_of_current_user() {
+        // The test itself runs as the current user
+        let process_name = std::fs::read_to_string("/proc/self/comm").unwrap();
+        assert!(WallpaperSetterDetector::running_processes().contains(&process_name.trim().to_string()));
+    }
+}
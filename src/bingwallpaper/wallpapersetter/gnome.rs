@@ -0,0 +1,92 @@
+use crate::bingwallpaper::configuration::{GnomeColorShadingType, GnomeConfiguration, WallpaperFitMode};
+use crate::bingwallpaper::wallpapersetter::{DesktopCommand, GSettings, WallpaperSetter};
+use crate::bingwallpaper::ColorPalette;
+
+/// Applies the wallpaper on GNOME (and desktops built on it, ie: Unity, Pantheon) through GSettings.
+pub struct GnomeWallpaperSetter {
+    gnome_configuration: GnomeConfiguration,
+    fit_mode: Option<WallpaperFitMode>,
+}
+
+impl GnomeWallpaperSetter {
+    /// Creates a new instance.
+    ///
+    /// # Arguments
+    /// * `gnome_configuration` - The GNOME configuration to use
+    /// * `fit_mode` - How the wallpaper fills the screen, unchanged if not specified
+    pub fn new(gnome_configuration: GnomeConfiguration, fit_mode: Option<WallpaperFitMode>) -> GnomeWallpaperSetter {
+        GnomeWallpaperSetter { gnome_configuration, fit_mode }
+    }
+
+    /// Returns the `picture-options` value of a fit mode, shared by GNOME, Cinnamon, MATE and Budgie.
+    pub(crate) fn picture_options(fit_mode: WallpaperFitMode) -> &'static str {
+        match fit_mode {
+            WallpaperFitMode::Zoom => "zoom",
+            WallpaperFitMode::Fit => "scaled",
+            WallpaperFitMode::Stretch => "stretched",
+            WallpaperFitMode::Center => "centered",
+            WallpaperFitMode::Tile => "wallpaper",
+            WallpaperFitMode::Span => "spanned",
+        }
+    }
+
+    /// Returns the background keys shared by GNOME and Cinnamon, besides the image: fit mode and colors.
+    ///
+    /// The colors, visible around the image (ie: `FIT` mode), are the two dominant colors of the wallpaper.
+    ///
+    /// # Arguments
+    /// * `wallpaper_filename` - The location of the wallpaper
+    /// * `gnome_configuration` - The GNOME configuration to use
+    /// * `fit_mode` - How the wallpaper fills the screen, unchanged if not specified
+    pub(crate) fn background_values(wallpaper_filename: &str,
+                                    gnome_configuration: &GnomeConfiguration,
+                                    fit_mode: Option<WallpaperFitMode>) -> Result<Vec<(&'static str, String)>, String> {
+        let mut values = Vec::new();
+
+        if let Some(fit_mode) = fit_mode {
+            values.push(("picture-options", GnomeWallpaperSetter::picture_options(fit_mode).to_string()));
+        }
+
+        let colors = ColorPalette::dominant_colors(wallpaper_filename, 2)?;
+        if let Some(primary_color) = colors.first() {
+            values.push(("primary-color", primary_color.clone()));
+            values.push(("secondary-color", colors.get(1).unwrap_or(primary_color).clone()));
+        }
+
+        if let Some(color_shading_type) = gnome_configuration.color_shading_type {
+            let color_shading_type = match color_shading_type {
+                GnomeColorShadingType::Solid => "solid",
+                GnomeColorShadingType::Horizontal => "horizontal",
+                GnomeColorShadingType::Vertical => "vertical",
+            };
+            values.push(("color-shading-type", color_shading_type.to_string()));
+        }
+
+        Ok(values)
+    }
+}
+
+impl WallpaperSetter for GnomeWallpaperSetter {
+    fn name(&self) -> &'static str {
+        "GNOME"
+    }
+
+    fn set_wallpaper(&self, wallpaper_filename: &str, dark_wallpaper_filename: Option<&str>) -> Result<(), String> {
+        let dark_wallpaper_filename = dark_wallpaper_filename.unwrap_or(wallpaper_filename);
+
+        let mut values = vec![
+            ("picture-uri", DesktopCommand::file_uri(wallpaper_filename)),
+            ("picture-uri-dark", DesktopCommand::file_uri(dark_wallpaper_filename)),
+        ];
+        values.extend(GnomeWallpaperSetter::background_values(
+            wallpaper_filename,
+            &self.gnome_configuration,
+            self.fit_mode)?);
+
+        GSettings::set("org.gnome.desktop.background", &values)
+    }
+
+    fn set_lock_screen(&self, lock_screen_filename: &str) -> Result<(), String> {
+        GSettings::set("org.gnome.desktop.screensaver", &[("picture-uri", DesktopCommand::file_uri(lock_screen_filename))])
+    }
+}
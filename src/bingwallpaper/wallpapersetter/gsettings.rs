@@ -0,0 +1,77 @@
+use crate::bingwallpaper::wallpapersetter::{Dconf, DesktopCommand};
+
+/// Changes GSettings string keys, and verifies them by reading them back.
+///
+/// Keys are written to dconf over D-Bus, in a single change. The `gsettings` command is only used when dconf is not
+/// available (ie: no session bus).
+pub struct GSettings {}
+
+impl GSettings {
+    /// Sets string (or enumeration) keys of a schema.
+    ///
+    /// # Arguments
+    /// * `schema_id` - The schema of the keys (ie: "org.gnome.desktop.background")
+    /// * `values` - The keys and their new value
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wallpapersetter::GSettings;
+    ///
+    /// GSettings::set("org.gnome.desktop.background", &[("picture-options", "zoom".to_string())])?;
+    /// ```
+    pub fn set(schema_id: &str, values: &[(&str, String)]) -> Result<(), String> {
+        let connection = match Dconf::connect() {
+            None => return GSettings::set_with_command(schema_id, values),
+            Some(connection) => connection,
+        };
+
+        // Schemas are stored under their identifier (ie: "/org/gnome/desktop/background/")
+        let schema_path = format!("/{}/", schema_id.replace('.', "/"));
+        let changes: Vec<(String, String)> = values
+            .iter()
+            .map(|(key, value)| (format!("{}{}", schema_path, key), value.clone()))
+            .collect();
+
+        Dconf::write(&connection, &changes)
+    }
+
+    /// Sets the keys with the `gsettings` command, one key at a time.
+    fn set_with_command(schema_id: &str, values: &[(&str, String)]) -> Result<(), String> {
+        for (key, value) in values {
+            DesktopCommand::run(DesktopCommand::command("gsettings").arg("set").arg(schema_id).arg(key).arg(value))?;
+
+            let stored_value = DesktopCommand::output(
+                DesktopCommand::command("gsettings").arg("get").arg(schema_id).arg(key))?;
+            let stored_value = GSettings::parse_string(&stored_value);
+            if stored_value.as_deref() != Some(value.as_str()) {
+                return Err(format!(
+                    "Can't verify setting {} {}: expected {:?}, found {:?}",
+                    schema_id,
+                    key,
+                    value,
+                    stored_value));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses a string printed by `gsettings get`, quoted and escaped (ie: 'it\'s').
+    fn parse_string(printed_value: &str) -> Option<String> {
+        let printed_value = printed_value.trim();
+        let quote = printed_value.chars().next().filter(|quote| *quote == '\'' || *quote == '"')?;
+        let quoted_value = printed_value.strip_prefix(quote)?.strip_suffix(quote)?;
+
+        let mut value = String::new();
+        let mut chars = quoted_value.chars();
+        while let Some(char) = chars.next() {
+            match char {
+                '\\' => value.push(chars.next()?),
+                _ => value.push(char),
+            }
+        }
+
+        Some(value)
+    }
+}
@@ -0,0 +1,214 @@
+use std::fs;
+
+/// Signature at the start of every GVDB file (little endian).
+const GVDB_SIGNATURE: &[u8; 8] = b"GVariant";
+
+/// Size of a hash table item: hash, parent, key start, key size, type, padding, value start and end.
+const GVDB_ITEM_SIZE: usize = 24;
+
+/// Parent index of the items without parent.
+const GVDB_NO_PARENT: u32 = u32::MAX;
+
+/// Reads values of a GVDB file, the format of the dconf databases (ie: `~/.config/dconf/user`).
+///
+/// Only the root hash table and string values are supported, which is what dconf writes for the user database.
+pub struct GvdbReader {
+    content: Vec<u8>,
+}
+
+/// Item of the root hash table.
+struct GvdbItem {
+    parent: u32,
+    key: (usize, usize),
+    item_type: u8,
+    value: (usize, usize),
+}
+
+impl GvdbReader {
+    /// Opens a GVDB file.
+    ///
+    /// # Arguments
+    /// * `filename` - The location of the GVDB file
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wallpapersetter::GvdbReader;
+    ///
+    /// let gvdb_reader = GvdbReader::open("/home/user/.config/dconf/user")?;
+    /// let picture_uri = gvdb_reader.string("/org/gnome/desktop/background/picture-uri");
+    /// ```
+    pub fn open(filename: &str) -> Result<GvdbReader, String> {
+        let content = match fs::read(filename) {
+            Err(error) => return Err(format!("Can't read GVDB file {:?}: {:?}", filename, error)),
+            Ok(content) => content,
+        };
+
+        if !content.starts_with(GVDB_SIGNATURE) {
+            return Err(format!("Can't read GVDB file {:?}: unsupported format", filename));
+        }
+
+        Ok(GvdbReader { content })
+    }
+
+    /// Returns the string value of a key, none if the key is not set or does not hold a string.
+    ///
+    /// # Arguments
+    /// * `key` - The full key (ie: "/org/gnome/desktop/background/picture-uri")
+    pub fn string(&self, key: &str) -> Option<String> {
+        let items = self.items()?;
+        let item = items
+            .iter()
+            .find(|item| item.item_type == b'v' && self.full_key(&items, item).as_deref() == Some(key))?;
+
+        // A variant: the value, a zero byte, then the type of the value
+        let variant = self.content.get(item.value.0..item.value.1)?;
+        let type_separator = variant.iter().rposition(|byte| *byte == 0)?;
+        if &variant[type_separator + 1..] != b"s" {
+            return None;
+        }
+
+        let value = variant[..type_separator].strip_suffix(&[0])?;
+        Some(String::from_utf8_lossy(value).to_string())
+    }
+
+    /// Returns the items of the root hash table.
+    fn items(&self) -> Option<Vec<GvdbItem>> {
+        let (table_start, table_end) = (self.u32_at(16)? as usize, self.u32_at(20)? as usize);
+
+        // Table header: bloom filter size (lower 27 bits) and number of buckets, skipped as items are scanned
+        let bloom_words = (self.u32_at(table_start)? & ((1 << 27) - 1)) as usize;
+        let buckets = self.u32_at(table_start + 4)? as usize;
+        let items_start = table_start + 8 + 4 * bloom_words + 4 * buckets;
+        if items_start > table_end || table_end > self.content.len() {
+            return None;
+        }
+
+        let item_count = (table_end - items_start) / GVDB_ITEM_SIZE;
+        (0..item_count)
+            .map(|item_idx| items_start + item_idx * GVDB_ITEM_SIZE)
+            .map(|offset| {
+                Some(GvdbItem {
+                    parent: self.u32_at(offset + 4)?,
+                    key: (self.u32_at(offset + 8)? as usize, self.u16_at(offset + 12)? as usize),
+                    item_type: *self.content.get(offset + 14)?,
+                    value: (self.u32_at(offset + 16)? as usize, self.u32_at(offset + 20)? as usize),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the full key of an item: each item only holds the part following its parent key.
+    fn full_key(&self, items: &[GvdbItem], item: &GvdbItem) -> Option<String> {
+        let mut key_parts = Vec::new();
+        let mut current_item = item;
+
+        loop {
+            let (key_start, key_size) = current_item.key;
+            key_parts.push(self.content.get(key_start..key_start + key_size)?);
+
+            if current_item.parent == GVDB_NO_PARENT {
+                break;
+            }
+
+            // Protects against a corrupted file
+            if key_parts.len() > items.len() {
+                return None;
+            }
+
+            current_item = items.get(current_item.parent as usize)?;
+        }
+
+        let full_key: Vec<u8> = key_parts.into_iter().rev().flatten().copied().collect();
+        String::from_utf8(full_key).ok()
+    }
+
+    /// Reads a little endian 32 bits integer.
+    fn u32_at(&self, offset: usize) -> Option<u32> {
+        let bytes = self.content.get(offset..offset + 4)?;
+        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Reads a little endian 16 bits integer.
+    fn u16_at(&self, offset: usize) -> Option<u16> {
+        let bytes = self.content.get(offset..offset + 2)?;
+        Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::process;
+
+    use super::{GvdbReader, GVDB_ITEM_SIZE, GVDB_NO_PARENT};
+
+    /// Builds a GVDB file with a single hash table holding the given items: parent, key, type and value.
+    fn gvdb_content(items: &[(u32, &str, u8, &[u8])]) -> Vec<u8> {
+        let table_start = 24;
+        let items_start = table_start + 8 + 4;
+        let table_end = items_start + items.len() * GVDB_ITEM_SIZE;
+
+        let mut content = b"GVariant".to_vec();
+        for value in [0, 0, table_start as u32, table_end as u32, 0, 1, 0] {
+            content.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let mut data = Vec::new();
+        for (parent, key, item_type, value) in items {
+            let key_start = table_end + data.len();
+            data.extend_from_slice(key.as_bytes());
+            let value_start = table_end + data.len();
+            data.extend_from_slice(value);
+
+            content.extend_from_slice(&0u32.to_le_bytes());
+            content.extend_from_slice(&parent.to_le_bytes());
+            content.extend_from_slice(&(key_start as u32).to_le_bytes());
+            content.extend_from_slice(&(key.len() as u16).to_le_bytes());
+            content.extend_from_slice(&[*item_type, 0]);
+            content.extend_from_slice(&(value_start as u32).to_le_bytes());
+            content.extend_from_slice(&((value_start + value.len()) as u32).to_le_bytes());
+        }
+
+        content.extend_from_slice(&data);
+        content
+    }
+
+    #[test]
+    fn read_string_values() {
+        let directory = std::env::temp_dir().join(format!("bingwallpaper-gvdb-{}", process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let filename = directory.join("user").to_string_lossy().to_string();
+
+        fs::write(&filename, gvdb_content(&[
+            (GVDB_NO_PARENT, "/org/gnome/", b'L', b""),
+            (0, "background/", b'L', b""),
+            (1, "picture-uri", b'v', b"file:///tmp/a.jpg\0\0s"),
+            (1, "show-desktop-icons", b'v', b"\x01\0b"),
+        ])).unwrap();
+
+        let gvdb_reader = GvdbReader::open(&filename).unwrap();
+        assert_eq!(
+            gvdb_reader.string("/org/gnome/background/picture-uri").as_deref(),
+            Some("file:///tmp/a.jpg"));
+        assert_eq!(gvdb_reader.string("/org/gnome/background/show-desktop-icons"), None);
+        assert_eq!(gvdb_reader.string("/org/gnome/background/"), None);
+        assert_eq!(gvdb_reader.string("/org/gnome/background/picture-options"), None);
+
+        // Other formats are rejected
+        fs::write(&filename, b"[org/gnome]").unwrap();
+        assert!(GvdbReader::open(&filename).is_err());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn corrupted_parents_are_ignored() {
+        let gvdb_reader = GvdbReader {
+            content: gvdb_content(&[(1, "a", b'L', b""), (0, "b", b'v', b"c\0\0s")]),
+        };
+
+        assert_eq!(gvdb_reader.string("ab"), None);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::bingwallpaper::configuration::{WallpaperFitMode, WaylandConfiguration};
+use crate::bingwallpaper::wallpapersetter::{DesktopCommand, WallpaperSetter};
+
+/// Applies the wallpaper on Hyprland with `hyprctl hyprpaper`, to every monitor or to the configured ones.
+pub struct HyprlandWallpaperSetter {
+    wayland_configuration: WaylandConfiguration,
+    fit_mode: Option<WallpaperFitMode>,
+}
+
+impl HyprlandWallpaperSetter {
+    /// Creates a new instance.
+    ///
+    /// # Arguments
+    /// * `wayland_configuration` - The Wayland configuration to use
+    /// * `fit_mode` - How the wallpaper fills the screen, `ZOOM` if not specified
+    pub fn new(wayland_configuration: WaylandConfiguration,
+               fit_mode: Option<WallpaperFitMode>) -> HyprlandWallpaperSetter {
+        HyprlandWallpaperSetter { wayland_configuration, fit_mode }
+    }
+
+    /// Sends a request to hyprpaper. hyprctl exits successfully even when hyprpaper rejects the request,
+    /// the reply is checked instead.
+    fn hyprpaper(request: &str, argument: &str) -> Result<(), String> {
+        let reply = DesktopCommand::output(DesktopCommand::command("hyprctl").arg("hyprpaper").arg(request).arg(argument))?;
+        let reply = reply.trim();
+
+        if !reply.is_empty() && reply != "ok" {
+            return Err(format!("hyprpaper rejected {} {:?}: {}", request, argument, reply));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the hyprpaper wallpaper argument of a monitor (empty for all of them).
+    fn wallpaper_argument(&self, monitor_name: &str, wallpaper_filename: &str) -> String {
+        // hyprpaper covers the monitor by default
+        let mode = match self.fit_mode {
+            Some(WallpaperFitMode::Fit) | Some(WallpaperFitMode::Center) => "contain:",
+            Some(WallpaperFitMode::Tile) => "tile:",
+            _ => "",
+        };
+
+        format!("{},{}{}", monitor_name, mode, wallpaper_filename)
+    }
+}
+
+impl WallpaperSetter for HyprlandWallpaperSetter {
+    fn name(&self) -> &'static str {
+        "Hyprland"
+    }
+
+    fn set_wallpaper(&self, wallpaper_filename: &str, _dark_wallpaper_filename: Option<&str>) -> Result<(), String> {
+        let output_filenames = self.wayland_configuration.output_filenames.clone().unwrap_or_default();
+
+        // Images must be loaded before being displayed. hyprpaper keeps loaded images by location: the previous image
+        // at the same location is unloaded first (it may not be loaded, the request can fail)
+        let mut filenames = vec![wallpaper_filename];
+        for output_filename in output_filenames.values() {
+            if !filenames.contains(&output_filename.as_str()) {
+                filenames.push(output_filename);
+            }
+        }
+        for filename in &filenames {
+            let _ = HyprlandWallpaperSetter::hyprpaper("unload", filename);
+        }
+        for filename in &filenames {
+            HyprlandWallpaperSetter::hyprpaper("preload", filename)?;
+        }
+
+        HyprlandWallpaperSetter::hyprpaper("wallpaper", &self.wallpaper_argument("", wallpaper_filename))?;
+        for (output_name, output_filename) in &output_filenames {
+            HyprlandWallpaperSetter::hyprpaper("wallpaper", &self.wallpaper_argument(output_name, output_filename))?;
+        }
+
+        // Frees the previous wallpaper
+        HyprlandWallpaperSetter::hyprpaper("unload", "unused")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use super::HyprlandWallpaperSetter;
+    use crate::bingwallpaper::configuration::{WallpaperFitMode, WaylandConfiguration};
+    use crate::bingwallpaper::wallpapersetter::{DesktopCommand, WallpaperSetter};
+
+    #[test]
+    fn wallpaper_argument() {
+        let fit_mode_arguments = [
+            (None, ",/home/user/bingwallpaper.jpg"),
+            (Some(WallpaperFitMode::Zoom), ",/home/user/bingwallpaper.jpg"),
+            (Some(WallpaperFitMode::Fit), ",contain:/home/user/bingwallpaper.jpg"),
+            (Some(WallpaperFitMode::Center), ",contain:/home/user/bingwallpaper.jpg"),
+            (Some(WallpaperFitMode::Tile), ",tile:/home/user/bingwallpaper.jpg"),
+        ];
+
+        for (fit_mode, argument) in fit_mode_arguments {
+            let wallpaper_setter = HyprlandWallpaperSetter::new(WaylandConfiguration::default(), fit_mode);
+            assert_eq!(wallpaper_setter.wallpaper_argument("", "/home/user/bingwallpaper.jpg"), argument);
+        }
+
+        let wallpaper_setter = HyprlandWallpaperSetter::new(WaylandConfiguration::default(), None);
+        assert_eq!(wallpaper_setter.wallpaper_argument("DP-1", "/home/user/a.jpg"), "DP-1,/home/user/a.jpg");
+    }
+
+    #[test]
+    fn images_are_loaded_then_displayed() {
+        let directory = env::temp_dir().join(format!("bingwallpaper-hyprland-{}", process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        DesktopCommand::write_stub(&directory, "hyprctl", "ok");
+
+        let wayland_configuration = WaylandConfiguration {
+            output_filenames: Some(BTreeMap::from([("DP-1".to_string(), "/home/user/portrait.jpg".to_string())])),
+            ..WaylandConfiguration::default()
+        };
+        DesktopCommand::set_stub_directory(Some(directory.clone()));
+        let result = HyprlandWallpaperSetter::new(wayland_configuration, Some(WallpaperFitMode::Tile))
+            .set_wallpaper("/home/user/bingwallpaper.jpg", None);
+        let calls = fs::read_to_string(directory.join("calls")).unwrap();
+
+        // hyprpaper rejects the request: hyprctl succeeds, the reply is an error
+        DesktopCommand::write_stub(&directory, "hyprctl", "wallpaper failed (not preloaded)");
+        let rejected_result = HyprlandWallpaperSetter::hyprpaper("wallpaper", ",/home/user/bingwallpaper.jpg");
+        DesktopCommand::set_stub_directory(None);
+
+        result.unwrap();
+        assert_eq!(
+            calls.lines().collect::<Vec<&str>>(),
+            [
+                "hyprpaper unload /home/user/bingwallpaper.jpg",
+                "hyprpaper unload /home/user/portrait.jpg",
+                "hyprpaper preload /home/user/bingwallpaper.jpg",
+                "hyprpaper preload /home/user/portrait.jpg",
+                "hyprpaper wallpaper ,tile:/home/user/bingwallpaper.jpg",
+                "hyprpaper wallpaper DP-1,tile:/home/user/portrait.jpg",
+                "hyprpaper unload unused",
+            ]);
+        assert!(rejected_result.unwrap_err().contains("not preloaded"));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
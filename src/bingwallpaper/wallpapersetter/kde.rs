@@ -0,0 +1,113 @@
+use crate::bingwallpaper::configuration::{PlasmaConfiguration, PlasmaFillMode, WallpaperFitMode};
+use crate::bingwallpaper::wallpapersetter::{DesktopCommand, WallpaperSetter};
+
+/// Applies the wallpaper on KDE Plasma, to every desktop containment.
+///
+/// Plasma doesn't reload an image applied again under the same location: each image is applied under a unique one.
+pub struct KdeWallpaperSetter {
+    plasma_configuration: PlasmaConfiguration,
+    fit_mode: Option<WallpaperFitMode>,
+}
+
+impl KdeWallpaperSetter {
+    /// Creates a new instance.
+    ///
+    /// # Arguments
+    /// * `plasma_configuration` - The Plasma configuration to use
+    /// * `fit_mode` - How the wallpaper fills the screen, when the Plasma fill mode is not configured
+    pub fn new(plasma_configuration: PlasmaConfiguration, fit_mode: Option<WallpaperFitMode>) -> KdeWallpaperSetter {
+        KdeWallpaperSetter { plasma_configuration, fit_mode }
+    }
+
+    /// Returns the Plasma script applying the wallpaper to every desktop containment.
+    /// Screens without a specific image get the wallpaper.
+    fn script(&self, wallpaper_filename: &str) -> Result<String, String> {
+        let mut screen_uris: Vec<String> = Vec::new();
+        for screen_filename in self.plasma_configuration.screen_filenames.iter().flatten() {
+            let screen_filename = DesktopCommand::unique_filename(screen_filename)?;
+            screen_uris.push(KdeWallpaperSetter::quote(&DesktopCommand::file_uri(&screen_filename)));
+        }
+
+        let fill_mode = self.plasma_configuration.fill_mode.unwrap_or(match self.fit_mode {
+            None | Some(WallpaperFitMode::Zoom) | Some(WallpaperFitMode::Span) => PlasmaFillMode::PreserveAspectCrop,
+            Some(WallpaperFitMode::Fit) => PlasmaFillMode::PreserveAspectFit,
+            Some(WallpaperFitMode::Stretch) => PlasmaFillMode::Stretch,
+            Some(WallpaperFitMode::Center) => PlasmaFillMode::Pad,
+            Some(WallpaperFitMode::Tile) => PlasmaFillMode::Tile,
+        });
+        let fill_mode = match fill_mode {
+            PlasmaFillMode::Stretch => 0,
+            PlasmaFillMode::PreserveAspectFit => 1,
+            PlasmaFillMode::PreserveAspectCrop => 2,
+            PlasmaFillMode::Tile => 3,
+            PlasmaFillMode::TileVertically => 4,
+            PlasmaFillMode::TileHorizontally => 5,
+            PlasmaFillMode::Pad => 6,
+        };
+
+        let mut script = format!("var screenImages = [{}];\n", screen_uris.join(", "));
+        script.push_str(&format!(
+            "var wallpaperImage = {};\n",
+            KdeWallpaperSetter::quote(&DesktopCommand::file_uri(wallpaper_filename))));
+        script.push_str("desktops().forEach(function (desktop) {\n");
+        script.push_str("    var image = desktop.screen >= 0 && desktop.screen < screenImages.length\n");
+        script.push_str("        ? screenImages[desktop.screen] : wallpaperImage;\n");
+        script.push_str("    desktop.wallpaperPlugin = \"org.kde.image\";\n");
+        script.push_str("    desktop.currentConfigGroup = [\"Wallpaper\", \"org.kde.image\", \"General\"];\n");
+        script.push_str("    desktop.writeConfig(\"Image\", image);\n");
+        script.push_str(&format!("    desktop.writeConfig(\"FillMode\", {});\n", fill_mode));
+        script.push_str("});\n");
+
+        Ok(script)
+    }
+
+    /// Returns a value as a JavaScript string literal.
+    fn quote(value: &str) -> String {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+impl WallpaperSetter for KdeWallpaperSetter {
+    fn name(&self) -> &'static str {
+        "KDE Plasma"
+    }
+
+    fn set_wallpaper(&self, wallpaper_filename: &str, _dark_wallpaper_filename: Option<&str>) -> Result<(), String> {
+        let wallpaper_filename = &DesktopCommand::unique_filename(wallpaper_filename)?;
+        let result = DesktopCommand::run(DesktopCommand::command("dbus-send")
+            .arg("--session")
+            .arg("--dest=org.kde.plasmashell")
+            .arg("--type=method_call")
+            .arg("--print-reply")
+            .arg("/PlasmaShell")
+            .arg("org.kde.PlasmaShell.evaluateScript")
+            .arg(format!("string:{}", self.script(wallpaper_filename)?)));
+
+        // Scripting can be disabled (ie: desktop layout locked), the image is then applied to every screen
+        if let Err(error) = result {
+            println!("Can't apply wallpaper with Plasma script, fallback to plasma-apply-wallpaperimage\n{}", error);
+            return DesktopCommand::run(DesktopCommand::command("plasma-apply-wallpaperimage").arg(wallpaper_filename));
+        }
+
+        Ok(())
+    }
+
+    fn set_lock_screen(&self, lock_screen_filename: &str) -> Result<(), String> {
+        let lock_screen_uri = DesktopCommand::file_uri(lock_screen_filename);
+
+        // Plasma 6 ships kwriteconfig6, Plasma 5 kwriteconfig5
+        let mut result = Ok(());
+        for program in ["kwriteconfig6", "kwriteconfig5"] {
+            result = DesktopCommand::run(DesktopCommand::command(program)
+                .args(["--file", "kscreenlockerrc"])
+                .args(["--group", "Greeter", "--group", "Wallpaper", "--group", "org.kde.image", "--group", "General"])
+                .args(["--key", "Image", &lock_screen_uri]));
+
+            if result.is_ok() {
+                break;
+            }
+        }
+
+        result
+    }
+}
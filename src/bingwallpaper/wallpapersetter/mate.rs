@@ -0,0 +1,42 @@
+use crate::bingwallpaper::configuration::WallpaperFitMode;
+use crate::bingwallpaper::wallpapersetter::{DesktopCommand, GnomeWallpaperSetter, WallpaperSetter};
+
+/// Applies the wallpaper on MATE with `gsettings`.
+pub struct MateWallpaperSetter {
+    fit_mode: Option<WallpaperFitMode>,
+}
+
+impl MateWallpaperSetter {
+    /// Creates a new instance.
+    ///
+    /// # Arguments
+    /// * `fit_mode` - How the wallpaper fills the screen, unchanged if not specified
+    pub fn new(fit_mode: Option<WallpaperFitMode>) -> MateWallpaperSetter {
+        MateWallpaperSetter { fit_mode }
+    }
+}
+
+impl WallpaperSetter for MateWallpaperSetter {
+    fn name(&self) -> &'static str {
+        "MATE"
+    }
+
+    fn set_wallpaper(&self, wallpaper_filename: &str, _dark_wallpaper_filename: Option<&str>) -> Result<(), String> {
+        // MATE expects a location, not an URI
+        DesktopCommand::run(DesktopCommand::command("gsettings")
+            .arg("set")
+            .arg("org.mate.background")
+            .arg("picture-filename")
+            .arg(wallpaper_filename))?;
+
+        if let Some(fit_mode) = self.fit_mode {
+            DesktopCommand::run(DesktopCommand::command("gsettings")
+                .arg("set")
+                .arg("org.mate.background")
+                .arg("picture-options")
+                .arg(GnomeWallpaperSetter::picture_options(fit_mode)))?;
+        }
+
+        Ok(())
+    }
+}
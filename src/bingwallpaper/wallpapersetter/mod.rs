@@ -0,0 +1,273 @@
+#[cfg(test)]
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+#[cfg(test)]
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::UNIX_EPOCH;
+
+pub use self::budgie::BudgieWallpaperSetter;
+pub use self::cinnamon::CinnamonWallpaperSetter;
+pub use self::dconf::Dconf;
+pub use self::detector::WallpaperSetterDetector;
+pub use self::gnome::GnomeWallpaperSetter;
+pub use self::gsettings::GSettings;
+pub use self::gvdb::GvdbReader;
+pub use self::hyprland::HyprlandWallpaperSetter;
+pub use self::kde::KdeWallpaperSetter;
+pub use self::mate::MateWallpaperSetter;
+pub use self::pcmanfm::PcmanfmWallpaperSetter;
+pub use self::portal::PortalWallpaperSetter;
+pub use self::sway::SwayWallpaperSetter;
+pub use self::swaybg::SwaybgWallpaperSetter;
+pub use self::swww::SwwwWallpaperSetter;
+pub use self::x11::X11WallpaperSetter;
+pub use self::xfce::XfceWallpaperSetter;
+
+mod budgie;
+mod cinnamon;
+mod dconf;
+mod detector;
+mod gnome;
+mod gsettings;
+mod gvdb;
+mod hyprland;
+mod kde;
+mod mate;
+mod pcmanfm;
+mod portal;
+mod sway;
+mod swaybg;
+mod swww;
+mod x11;
+mod xfce;
+
+/// Applies the wallpaper on a desktop environment, one implementation per desktop.
+pub trait WallpaperSetter {
+    /// Returns the name of the desktop environment (ie: "GNOME").
+    fn name(&self) -> &'static str;
+
+    /// Applies the wallpaper.
+    ///
+    /// # Arguments
+    /// * `wallpaper_filename` - The location of the wallpaper
+    /// * `dark_wallpaper_filename` - The location of the wallpaper used with the dark style, if any
+    fn set_wallpaper(&self, wallpaper_filename: &str, dark_wallpaper_filename: Option<&str>) -> Result<(), String>;
+
+    /// Applies the lock screen wallpaper.
+    ///
+    /// # Arguments
+    /// * `lock_screen_filename` - The location of the lock screen wallpaper
+    fn set_lock_screen(&self, lock_screen_filename: &str) -> Result<(), String> {
+        Err(format!("Can't change lock screen wallpaper {:?}: not supported on {}", lock_screen_filename, self.name()))
+    }
+}
+
+/// Runs the commands used by the wallpaper setters.
+pub struct DesktopCommand {}
+
+#[cfg(test)]
+thread_local! {
+    /// Directory holding the stubs of the desktop programs, for the tests running on the current thread.
+    static STUB_DIRECTORY: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+impl DesktopCommand {
+    /// Returns a command running a desktop program (ie: "gsettings"), found in `PATH`.
+    ///
+    /// In tests, the program is taken from the stub directory of the current thread, if any.
+    ///
+    /// # Arguments
+    /// * `program` - The name of the program
+    pub fn command(program: &str) -> Command {
+        #[cfg(test)]
+        if let Some(stub_directory) = STUB_DIRECTORY.with(|stub_directory| stub_directory.borrow().clone()) {
+            return Command::new(stub_directory.join(program));
+        }
+
+        Command::new(program)
+    }
+
+    /// Runs the desktop programs from the given directory, instead of `PATH`, for the current thread.
+    ///
+    /// # Arguments
+    /// * `stub_directory` - The directory holding the stubs, none to use `PATH` again
+    #[cfg(test)]
+    pub fn set_stub_directory(stub_directory: Option<PathBuf>) {
+        STUB_DIRECTORY.with(|current_stub_directory| *current_stub_directory.borrow_mut() = stub_directory);
+    }
+
+    /// Writes the stub of a desktop program: it appends its arguments to the `calls` file of the directory,
+    /// one call per line, then prints the given output.
+    ///
+    /// # Arguments
+    /// * `stub_directory` - The directory holding the stubs
+    /// * `program` - The name of the program
+    /// * `output` - What the program prints, without single quotes
+    #[cfg(test)]
+    pub fn write_stub(stub_directory: &Path, program: &str, output: &str) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let script = format!(
+            "#!/bin/sh\necho \"$*\" >> \"{}\"\nprintf '%s' '{}'\n",
+            stub_directory.join("calls").display(),
+            output);
+        fs::write(stub_directory.join(program), script).unwrap();
+        fs::set_permissions(stub_directory.join(program), fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    /// Runs a command and waits for it. The command must exit successfully.
+    ///
+    /// # Arguments
+    /// * `command` - The command to run
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wallpapersetter::DesktopCommand;
+    ///
+    /// DesktopCommand::run(Command::new("gsettings").args(["set", "org.gnome.desktop.background", "picture-uri", uri]))?;
+    /// ```
+    pub fn run(command: &mut Command) -> Result<(), String> {
+        DesktopCommand::output(command).map(|_| ())
+    }
+
+    /// Runs a command, waits for it and returns its standard output. The command must exit successfully.
+    ///
+    /// # Arguments
+    /// * `command` - The command to run
+    pub fn output(command: &mut Command) -> Result<String, String> {
+        let program = command.get_program().to_string_lossy().to_string();
+        let output = match command.output() {
+            Err(error) => return Err(format!("Can't run {:?}: {:?}", program, error)),
+            Ok(output) => output,
+        };
+
+        if !output.status.success() {
+            return Err(format!(
+                "{:?} failed ({}): {}",
+                program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// Returns a location of the wallpaper unique to its content, for desktops which don't reload an image applied
+    /// again under the same location (ie: KDE Plasma, XFCE).
+    ///
+    /// The wallpaper is hard linked (or copied) next to itself under a hidden name, built from its modification time,
+    /// and the links of the previous wallpapers are removed.
+    ///
+    /// # Arguments
+    /// * `filename` - The location of the wallpaper
+    pub fn unique_filename(filename: &str) -> Result<String, String> {
+        let path = Path::new(filename);
+        let (parent_directory, file_name) = match (path.parent(), path.file_name()) {
+            (Some(parent_directory), Some(file_name)) => (parent_directory, file_name.to_string_lossy()),
+            _ => return Err(format!("Can't apply wallpaper {:?}: not a file", filename)),
+        };
+        let extension = path.extension().map(|extension| extension.to_string_lossy()).unwrap_or_default();
+
+        let modified = match fs::metadata(path).and_then(|metadata| metadata.modified()) {
+            Err(error) => return Err(format!("Can't read wallpaper {:?}: {:?}", filename, error)),
+            Ok(modified) => modified.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos(),
+        };
+
+        let unique_filename_prefix = format!("._{}_", file_name);
+        let unique_file_name = format!("{}{:x}.{}", unique_filename_prefix, modified, extension);
+        let unique_filename = parent_directory.join(&unique_file_name);
+
+        if let Ok(dir_entries) = fs::read_dir(parent_directory) {
+            for dir_entry in dir_entries.filter_map(|dir_entry| dir_entry.ok()) {
+                let dir_entry_name = dir_entry.file_name().to_string_lossy().to_string();
+                if dir_entry_name.starts_with(&unique_filename_prefix) && dir_entry_name != unique_file_name {
+                    let _ = fs::remove_file(dir_entry.path());
+                }
+            }
+        }
+
+        // Hard link avoids copying the file when possible
+        if !unique_filename.exists() && fs::hard_link(path, &unique_filename).is_err() {
+            if let Err(error) = fs::copy(path, &unique_filename) {
+                return Err(format!("Can't copy wallpaper to {:?}: {:?}", unique_filename, error));
+            }
+        }
+
+        Ok(unique_filename.to_string_lossy().to_string())
+    }
+
+    /// Returns the "file://" URI of a file, relative locations being resolved from the working directory.
+    ///
+    /// # Arguments
+    /// * `filename` - The location of the file
+    pub fn file_uri(filename: &str) -> String {
+        let path = Path::new(filename);
+        let absolute_path = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            path.canonicalize().unwrap_or(path.to_path_buf())
+        };
+
+        // Escapes characters which are not allowed in an URI path
+        let mut uri = String::from("file://");
+        for byte in absolute_path.to_string_lossy().bytes() {
+            if byte.is_ascii_alphanumeric() || b"/-._~!$&'()*+,;=:@".contains(&byte) {
+                uri.push(byte as char);
+            } else {
+                uri.push_str(&format!("%{:02X}", byte));
+            }
+        }
+
+        uri
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::process;
+
+    use super::DesktopCommand;
+
+    #[test]
+    fn unique_filename_changes_with_the_wallpaper() {
+        let directory = std::env::temp_dir().join(format!("bingwallpaper-unique-{}", process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let wallpaper_filename = directory.join(".bingwallpaper.jpg").to_string_lossy().to_string();
+        fs::write(&wallpaper_filename, b"monday").unwrap();
+        fs::write(directory.join(".bingwallpaper-dark.jpg"), b"dark").unwrap();
+
+        let monday_filename = DesktopCommand::unique_filename(&wallpaper_filename).unwrap();
+        assert!(monday_filename.contains("/._.bingwallpaper.jpg_") && monday_filename.ends_with(".jpg"));
+        assert_eq!(DesktopCommand::unique_filename(&wallpaper_filename).unwrap(), monday_filename);
+
+        // A new wallpaper gets a new location, the previous one is removed
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        fs::write(&wallpaper_filename, b"tuesday").unwrap();
+        let tuesday_filename = DesktopCommand::unique_filename(&wallpaper_filename).unwrap();
+        assert_ne!(tuesday_filename, monday_filename);
+        assert_eq!(fs::read(&tuesday_filename).unwrap(), b"tuesday");
+        assert!(!std::path::Path::new(&monday_filename).exists());
+        assert!(directory.join(".bingwallpaper-dark.jpg").exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn file_uri_escapes_the_path() {
+        assert_eq!(
+            DesktopCommand::file_uri("/home/user/My Wallpapers/été #1.jpg"),
+            "file:///home/user/My%20Wallpapers/%C3%A9t%C3%A9%20%231.jpg");
+        assert_eq!(DesktopCommand::file_uri("/tmp/a-b_c.d~(1)"), "file:///tmp/a-b_c.d~(1)");
+
+        // Relative locations are resolved from the working directory
+        let current_directory = std::env::current_dir().unwrap().canonicalize().unwrap();
+        assert_eq!(
+            DesktopCommand::file_uri("Cargo.toml"),
+            format!("file://{}/Cargo.toml", current_directory.to_string_lossy()));
+    }
+}
@@ -0,0 +1,57 @@
+use crate::bingwallpaper::configuration::WallpaperFitMode;
+use crate::bingwallpaper::wallpapersetter::{DesktopCommand, WallpaperSetter};
+
+/// Applies the wallpaper on LXQt (`pcmanfm-qt`) and LXDE (`pcmanfm`), which draw the desktop with their file manager.
+pub struct PcmanfmWallpaperSetter {
+    is_qt: bool,
+    fit_mode: Option<WallpaperFitMode>,
+}
+
+impl PcmanfmWallpaperSetter {
+    /// Creates a new instance for LXQt.
+    ///
+    /// # Arguments
+    /// * `fit_mode` - How the wallpaper fills the screen, unchanged if not specified
+    pub fn lxqt(fit_mode: Option<WallpaperFitMode>) -> PcmanfmWallpaperSetter {
+        PcmanfmWallpaperSetter { is_qt: true, fit_mode }
+    }
+
+    /// Creates a new instance for LXDE.
+    ///
+    /// # Arguments
+    /// * `fit_mode` - How the wallpaper fills the screen, unchanged if not specified
+    pub fn lxde(fit_mode: Option<WallpaperFitMode>) -> PcmanfmWallpaperSetter {
+        PcmanfmWallpaperSetter { is_qt: false, fit_mode }
+    }
+
+    /// Returns the `--wallpaper-mode` value of a fit mode. Each file manager names the modes differently.
+    fn wallpaper_mode(&self, fit_mode: WallpaperFitMode) -> &'static str {
+        match fit_mode {
+            WallpaperFitMode::Zoom if self.is_qt => "zoom",
+            WallpaperFitMode::Zoom => "crop",
+            WallpaperFitMode::Fit => "fit",
+            WallpaperFitMode::Stretch => "stretch",
+            WallpaperFitMode::Center => "center",
+            WallpaperFitMode::Tile => "tile",
+            WallpaperFitMode::Span if self.is_qt => "stretch",
+            WallpaperFitMode::Span => "screen",
+        }
+    }
+}
+
+impl WallpaperSetter for PcmanfmWallpaperSetter {
+    fn name(&self) -> &'static str {
+        if self.is_qt { "LXQt" } else { "LXDE" }
+    }
+
+    fn set_wallpaper(&self, wallpaper_filename: &str, _dark_wallpaper_filename: Option<&str>) -> Result<(), String> {
+        let mut command = DesktopCommand::command(if self.is_qt { "pcmanfm-qt" } else { "pcmanfm" });
+        command.arg(format!("--set-wallpaper={}", wallpaper_filename));
+
+        if let Some(fit_mode) = self.fit_mode {
+            command.arg(format!("--wallpaper-mode={}", self.wallpaper_mode(fit_mode)));
+        }
+
+        DesktopCommand::run(&mut command)
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use zbus::blocking::{connection, Connection, MessageIterator};
+use zbus::message::Type;
+use zbus::zvariant::{Fd, OwnedObjectPath, OwnedValue, Value};
+use zbus::MatchRule;
+
+use crate::bingwallpaper::configuration::{PortalConfiguration, PortalSetOn};
+use crate::bingwallpaper::wallpapersetter::WallpaperSetter;
+
+/// Well-known name of the XDG Desktop Portal service.
+const PORTAL_SERVICE_NAME: &str = "org.freedesktop.portal.Desktop";
+
+/// Maximum time to wait for the portal response.
+const PORTAL_RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Maximum time to wait for the portal response, when the user has to confirm the preview.
+const PORTAL_PREVIEW_RESPONSE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Applies the wallpaper through the XDG Desktop Portal, the only way from a sandbox (ie: Flatpak, Snap).
+///
+/// The wallpaper is sent as a file descriptor, the portal doesn't need to access its location.
+pub struct PortalWallpaperSetter {
+    portal_configuration: PortalConfiguration,
+
+    /// Address of the D-Bus bus providing the portal, the session bus if none.
+    bus_address: Option<String>,
+
+    /// Maximum time to wait for the portal response, depending on the preview if none.
+    response_timeout: Option<Duration>,
+}
+
+impl PortalWallpaperSetter {
+    /// Creates a new instance.
+    ///
+    /// # Arguments
+    /// * `portal_configuration` - The portal configuration to use
+    pub fn new(portal_configuration: PortalConfiguration) -> PortalWallpaperSetter {
+        PortalWallpaperSetter { portal_configuration, bus_address: None, response_timeout: None }
+    }
+
+    /// Connects to the bus providing the portal.
+    fn connect(&self) -> zbus::Result<Connection> {
+        match &self.bus_address {
+            None => Connection::session(),
+            Some(bus_address) => connection::Builder::address(bus_address.as_str())?.build(),
+        }
+    }
+
+    /// Calls `SetWallpaperFile`, then waits for the response of the request.
+    fn set_wallpaper_file(&self, filename: &str, set_on: PortalSetOn) -> Result<(), String> {
+        let connection = match self.connect() {
+            Err(error) => return Err(format!("Can't connect to D-Bus session bus: {:?}", error)),
+            Ok(connection) => connection,
+        };
+
+        let file = match File::open(filename) {
+            Err(error) => return Err(format!("Can't open wallpaper {:?}: {:?}", filename, error)),
+            Ok(file) => file,
+        };
+
+        // Responses are listened to before the call, so that a fast response can't be missed
+        let match_rule = MatchRule::builder()
+            .msg_type(Type::Signal)
+            .interface("org.freedesktop.portal.Request")
+            .and_then(|builder| builder.member("Response"))
+            .map(|builder| builder.build());
+        let responses = match match_rule.and_then(|rule| MessageIterator::for_match_rule(rule, &connection, Some(16))) {
+            Err(error) => return Err(format!("Can't listen to desktop portal responses: {:?}", error)),
+            Ok(responses) => responses,
+        };
+
+        let show_preview = self.portal_configuration.show_preview.unwrap_or(false);
+        let set_on = match set_on {
+            PortalSetOn::Background => "background",
+            PortalSetOn::Lockscreen => "lockscreen",
+            PortalSetOn::Both => "both",
+        };
+        let handle_token = format!(
+            "bingwallpaper{}",
+            SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_micros());
+
+        let mut options: HashMap<&str, Value> = HashMap::new();
+        options.insert("handle_token", Value::from(handle_token.as_str()));
+        options.insert("set-on", Value::from(set_on));
+        options.insert("show-preview", Value::from(show_preview));
+
+        let reply = connection.call_method(
+            Some(PORTAL_SERVICE_NAME),
+            "/org/freedesktop/portal/desktop",
+            Some("org.freedesktop.portal.Wallpaper"),
+            "SetWallpaperFile",
+            &("", Fd::from(&file), options));
+        let request_handle = match reply.and_then(|reply| reply.body().deserialize::<OwnedObjectPath>()) {
+            Err(error) => return Err(format!("Can't call desktop portal: {:?}", error)),
+            Ok(request_handle) => request_handle,
+        };
+
+        // The response comes as a signal of the request object, once applied (or confirmed by the user)
+        let (response_sender, response_receiver) = mpsc::channel();
+        let response_waiter = thread::spawn(move || {
+            let response = responses
+                .filter_map(|message| message.ok())
+                .find(|message| message.header().path().is_some_and(|path| path.as_str() == request_handle.as_str()))
+                .map(|message| message.body().deserialize::<(u32, HashMap<String, OwnedValue>)>());
+            let _ = response_sender.send(response);
+        });
+
+        let timeout = self.response_timeout.unwrap_or(
+            if show_preview { PORTAL_PREVIEW_RESPONSE_TIMEOUT } else { PORTAL_RESPONSE_TIMEOUT });
+        let response = response_receiver.recv_timeout(timeout);
+        if response.is_err() {
+            // Closing the connection ends the responses, so that the waiting thread doesn't outlive the request
+            let _ = connection.close();
+            let _ = response_waiter.join();
+        }
+
+        match response {
+            Err(_) => Err("Can't change wallpaper through desktop portal: no response".to_string()),
+            Ok(None) => Err("Can't change wallpaper through desktop portal: connection closed".to_string()),
+            Ok(Some(Err(error))) => Err(format!("Can't read desktop portal response: {:?}", error)),
+            Ok(Some(Ok((0, _)))) => Ok(()),
+            Ok(Some(Ok((1, _)))) => Err("Can't change wallpaper through desktop portal: cancelled by user".to_string()),
+            Ok(Some(Ok((response, _)))) => {
+                Err(format!("Can't change wallpaper through desktop portal: failed (response {})", response))
+            }
+        }
+    }
+}
+
+impl WallpaperSetter for PortalWallpaperSetter {
+    fn name(&self) -> &'static str {
+        "XDG Desktop Portal"
+    }
+
+    fn set_wallpaper(&self, wallpaper_filename: &str, _dark_wallpaper_filename: Option<&str>) -> Result<(), String> {
+        let set_on = self.portal_configuration.set_on.unwrap_or(PortalSetOn::Background);
+        self.set_wallpaper_file(wallpaper_filename, set_on)
+    }
+
+    fn set_lock_screen(&self, lock_screen_filename: &str) -> Result<(), String> {
+        self.set_wallpaper_file(lock_screen_filename, PortalSetOn::Lockscreen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{BufRead, BufReader, Read};
+    use std::os::fd;
+    use std::process::{self, Child, Command, Stdio};
+    use std::sync::mpsc::{self, Receiver};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use zbus::blocking::{connection, MessageIterator};
+    use zbus::message::Type;
+    use zbus::zvariant::{OwnedFd, OwnedObjectPath, OwnedValue};
+
+    use super::PortalWallpaperSetter;
+    use crate::bingwallpaper::configuration::{PortalConfiguration, PortalSetOn};
+    use crate::bingwallpaper::wallpapersetter::WallpaperSetter;
+
+    /// Private session bus, stopped when dropped.
+    struct SessionBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl SessionBus {
+        /// Starts a bus daemon, none if `dbus-daemon` is not installed.
+        fn start() -> Option<SessionBus> {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()?;
+
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take()?).read_line(&mut address).ok()?;
+            Some(SessionBus { daemon, address: address.trim().to_string() })
+        }
+    }
+
+    impl Drop for SessionBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    /// A `SetWallpaperFile` call received by the stub portal.
+    struct PortalCall {
+        content: String,
+        options: HashMap<String, OwnedValue>,
+    }
+
+    /// Serves a stub portal on the bus: each call is answered with the next response code, none to never answer.
+    fn serve_portal(address: &str, responses: Vec<Option<u32>>) -> Receiver<PortalCall> {
+        let connection = connection::Builder::address(address)
+            .and_then(|builder| builder.name("org.freedesktop.portal.Desktop"))
+            .and_then(|builder| builder.build())
+            .unwrap();
+        let (call_sender, call_receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut responses = responses.into_iter();
+            for message in MessageIterator::from(&connection).filter_map(|message| message.ok()) {
+                let header = message.header();
+                if header.message_type() != Type::MethodCall
+                    || header.member().is_none_or(|member| member.as_str() != "SetWallpaperFile") {
+                    continue;
+                }
+
+                let (_, wallpaper_fd, options) = message
+                    .body()
+                    .deserialize::<(String, OwnedFd, HashMap<String, OwnedValue>)>()
+                    .unwrap();
+                let mut content = String::new();
+                File::from(fd::OwnedFd::from(wallpaper_fd)).read_to_string(&mut content).unwrap();
+
+                let handle_token = String::try_from(options["handle_token"].try_clone().unwrap()).unwrap();
+                let request_handle = format!("/org/freedesktop/portal/desktop/request/1_1/{}", handle_token);
+                connection.reply(&header, &OwnedObjectPath::try_from(request_handle.as_str()).unwrap()).unwrap();
+                if let Some(response) = responses.next().flatten() {
+                    let results: HashMap<String, OwnedValue> = HashMap::new();
+                    let interface = "org.freedesktop.portal.Request";
+                    let body = (response, results);
+                    connection.emit_signal(None::<&str>, request_handle.as_str(), interface, "Response", &body).unwrap();
+                }
+
+                let _ = call_sender.send(PortalCall { content, options });
+            }
+        });
+
+        call_receiver
+    }
+
+    #[test]
+    fn set_wallpaper_through_portal() {
+        let session_bus = match SessionBus::start() {
+            None => return println!("dbus-daemon is not installed, skipped"),
+            Some(session_bus) => session_bus,
+        };
+        let directory = env::temp_dir().join(format!("bingwallpaper-portal-{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let wallpaper_filename = directory.join("wallpaper.jpg").to_string_lossy().to_string();
+        fs::write(&wallpaper_filename, "wallpaper").unwrap();
+
+        let calls = serve_portal(&session_bus.address, vec![Some(0), Some(1), Some(2), None, Some(0)]);
+        let portal_wallpaper_setter = |set_on, show_preview| PortalWallpaperSetter {
+            portal_configuration: PortalConfiguration { set_on, show_preview },
+            bus_address: Some(session_bus.address.clone()),
+            response_timeout: Some(Duration::from_millis(500)),
+        };
+        let option = |call: &PortalCall, name: &str| -> OwnedValue { call.options[name].try_clone().unwrap() };
+
+        // Applied: the wallpaper is sent as a file descriptor, with the configured options
+        portal_wallpaper_setter(None, None).set_wallpaper(&wallpaper_filename, None).unwrap();
+        let call = calls.recv().unwrap();
+        assert_eq!(call.content, "wallpaper");
+        assert_eq!(String::try_from(option(&call, "set-on")).unwrap(), "background");
+        assert!(!bool::try_from(option(&call, "show-preview")).unwrap());
+
+        // Cancelled by the user
+        let error = portal_wallpaper_setter(Some(PortalSetOn::Both), Some(true))
+            .set_wallpaper(&wallpaper_filename, None)
+            .unwrap_err();
+        assert_eq!(error, "Can't change wallpaper through desktop portal: cancelled by user");
+        let call = calls.recv().unwrap();
+        assert_eq!(String::try_from(option(&call, "set-on")).unwrap(), "both");
+        assert!(bool::try_from(option(&call, "show-preview")).unwrap());
+
+        // Failed
+        let error = portal_wallpaper_setter(None, None).set_lock_screen(&wallpaper_filename).unwrap_err();
+        assert_eq!(error, "Can't change wallpaper through desktop portal: failed (response 2)");
+        assert_eq!(String::try_from(option(&calls.recv().unwrap(), "set-on")).unwrap(), "lockscreen");
+
+        // No response: the connection is closed once the timeout has elapsed, and the next requests still work
+        let started_at = Instant::now();
+        let error = portal_wallpaper_setter(None, None).set_wallpaper(&wallpaper_filename, None).unwrap_err();
+        assert_eq!(error, "Can't change wallpaper through desktop portal: no response");
+        assert!(started_at.elapsed() < Duration::from_secs(5), "{:?}", started_at.elapsed());
+        calls.recv().unwrap();
+
+        portal_wallpaper_setter(None, None).set_wallpaper(&wallpaper_filename, None).unwrap();
+        calls.recv().unwrap();
+
+        // Unreadable wallpaper: no call
+        let error = portal_wallpaper_setter(None, None)
+            .set_wallpaper(&directory.join("missing.jpg").to_string_lossy(), None)
+            .unwrap_err();
+        assert!(error.starts_with("Can't open wallpaper"), "{}", error);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
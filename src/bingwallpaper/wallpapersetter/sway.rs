@@ -0,0 +1,111 @@
+use crate::bingwallpaper::configuration::{WallpaperFitMode, WaylandConfiguration};
+use crate::bingwallpaper::wallpapersetter::{DesktopCommand, WallpaperSetter};
+
+/// Applies the wallpaper on sway with `swaymsg`, to every output or to the configured ones.
+pub struct SwayWallpaperSetter {
+    wayland_configuration: WaylandConfiguration,
+    fit_mode: Option<WallpaperFitMode>,
+}
+
+impl SwayWallpaperSetter {
+    /// Creates a new instance.
+    ///
+    /// # Arguments
+    /// * `wayland_configuration` - The Wayland configuration to use
+    /// * `fit_mode` - How the wallpaper fills the screen, `ZOOM` if not specified
+    pub fn new(wayland_configuration: WaylandConfiguration, fit_mode: Option<WallpaperFitMode>) -> SwayWallpaperSetter {
+        SwayWallpaperSetter { wayland_configuration, fit_mode }
+    }
+
+    /// Returns the background mode of a fit mode, shared by sway and swaybg. There is no mode spanning outputs.
+    pub(crate) fn background_mode(fit_mode: Option<WallpaperFitMode>) -> &'static str {
+        match fit_mode {
+            None | Some(WallpaperFitMode::Zoom) | Some(WallpaperFitMode::Span) => "fill",
+            Some(WallpaperFitMode::Fit) => "fit",
+            Some(WallpaperFitMode::Stretch) => "stretch",
+            Some(WallpaperFitMode::Center) => "center",
+            Some(WallpaperFitMode::Tile) => "tile",
+        }
+    }
+
+    /// Quotes an argument of a sway command.
+    fn quote(value: &str) -> String {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+impl WallpaperSetter for SwayWallpaperSetter {
+    fn name(&self) -> &'static str {
+        "sway"
+    }
+
+    fn set_wallpaper(&self, wallpaper_filename: &str, _dark_wallpaper_filename: Option<&str>) -> Result<(), String> {
+        let mode = SwayWallpaperSetter::background_mode(self.fit_mode);
+
+        // Every output first, then the configured ones, as a single list of commands
+        let mut commands = vec![format!("output * bg {} {}", SwayWallpaperSetter::quote(wallpaper_filename), mode)];
+        for (output_name, output_filename) in self.wayland_configuration.output_filenames.iter().flatten() {
+            commands.push(format!(
+                "output {} bg {} {}",
+                SwayWallpaperSetter::quote(output_name),
+                SwayWallpaperSetter::quote(output_filename),
+                mode));
+        }
+
+        DesktopCommand::run(DesktopCommand::command("swaymsg").arg(commands.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use super::SwayWallpaperSetter;
+    use crate::bingwallpaper::configuration::{WallpaperFitMode, WaylandConfiguration};
+    use crate::bingwallpaper::wallpapersetter::{DesktopCommand, WallpaperSetter};
+
+    #[test]
+    fn background_mode() {
+        let fit_mode_backgrounds = [
+            (None, "fill"),
+            (Some(WallpaperFitMode::Zoom), "fill"),
+            (Some(WallpaperFitMode::Span), "fill"),
+            (Some(WallpaperFitMode::Fit), "fit"),
+            (Some(WallpaperFitMode::Stretch), "stretch"),
+            (Some(WallpaperFitMode::Center), "center"),
+            (Some(WallpaperFitMode::Tile), "tile"),
+        ];
+
+        for (fit_mode, background_mode) in fit_mode_backgrounds {
+            assert_eq!(SwayWallpaperSetter::background_mode(fit_mode), background_mode);
+        }
+    }
+
+    #[test]
+    fn every_output_then_the_configured_ones() {
+        let directory = env::temp_dir().join(format!("bingwallpaper-sway-{}", process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        DesktopCommand::write_stub(&directory, "swaymsg", "");
+
+        let wayland_configuration = WaylandConfiguration {
+            output_filenames: Some(BTreeMap::from([("HDMI-A-1".to_string(), "/home/user/portrait.jpg".to_string())])),
+            ..WaylandConfiguration::default()
+        };
+        DesktopCommand::set_stub_directory(Some(directory.clone()));
+        let result = SwayWallpaperSetter::new(wayland_configuration, Some(WallpaperFitMode::Fit))
+            .set_wallpaper("/home/user/bing \"wallpaper\".jpg", None);
+        DesktopCommand::set_stub_directory(None);
+
+        result.unwrap();
+        assert_eq!(
+            fs::read_to_string(directory.join("calls")).unwrap(),
+            "output * bg \"/home/user/bing \\\"wallpaper\\\".jpg\" fit, \
+            output \"HDMI-A-1\" bg \"/home/user/portrait.jpg\" fit\n");
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,47 @@
+use crate::bingwallpaper::configuration::{WallpaperFitMode, WaylandConfiguration};
+use crate::bingwallpaper::wallpapersetter::{DesktopCommand, SwayWallpaperSetter, WallpaperSetter};
+use crate::bingwallpaper::BackgroundProcess;
+
+/// The swaybg process started by this instance, stopped when the wallpaper changes again.
+static SWAYBG_PROCESS: BackgroundProcess = BackgroundProcess::new("swaybg", &["swaybg"]);
+
+/// Applies the wallpaper with `swaybg`, on Wayland compositors without wallpaper IPC (ie: river, labwc).
+///
+/// swaybg displays the wallpaper as long as it runs: a new process is started for each wallpaper, then the previous
+/// one is stopped.
+pub struct SwaybgWallpaperSetter {
+    wayland_configuration: WaylandConfiguration,
+    fit_mode: Option<WallpaperFitMode>,
+}
+
+impl SwaybgWallpaperSetter {
+    /// Creates a new instance.
+    ///
+    /// # Arguments
+    /// * `wayland_configuration` - The Wayland configuration to use
+    /// * `fit_mode` - How the wallpaper fills the screen, `ZOOM` if not specified
+    pub fn new(wayland_configuration: WaylandConfiguration,
+               fit_mode: Option<WallpaperFitMode>) -> SwaybgWallpaperSetter {
+        SwaybgWallpaperSetter { wayland_configuration, fit_mode }
+    }
+}
+
+impl WallpaperSetter for SwaybgWallpaperSetter {
+    fn name(&self) -> &'static str {
+        "swaybg"
+    }
+
+    fn set_wallpaper(&self, wallpaper_filename: &str, _dark_wallpaper_filename: Option<&str>) -> Result<(), String> {
+        let mode = SwayWallpaperSetter::background_mode(self.fit_mode);
+
+        // Every output first, the configured ones take precedence
+        let mut command = DesktopCommand::command("swaybg");
+        command.args(["--output", "*", "--image", wallpaper_filename, "--mode", mode]);
+        for (output_name, output_filename) in self.wayland_configuration.output_filenames.iter().flatten() {
+            command.args(["--output", output_name, "--image", output_filename, "--mode", mode]);
+        }
+
+        // The new wallpaper is displayed, the previous one can go
+        SWAYBG_PROCESS.start(&mut command, wallpaper_filename.to_string())
+    }
+}
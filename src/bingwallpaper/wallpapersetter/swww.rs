@@ -0,0 +1,191 @@
+use crate::bingwallpaper::configuration::{SwwwTransitionType, WallpaperFitMode, WaylandConfiguration};
+use crate::bingwallpaper::wallpapersetter::{DesktopCommand, WallpaperSetter};
+
+/// Applies the wallpaper with `swww`, on any Wayland compositor, with an animated transition.
+pub struct SwwwWallpaperSetter {
+    wayland_configuration: WaylandConfiguration,
+    fit_mode: Option<WallpaperFitMode>,
+}
+
+impl SwwwWallpaperSetter {
+    /// Creates a new instance.
+    ///
+    /// # Arguments
+    /// * `wayland_configuration` - The Wayland configuration to use
+    /// * `fit_mode` - How the wallpaper fills the screen, `ZOOM` if not specified
+    pub fn new(wayland_configuration: WaylandConfiguration, fit_mode: Option<WallpaperFitMode>) -> SwwwWallpaperSetter {
+        SwwwWallpaperSetter { wayland_configuration, fit_mode }
+    }
+
+    /// Returns the names of the outputs known by the swww daemon.
+    fn outputs() -> Result<Vec<String>, String> {
+        // One output per line (ie: "eDP-1: 1920x1080, scale: 1, currently displaying: color: 000000"),
+        // recent versions start the line with ": "
+        let outputs = DesktopCommand::output(DesktopCommand::command("swww").arg("query"))?;
+
+        Ok(outputs
+            .lines()
+            .filter_map(|line| line.trim().trim_start_matches(':').split(':').next())
+            .map(|output_name| output_name.trim().to_string())
+            .filter(|output_name| !output_name.is_empty())
+            .collect())
+    }
+
+    /// Displays an image on some outputs (all of them if empty).
+    fn display(&self, filename: &str, output_names: &[String]) -> Result<(), String> {
+        let resize = match self.fit_mode {
+            Some(WallpaperFitMode::Fit) => "fit",
+            Some(WallpaperFitMode::Center) | Some(WallpaperFitMode::Tile) => "no",
+            _ => "crop",
+        };
+
+        let mut command = DesktopCommand::command("swww");
+        command.arg("img").arg(filename).args(["--resize", resize]);
+
+        if let Some(transition_type) = self.wayland_configuration.transition_type {
+            command.args(["--transition-type", SwwwWallpaperSetter::transition_name(transition_type)]);
+        }
+        if let Some(transition_duration) = self.wayland_configuration.transition_duration {
+            command.args(["--transition-duration", &transition_duration.to_string()]);
+        }
+        if !output_names.is_empty() {
+            command.args(["--outputs", &output_names.join(",")]);
+        }
+
+        DesktopCommand::run(&mut command)
+    }
+
+    /// Returns the swww name of a transition type.
+    fn transition_name(transition_type: SwwwTransitionType) -> &'static str {
+        match transition_type {
+            SwwwTransitionType::None => "none",
+            SwwwTransitionType::Simple => "simple",
+            SwwwTransitionType::Fade => "fade",
+            SwwwTransitionType::Left => "left",
+            SwwwTransitionType::Right => "right",
+            SwwwTransitionType::Top => "top",
+            SwwwTransitionType::Bottom => "bottom",
+            SwwwTransitionType::Wipe => "wipe",
+            SwwwTransitionType::Wave => "wave",
+            SwwwTransitionType::Grow => "grow",
+            SwwwTransitionType::Center => "center",
+            SwwwTransitionType::Outer => "outer",
+            SwwwTransitionType::Any => "any",
+            SwwwTransitionType::Random => "random",
+        }
+    }
+}
+
+impl WallpaperSetter for SwwwWallpaperSetter {
+    fn name(&self) -> &'static str {
+        "swww"
+    }
+
+    fn set_wallpaper(&self, wallpaper_filename: &str, _dark_wallpaper_filename: Option<&str>) -> Result<(), String> {
+        let output_filenames = self.wayland_configuration.output_filenames.clone().unwrap_or_default();
+        if output_filenames.is_empty() {
+            return self.display(wallpaper_filename, &[]);
+        }
+
+        // Each output is animated once: the wallpaper only goes to the outputs without their own image
+        let outputs = match SwwwWallpaperSetter::outputs() {
+            Err(error) => return Err(format!("Can't list swww outputs, swww-daemon must be running: {}", error)),
+            Ok(outputs) => outputs,
+        };
+        let other_outputs: Vec<String> = outputs
+            .into_iter()
+            .filter(|output_name| !output_filenames.contains_key(output_name))
+            .collect();
+        if !other_outputs.is_empty() {
+            self.display(wallpaper_filename, &other_outputs)?;
+        }
+
+        for (output_name, output_filename) in &output_filenames {
+            self.display(output_filename, std::slice::from_ref(output_name))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use super::SwwwWallpaperSetter;
+    use crate::bingwallpaper::configuration::{SwwwTransitionType, WallpaperFitMode, WaylandConfiguration};
+    use crate::bingwallpaper::wallpapersetter::{DesktopCommand, WallpaperSetter};
+
+    #[test]
+    fn outputs_of_both_query_formats() {
+        let directory = env::temp_dir().join(format!("bingwallpaper-swww-query-{}", process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        DesktopCommand::set_stub_directory(Some(directory.clone()));
+
+        DesktopCommand::write_stub(
+            &directory,
+            "swww",
+            "eDP-1: 1920x1080, scale: 1, currently displaying: color: 000000\n\
+            HDMI-A-1: 2560x1440, scale: 1, currently displaying: image: /home/user/bingwallpaper.jpg\n");
+        let previous_outputs = SwwwWallpaperSetter::outputs();
+
+        DesktopCommand::write_stub(
+            &directory,
+            "swww",
+            ": eDP-1: 1920x1080, scale: 1, currently displaying: color: 000000\n\
+            : HDMI-A-1: 2560x1440, scale: 1.5, currently displaying: image: /home/user/bingwallpaper.jpg\n\n");
+        let outputs = SwwwWallpaperSetter::outputs();
+
+        DesktopCommand::set_stub_directory(None);
+
+        assert_eq!(previous_outputs.unwrap(), ["eDP-1", "HDMI-A-1"]);
+        assert_eq!(outputs.unwrap(), ["eDP-1", "HDMI-A-1"]);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn each_output_is_animated_once() {
+        let directory = env::temp_dir().join(format!("bingwallpaper-swww-img-{}", process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        DesktopCommand::write_stub(&directory, "swww", ": eDP-1: 1920x1080\n: HDMI-A-1: 2560x1440\n: DP-2: 1920x1080\n");
+
+        let wayland_configuration = WaylandConfiguration {
+            output_filenames: Some(BTreeMap::from([("HDMI-A-1".to_string(), "/home/user/portrait.jpg".to_string())])),
+            transition_type: Some(SwwwTransitionType::Wipe),
+            transition_duration: Some(1.5),
+        };
+        DesktopCommand::set_stub_directory(Some(directory.clone()));
+        let result = SwwwWallpaperSetter::new(wayland_configuration, Some(WallpaperFitMode::Fit))
+            .set_wallpaper("/home/user/bingwallpaper.jpg", None);
+        let calls = fs::read_to_string(directory.join("calls")).unwrap();
+        fs::remove_file(directory.join("calls")).unwrap();
+
+        // Without output images: a single call, to every output
+        let result_without_outputs = SwwwWallpaperSetter::new(WaylandConfiguration::default(), None)
+            .set_wallpaper("/home/user/bingwallpaper.jpg", None);
+        let calls_without_outputs = fs::read_to_string(directory.join("calls")).unwrap();
+        DesktopCommand::set_stub_directory(None);
+
+        result.unwrap();
+        assert_eq!(
+            calls.lines().collect::<Vec<&str>>(),
+            [
+                "query",
+                "img /home/user/bingwallpaper.jpg --resize fit --transition-type wipe --transition-duration 1.5 \
+                --outputs eDP-1,DP-2",
+                "img /home/user/portrait.jpg --resize fit --transition-type wipe --transition-duration 1.5 \
+                --outputs HDMI-A-1",
+            ]);
+
+        result_without_outputs.unwrap();
+        assert_eq!(calls_without_outputs, "img /home/user/bingwallpaper.jpg --resize crop\n");
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
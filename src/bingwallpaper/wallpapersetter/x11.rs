@@ -0,0 +1,333 @@
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, RgbImage};
+use x11rb::connection::Connection;
+use x11rb::errors::ReplyOrIdError;
+use x11rb::protocol::randr::ConnectionExt as _;
+use x11rb::protocol::xinerama::ConnectionExt as _;
+use x11rb::protocol::xproto::{
+    AtomEnum, ChangeWindowAttributesAux, CloseDown, ConnectionExt as _, CreateGCAux, ImageFormat, ImageOrder, PropMode,
+    Screen, Setup, VisualClass,
+};
+use x11rb::wrapper::ConnectionExt as _;
+
+use crate::bingwallpaper::configuration::WallpaperFitMode;
+use crate::bingwallpaper::wallpapersetter::WallpaperSetter;
+
+/// Root window properties holding the wallpaper pixmap, read by compositors and transparent terminals.
+const ROOT_PIXMAP_PROPERTIES: [&str; 2] = ["_XROOTPMAP_ID", "ESETROOT_PMAP_ID"];
+
+/// Applies the wallpaper on the X11 root window, for window managers without desktop (ie: i3, bspwm, awesome, dwm).
+///
+/// The image is drawn into a pixmap covering every monitor, which is kept by the X server once disconnected.
+/// Like `Esetroot` and `feh`, the pixmap of the previous wallpaper is freed when a new one is applied.
+pub struct X11WallpaperSetter {
+    fit_mode: Option<WallpaperFitMode>,
+}
+
+/// Pixel layout of the root window, as expected by `PutImage`.
+struct PixelFormat {
+    depth: u8,
+    bytes_per_pixel: usize,
+    scanline_pad: usize,
+    masks: [u32; 3],
+    is_msb_first: bool,
+}
+
+impl X11WallpaperSetter {
+    /// Creates a new instance.
+    ///
+    /// # Arguments
+    /// * `fit_mode` - How the wallpaper fills each monitor, `ZOOM` if not specified
+    pub fn new(fit_mode: Option<WallpaperFitMode>) -> X11WallpaperSetter {
+        X11WallpaperSetter { fit_mode }
+    }
+
+    /// Returns the area (x, y, width, height) of each monitor, from RandR, then Xinerama, else the whole screen.
+    fn monitors(connection: &impl Connection, screen: &Screen) -> Vec<(i64, i64, u32, u32)> {
+        let randr_monitors: Vec<(i64, i64, u32, u32)> = connection
+            .randr_get_monitors(screen.root, true)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .map(|reply| {
+                reply
+                    .monitors
+                    .iter()
+                    .map(|monitor| (monitor.x as i64, monitor.y as i64, monitor.width as u32, monitor.height as u32))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if !randr_monitors.is_empty() {
+            return randr_monitors;
+        }
+
+        let xinerama_screens: Vec<(i64, i64, u32, u32)> = connection
+            .xinerama_query_screens()
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .map(|reply| {
+                reply
+                    .screen_info
+                    .iter()
+                    .map(|info| (info.x_org as i64, info.y_org as i64, info.width as u32, info.height as u32))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if !xinerama_screens.is_empty() {
+            return xinerama_screens;
+        }
+
+        vec![(0, 0, screen.width_in_pixels as u32, screen.height_in_pixels as u32)]
+    }
+
+    /// Renders the wallpaper of the whole screen, each monitor showing the image with the fit mode.
+    fn render(&self, image: &DynamicImage, dimensions: (u32, u32), monitors: &[(i64, i64, u32, u32)]) -> RgbImage {
+        let mut screen_image = RgbImage::new(dimensions.0, dimensions.1);
+
+        let areas = if self.fit_mode == Some(WallpaperFitMode::Span) {
+            vec![(0, 0, dimensions.0, dimensions.1)]
+        } else {
+            monitors.to_vec()
+        };
+
+        for (x, y, width, height) in areas {
+            let mut area_image = RgbImage::new(width, height);
+            match self.fit_mode.unwrap_or(WallpaperFitMode::Zoom) {
+                WallpaperFitMode::Zoom | WallpaperFitMode::Span => {
+                    area_image = image.resize_to_fill(width, height, FilterType::Lanczos3).into_rgb8();
+                }
+                WallpaperFitMode::Stretch => {
+                    area_image = image.resize_exact(width, height, FilterType::Lanczos3).into_rgb8();
+                }
+                WallpaperFitMode::Fit => {
+                    let resized_image = image.resize(width, height, FilterType::Lanczos3).into_rgb8();
+                    X11WallpaperSetter::center(&mut area_image, &resized_image);
+                }
+                WallpaperFitMode::Center => X11WallpaperSetter::center(&mut area_image, &image.to_rgb8()),
+                WallpaperFitMode::Tile => imageops::tile(&mut area_image, &image.to_rgb8()),
+            }
+
+            imageops::replace(&mut screen_image, &area_image, x, y);
+        }
+
+        screen_image
+    }
+
+    /// Draws an image at the center of another one, cropped if larger.
+    fn center(bottom: &mut RgbImage, top: &RgbImage) {
+        let x = (bottom.width() as i64 - top.width() as i64) / 2;
+        let y = (bottom.height() as i64 - top.height() as i64) / 2;
+        imageops::replace(bottom, top, x, y);
+    }
+
+    /// Returns the pixel layout of the root window. Only true color visuals are supported.
+    fn pixel_format(setup: &Setup, screen: &Screen) -> Result<PixelFormat, String> {
+        let visual = screen
+            .allowed_depths
+            .iter()
+            .flat_map(|depth| depth.visuals.iter())
+            .find(|visual| visual.visual_id == screen.root_visual);
+        let format = setup.pixmap_formats.iter().find(|format| format.depth == screen.root_depth);
+
+        match (visual, format) {
+            (Some(visual), Some(format))
+                if (visual.class == VisualClass::TRUE_COLOR || visual.class == VisualClass::DIRECT_COLOR)
+                    && [16, 24, 32].contains(&format.bits_per_pixel) =>
+            {
+                Ok(PixelFormat {
+                    depth: screen.root_depth,
+                    bytes_per_pixel: format.bits_per_pixel as usize / 8,
+                    scanline_pad: format.scanline_pad as usize / 8,
+                    masks: [visual.red_mask, visual.green_mask, visual.blue_mask],
+                    is_msb_first: setup.image_byte_order == ImageOrder::MSB_FIRST,
+                })
+            }
+            _ => Err(format!("Can't change X11 root window wallpaper: unsupported visual (depth {})", screen.root_depth)),
+        }
+    }
+
+    /// Converts the image into the pixel layout of the root window. Returns the pixels and the length of a row.
+    fn encode(image: &RgbImage, pixel_format: &PixelFormat) -> (Vec<u8>, usize) {
+        let row_length = (image.width() as usize * pixel_format.bytes_per_pixel).div_ceil(pixel_format.scanline_pad)
+            * pixel_format.scanline_pad;
+
+        // Each channel is scaled to the size of its mask (ie: 5 bits for red in 16 bits visuals)
+        let channels: Vec<(u32, u32)> = pixel_format
+            .masks
+            .iter()
+            .map(|mask| (mask.trailing_zeros().min(31), (mask >> mask.trailing_zeros().min(31)).count_ones()))
+            .collect();
+
+        let mut content = vec![0u8; row_length * image.height() as usize];
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let value = (0..3)
+                .map(|channel| {
+                    let (shift, bits) = channels[channel];
+                    let maximum = (1u64 << bits) - 1;
+                    ((pixel.0[channel] as u64 * maximum / 255) as u32) << shift
+                })
+                .fold(0u32, |value, channel_value| value | channel_value);
+
+            let bytes = if pixel_format.is_msb_first {
+                value.to_be_bytes()[4 - pixel_format.bytes_per_pixel..].to_vec()
+            } else {
+                value.to_le_bytes()[..pixel_format.bytes_per_pixel].to_vec()
+            };
+            let offset = y as usize * row_length + x as usize * pixel_format.bytes_per_pixel;
+            content[offset..offset + pixel_format.bytes_per_pixel].copy_from_slice(&bytes);
+        }
+
+        (content, row_length)
+    }
+
+    /// Uploads the pixels into a new pixmap and makes it the root window background.
+    fn apply(connection: &impl Connection,
+             screen: &Screen,
+             pixel_format: &PixelFormat,
+             content: &[u8],
+             row_length: usize) -> Result<(), ReplyOrIdError> {
+        let (width, height) = (screen.width_in_pixels, screen.height_in_pixels);
+
+        let pixmap = connection.generate_id()?;
+        connection.create_pixmap(pixel_format.depth, pixmap, screen.root, width, height)?;
+        let gc = connection.generate_id()?;
+        connection.create_gc(gc, pixmap, &CreateGCAux::new())?;
+
+        // The image is split in bands of rows, to fit in the maximum request length
+        let rows_per_request = ((connection.maximum_request_bytes() - 64) / row_length).clamp(1, height as usize);
+        for (band_idx, band) in content.chunks(rows_per_request * row_length).enumerate() {
+            connection.put_image(
+                ImageFormat::Z_PIXMAP,
+                pixmap,
+                gc,
+                width,
+                (band.len() / row_length) as u16,
+                0,
+                (band_idx * rows_per_request) as i16,
+                0,
+                pixel_format.depth,
+                band)?;
+        }
+        connection.free_gc(gc)?;
+
+        // Esetroot convention: when both properties match, the previous pixmap is owned by the client that set it
+        let mut atoms = Vec::new();
+        for property_name in ROOT_PIXMAP_PROPERTIES {
+            atoms.push(connection.intern_atom(false, property_name.as_bytes())?.reply()?.atom);
+        }
+        let mut previous_pixmaps = Vec::new();
+        for atom in &atoms {
+            let reply = connection.get_property(false, screen.root, *atom, AtomEnum::PIXMAP, 0, 1)?.reply()?;
+            previous_pixmaps.push(reply.value32().and_then(|mut values| values.next()));
+        }
+        if let [Some(root_pixmap), Some(esetroot_pixmap)] = previous_pixmaps[..] {
+            if root_pixmap == esetroot_pixmap {
+                connection.kill_client(root_pixmap)?;
+            }
+        }
+
+        for atom in &atoms {
+            connection.change_property32(PropMode::REPLACE, screen.root, *atom, AtomEnum::PIXMAP, &[pixmap])?;
+        }
+        connection.change_window_attributes(screen.root, &ChangeWindowAttributesAux::new().background_pixmap(pixmap))?;
+        connection.clear_area(false, screen.root, 0, 0, 0, 0)?;
+
+        // The pixmap must outlive the connection
+        connection.set_close_down_mode(CloseDown::RETAIN_PERMANENT)?;
+        connection.sync()?;
+
+        Ok(())
+    }
+}
+
+impl WallpaperSetter for X11WallpaperSetter {
+    fn name(&self) -> &'static str {
+        "X11"
+    }
+
+    fn set_wallpaper(&self, wallpaper_filename: &str, _dark_wallpaper_filename: Option<&str>) -> Result<(), String> {
+        let (connection, screen_num) = match x11rb::connect(None) {
+            Err(error) => return Err(format!("Can't connect to X11 display: {:?}", error)),
+            Ok(connection) => connection,
+        };
+        let screen = &connection.setup().roots[screen_num];
+        let pixel_format = X11WallpaperSetter::pixel_format(connection.setup(), screen)?;
+
+        let image = match image::open(wallpaper_filename) {
+            Err(error) => return Err(format!("Can't open wallpaper {:?}: {:?}", wallpaper_filename, error)),
+            Ok(image) => image,
+        };
+
+        let monitors = X11WallpaperSetter::monitors(&connection, screen);
+        let dimensions = (screen.width_in_pixels as u32, screen.height_in_pixels as u32);
+        let screen_image = self.render(&image, dimensions, &monitors);
+        let (content, row_length) = X11WallpaperSetter::encode(&screen_image, &pixel_format);
+
+        match X11WallpaperSetter::apply(&connection, screen, &pixel_format, &content, row_length) {
+            Err(error) => Err(format!("Can't change X11 root window wallpaper: {:?}", error)),
+            Ok(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    use super::{PixelFormat, X11WallpaperSetter};
+    use crate::bingwallpaper::configuration::WallpaperFitMode;
+
+    #[test]
+    fn encode_24_bits_lsb_first() {
+        let pixel_format = PixelFormat {
+            depth: 24,
+            bytes_per_pixel: 4,
+            scanline_pad: 4,
+            masks: [0xFF0000, 0x00FF00, 0x0000FF],
+            is_msb_first: false,
+        };
+        let image = RgbImage::from_fn(2, 1, |x, _| if x == 0 { Rgb([0x12, 0x34, 0x56]) } else { Rgb([255, 0, 128]) });
+
+        let (content, row_length) = X11WallpaperSetter::encode(&image, &pixel_format);
+        assert_eq!(row_length, 8);
+        assert_eq!(content, vec![0x56, 0x34, 0x12, 0x00, 0x80, 0x00, 0xFF, 0x00]);
+    }
+
+    #[test]
+    fn encode_16_bits_msb_first_with_row_padding() {
+        // 5 bits of red, 6 of green, 5 of blue; rows padded to 4 bytes
+        let pixel_format = PixelFormat {
+            depth: 16,
+            bytes_per_pixel: 2,
+            scanline_pad: 4,
+            masks: [0xF800, 0x07E0, 0x001F],
+            is_msb_first: true,
+        };
+        let image = RgbImage::from_fn(3, 2, |x, y| match (x, y) {
+            (0, 0) => Rgb([255, 0, 0]),
+            (1, 0) => Rgb([0, 255, 0]),
+            (2, 0) => Rgb([0, 0, 255]),
+            _ => Rgb([255, 255, 255]),
+        });
+
+        let (content, row_length) = X11WallpaperSetter::encode(&image, &pixel_format);
+        assert_eq!(row_length, 8);
+        assert_eq!(content.len(), 16);
+        assert_eq!(&content[..8], &[0xF8, 0x00, 0x07, 0xE0, 0x00, 0x1F, 0x00, 0x00]);
+        assert_eq!(&content[8..], &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn render_each_monitor() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 2, Rgb([200, 100, 50])));
+        let monitors = [(0, 0, 8, 4), (8, 0, 4, 4)];
+
+        // Zoom covers each monitor, fit leaves borders around the image
+        let screen_image = X11WallpaperSetter::new(None).render(&image, (12, 4), &monitors);
+        assert!(screen_image.pixels().all(|pixel| *pixel == Rgb([200, 100, 50])));
+
+        let screen_image = X11WallpaperSetter::new(Some(WallpaperFitMode::Fit)).render(&image, (12, 4), &monitors);
+        assert_eq!(*screen_image.get_pixel(4, 2), Rgb([200, 100, 50]));
+        assert_eq!(*screen_image.get_pixel(10, 0), Rgb([0, 0, 0]));
+        assert_eq!(*screen_image.get_pixel(10, 2), Rgb([200, 100, 50]));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::bingwallpaper::configuration::WallpaperFitMode;
+use crate::bingwallpaper::wallpapersetter::{DesktopCommand, WallpaperSetter};
+
+/// Applies the wallpaper on XFCE with `xfconf-query`, to every monitor and workspace.
+///
+/// xfdesktop doesn't reload an image applied again under the same location: each image is applied under a unique one.
+pub struct XfceWallpaperSetter {
+    fit_mode: Option<WallpaperFitMode>,
+}
+
+impl XfceWallpaperSetter {
+    /// Creates a new instance.
+    ///
+    /// # Arguments
+    /// * `fit_mode` - How the wallpaper fills the screen, unchanged if not specified
+    pub fn new(fit_mode: Option<WallpaperFitMode>) -> XfceWallpaperSetter {
+        XfceWallpaperSetter { fit_mode }
+    }
+
+    /// Returns the `image-style` value of a fit mode.
+    fn image_style(fit_mode: WallpaperFitMode) -> i32 {
+        match fit_mode {
+            WallpaperFitMode::Center => 1,
+            WallpaperFitMode::Tile => 2,
+            WallpaperFitMode::Stretch => 3,
+            WallpaperFitMode::Fit => 4,
+            WallpaperFitMode::Zoom => 5,
+            WallpaperFitMode::Span => 6,
+        }
+    }
+
+    /// Sets a property of the XFCE desktop, creating it if needed.
+    fn set_property(property: &str, value_type: &str, value: &str) -> Result<(), String> {
+        DesktopCommand::run(DesktopCommand::command("xfconf-query")
+            .args(["--channel", "xfce4-desktop"])
+            .args(["--property", property])
+            .args(["--create", "--type", value_type])
+            .args(["--set", value]))
+    }
+}
+
+impl WallpaperSetter for XfceWallpaperSetter {
+    fn name(&self) -> &'static str {
+        "XFCE"
+    }
+
+    fn set_wallpaper(&self, wallpaper_filename: &str, _dark_wallpaper_filename: Option<&str>) -> Result<(), String> {
+        // One "last-image" property per monitor and workspace (ie: "/backdrop/screen0/monitoreDP-1/workspace0/last-image")
+        let properties = DesktopCommand::output(DesktopCommand::command("xfconf-query")
+            .args(["--channel", "xfce4-desktop"])
+            .arg("--list"))?;
+        let image_properties: Vec<&str> = properties
+            .lines()
+            .map(|property| property.trim())
+            .filter(|property| property.starts_with("/backdrop/") && property.ends_with("/last-image"))
+            .collect();
+
+        if image_properties.is_empty() {
+            return Err("Can't find any XFCE backdrop, the desktop must have been started once".to_string());
+        }
+
+        let wallpaper_filename = DesktopCommand::unique_filename(wallpaper_filename)?;
+        for image_property in image_properties {
+            XfceWallpaperSetter::set_property(image_property, "string", &wallpaper_filename)?;
+
+            if let Some(fit_mode) = self.fit_mode {
+                let style_property = format!("{}/image-style", image_property.trim_end_matches("/last-image"));
+                let image_style = XfceWallpaperSetter::image_style(fit_mode).to_string();
+                XfceWallpaperSetter::set_property(&style_property, "int", &image_style)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use crate::bingwallpaper::configuration::WallpaperFitMode;
+    use crate::bingwallpaper::wallpapersetter::{DesktopCommand, MateWallpaperSetter, WallpaperSetter, XfceWallpaperSetter};
+
+    #[test]
+    fn xfce_and_mate_command_lines() {
+        let directory = env::temp_dir().join(format!("bingwallpaper-stubs-{}", process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let properties = [
+            "/backdrop/screen0/monitorHDMI-1/workspace0/color-style",
+            "/backdrop/screen0/monitorHDMI-1/workspace0/last-image",
+        ];
+        DesktopCommand::write_stub(&directory, "xfconf-query", &properties.join("\n"));
+        DesktopCommand::write_stub(&directory, "gsettings", "");
+        let wallpaper_filename = directory.join("bing wallpaper.jpg").to_string_lossy().to_string();
+        fs::write(&wallpaper_filename, b"jpeg").unwrap();
+
+        DesktopCommand::set_stub_directory(Some(directory.clone()));
+
+        XfceWallpaperSetter::new(Some(WallpaperFitMode::Zoom)).set_wallpaper(&wallpaper_filename, None).unwrap();
+        let xfce_calls = fs::read_to_string(directory.join("calls")).unwrap();
+        fs::remove_file(directory.join("calls")).unwrap();
+
+        MateWallpaperSetter::new(Some(WallpaperFitMode::Fit)).set_wallpaper(&wallpaper_filename, None).unwrap();
+        let mate_calls = fs::read_to_string(directory.join("calls")).unwrap();
+
+        DesktopCommand::set_stub_directory(None);
+
+        // XFCE gets a unique location, so that xfdesktop reloads the image
+        let xfce_calls: Vec<&str> = xfce_calls.lines().collect();
+        assert_eq!(xfce_calls.len(), 3);
+        assert_eq!(xfce_calls[0], "--channel xfce4-desktop --list");
+        let last_image_call = "--channel xfce4-desktop --property /backdrop/screen0/monitorHDMI-1/workspace0/last-image \
+            --create --type string --set ";
+        assert!(xfce_calls[1].starts_with(&format!("{}{}/._bing wallpaper.jpg_", last_image_call, directory.display())));
+        assert_eq!(
+            xfce_calls[2],
+            "--channel xfce4-desktop --property /backdrop/screen0/monitorHDMI-1/workspace0/image-style \
+            --create --type int --set 5");
+
+        assert_eq!(
+            mate_calls,
+            format!(
+                "set org.mate.background picture-filename {}\nset org.mate.background picture-options scaled\n",
+                wallpaper_filename));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -56,7 +56,11 @@ fn main() {
     let bing_wallpaper_changer = BingWallpaperChanger::new(config);
 
     // Run
-    if args.must_loop {
+    if args.apply_greeter {
+        if let Err(error) = bing_wallpaper_changer.try_apply_greeter(args.greeter_root.as_deref()) {
+            panic!("Can't apply greeter background: {:?}", error);
+        }
+    } else if args.must_loop {
         let thread_handle: JoinHandle<()> = thread::Builder::new().name("bingwallpaper".to_string()).spawn(move || {
             loop {
                 if args.download_only {
@@ -67,7 +71,15 @@ fn main() {
                     println!("Can't change wallpaper: {:?}", error);
                 }
 
-                sleep(Duration::from_secs(sleep_duration_sec.unwrap_or(900)));
+                // Wakes up on time to switch between the day and night wallpapers
+                let mut sleep_duration = Duration::from_secs(sleep_duration_sec.unwrap_or(900));
+                if !args.download_only {
+                    if let Some(time_until_next_sun_event) = bing_wallpaper_changer.time_until_next_sun_event() {
+                        sleep_duration = sleep_duration.min(time_until_next_sun_event);
+                    }
+                }
+
+                sleep(sleep_duration);
             }
         }).unwrap();
 